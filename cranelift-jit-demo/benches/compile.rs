@@ -0,0 +1,210 @@
+//! Compile-time benchmarks of the stages of the Kaleidoscope pipeline on generated programs of
+//! increasing size, comparing the Cranelift backend with the LLVM backend.
+//!
+//! The `codegen` benchmarks compile every function of a program to machine code in a new
+//! module, from the AST: Cranelift builds its IR and generates the code of each function, while
+//! LLVM generates its IR, runs the function passes of the JIT and emits an object file. The `jit`
+//! benchmarks measure the whole work of a JIT on a program: a new JIT defines every function,
+//! then evaluates the top-level expression, which is when the ORC JIT of LLVM lazily compiles the
+//! functions it calls, and when Cranelift finalizes the code of the expression.
+//!
+//! The LLVM backend is only benchmarked with the `llvm` feature:
+//! `cargo bench --features llvm`.
+
+use std::fmt::Write;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use cranelift_jit_demo::kaleido::CraneliftJit;
+use kaleidoscope::backend::Backend;
+use kaleidoscope::lexer::{Lexer, Token};
+use kaleidoscope::operator::OperatorTable;
+use kaleidoscope::optimizer::{self, OptimizeOptions};
+use kaleidoscope::parser::Function;
+use kaleidoscope::syntax;
+
+/// Numbers of functions of the generated programs.
+const SIZES: &[usize] = &[ 10, 100, 500 ];
+
+/// Returns a program made of the given number of functions, each of them calling the
+/// previous one, followed by a top-level expression calling the last one.
+fn program(size: usize) -> String {
+    let mut source = String::from("def f0(x, y) x * y\n");
+
+    for i in 1..size {
+        writeln!(
+            source,
+            "def f{i}(x, y) var a = x in (for j = 0, j < 4 in a = a * 0.5 + y) + (if a < y then f{prev}(a, y) else f{prev}(y, a - 1))",
+            i = i,
+            prev = i - 1
+        ).unwrap();
+    }
+
+    writeln!(source, "f{}(1, 2)", size - 1).unwrap();
+    source
+}
+
+fn parse(source: &str) -> Vec<Function> {
+    syntax::parse(source, &OperatorTable::new()).functions().expect("Cannot parse program.")
+}
+
+/// Defines the functions of the given program in the given backend, returning the top-level
+/// expression which ends it.
+fn define<'a>(backend: &mut dyn Backend, functions: &'a [Function]) -> &'a Function {
+    let (expression, definitions) = functions.split_last().expect("Empty program.");
+
+    for function in definitions {
+        backend.eval(function).expect("Cannot define function.");
+    }
+
+    expression
+}
+
+fn frontend(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frontend");
+
+    for &size in SIZES {
+        let source = program(size);
+
+        group.bench_with_input(BenchmarkId::new("lex", size), &source, |b, source| {
+            b.iter(|| Lexer::new(source).collect::<Vec<Token>>())
+        });
+        group.bench_with_input(BenchmarkId::new("parse", size), &source, |b, source| {
+            b.iter(|| parse(source))
+        });
+    }
+
+    group.finish();
+}
+
+fn optimize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("optimize");
+    let options = OptimizeOptions::default();
+
+    for &size in SIZES {
+        let functions = parse(&program(size));
+
+        group.bench_with_input(BenchmarkId::new("ast", size), &functions, |b, functions| {
+            b.iter(|| {
+                functions.iter().enumerate()
+                    .map(|(index, function)| optimizer::optimize(function.clone(), &functions[..index], &options))
+                    .collect::<Vec<Function>>()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn codegen(c: &mut Criterion) {
+    let mut group = c.benchmark_group("codegen");
+
+    group.sample_size(10);
+
+    for &size in SIZES {
+        let functions = parse(&program(size));
+
+        for &optimize in &[ false, true ] {
+            let name = if optimize { "cranelift-speed" } else { "cranelift-none" };
+            let mut jit = CraneliftJit::new(optimize);
+
+            define(&mut jit, &functions);
+
+            group.bench_function(BenchmarkId::new(name, size), |b| {
+                b.iter(|| {
+                    let mut module = jit.create_module().unwrap();
+
+                    for function in functions.iter().filter(|function| function.body.is_some()) {
+                        jit.compile(&mut module, function).unwrap();
+                    }
+
+                    // none of the compiled functions was called
+                    unsafe { module.free_memory() };
+                })
+            });
+        }
+
+        #[cfg(feature = "llvm")]
+        for &(name, optimization) in llvm::LEVELS {
+            group.bench_function(BenchmarkId::new(name, size), |b| llvm::codegen(b, &functions, optimization));
+        }
+    }
+
+    group.finish();
+}
+
+fn jit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("jit");
+
+    group.sample_size(10);
+
+    for &size in SIZES {
+        let functions = parse(&program(size));
+
+        for &optimize in &[ false, true ] {
+            let name = if optimize { "cranelift-speed" } else { "cranelift-none" };
+
+            group.bench_function(BenchmarkId::new(name, size), |b| {
+                b.iter_batched(|| CraneliftJit::new(optimize), |mut jit| {
+                    let expression = define(&mut jit, &functions);
+
+                    jit.eval(expression).unwrap();
+                    jit
+                }, BatchSize::PerIteration)
+            });
+        }
+
+        #[cfg(feature = "llvm")]
+        for &(name, optimization) in llvm::LEVELS {
+            let context = inkwell::context::Context::create();
+
+            group.bench_function(BenchmarkId::new(name, size), |b| {
+                b.iter_batched(|| kaleidoscope::jit::Jit::new(&context, optimization), |mut jit| {
+                    let expression = define(&mut jit, &functions);
+
+                    jit.eval(expression).unwrap();
+                    jit
+                }, BatchSize::PerIteration)
+            });
+        }
+    }
+
+    group.finish();
+}
+
+#[cfg(feature = "llvm")]
+mod llvm {
+    use criterion::Bencher;
+    use inkwell::OptimizationLevel;
+    use inkwell::context::Context;
+    use inkwell::targets::FileType;
+    use kaleidoscope::compiler::Compiler;
+    use kaleidoscope::jit;
+    use kaleidoscope::parser::Function;
+
+    /// Optimization levels of the LLVM backend, with their name.
+    pub const LEVELS: &[(&str, OptimizationLevel)] = &[
+        ("llvm-none", OptimizationLevel::None),
+        ("llvm-default", OptimizationLevel::Default)
+    ];
+
+    /// Compiles the given functions to LLVM IR, with the function passes used by the JIT at the
+    /// given optimization level, then to machine code, like the JIT does.
+    pub fn codegen(b: &mut Bencher, functions: &[Function], optimization: OptimizationLevel) {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let machine = jit::host_target_machine(optimization).unwrap();
+
+        b.iter(|| {
+            let module = context.create_module("bench");
+            let fpm = jit::function_passes(&module, optimization);
+
+            for function in functions {
+                Compiler::compile(&context, &builder, &fpm, &module, function, None).unwrap();
+            }
+
+            machine.write_to_memory_buffer(&module, FileType::Object).unwrap()
+        })
+    }
+}
+
+criterion_group!(benches, frontend, optimize, codegen, jit);
+criterion_main!(benches);
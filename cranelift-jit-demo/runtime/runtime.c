@@ -0,0 +1,25 @@
+/*
+ * Runtime of the programs of the toy language compiled to object files, which provides
+ * the host functions of the JIT. The program must define a function `main()`, which is the
+ * entry point of the executable and whose result is its exit status.
+ */
+
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+/* Prints the given value on its own line, and returns it. */
+int64_t println(int64_t value) {
+    printf("%lld\n", (long long) value);
+    return value;
+}
+
+/* Allocates the given number of zeroed bytes, returning their address or 0 if they could
+ * not be allocated. The memory is never freed. */
+int64_t alloc(int64_t size) {
+    if (size < 0) {
+        return 0;
+    }
+
+    return (int64_t) (intptr_t) calloc(size > 0 ? (size_t) size : 1, 1);
+}
@@ -0,0 +1,112 @@
+//! Ahead-of-time compilation of the toy language of the `frontend` module to object files
+//! with `cranelift-object`, as an alternative to the JIT which does not depend on LLVM.
+//!
+//! The functions of the compiled programs are exported with their name, and call the host
+//! functions of the JIT as external symbols. The object files are linked into executables
+//! with the runtime of `runtime/runtime.c`, which defines these host functions, by the
+//! system C compiler.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use cranelift::prelude::*;
+use cranelift_module::{FuncId, Module};
+use cranelift_object::{ObjectBuilder, ObjectModule};
+use crate::compiler::{self, CompileError};
+
+/// Source code of the runtime linked with the object files.
+pub const RUNTIME: &str = include_str!("../runtime/runtime.c");
+
+/// Defines a compiler of the functions of one or more programs into a single object file.
+pub struct ObjectCompiler {
+    module: ObjectModule,
+    symbols: HashSet<String>,
+    functions: HashMap<String, FuncId>
+}
+
+impl ObjectCompiler {
+
+    /// Creates a compiler of an object file with the given name for the host machine,
+    /// whose compiled functions can call the functions of the runtime.
+    pub fn new(name: &str) -> Result<ObjectCompiler, CompileError> {
+        let mut flags = settings::builder();
+
+        // the system C compiler links position independent executables by default
+        flags.set("opt_level", "speed").expect("Unknown Cranelift setting.");
+        flags.set("is_pic", "true").expect("Unknown Cranelift setting.");
+
+        let isa = cranelift_native::builder()
+            .map_err(|_| CompileError::Compilation("Could not create compiler for the host machine."))?
+            .finish(settings::Flags::new(flags))
+            .map_err(|_| CompileError::Compilation("Could not create compiler for the host machine."))?;
+        let builder = ObjectBuilder::new(isa, name, cranelift_module::default_libcall_names())
+            .map_err(|_| CompileError::Compilation("Could not create compiler for the host machine."))?;
+
+        let mut module = ObjectModule::new(builder);
+
+        compiler::declare_builtins(&mut module)?;
+
+        Ok(ObjectCompiler {
+            module,
+            symbols: compiler::BUILTIN_FUNCTIONS.iter().map(|(name, _)| name.to_string()).collect(),
+            functions: HashMap::new()
+        })
+    }
+
+    /// Defines a read-only data object with the given name, containing the given string
+    /// followed by a NUL character.
+    pub fn define_string(&mut self, name: &str, value: &str) -> Result<(), CompileError> {
+        let mut contents = value.as_bytes().to_vec();
+
+        contents.push(0);
+        compiler::define_data(&mut self.module, name, contents, false).map(|_| ())
+    }
+
+    /// Defines a global variable with the given name and initial value, which can be read
+    /// and assigned by compiled functions.
+    pub fn define_global(&mut self, name: &str, value: i64) -> Result<(), CompileError> {
+        compiler::define_data(&mut self.module, name, value.to_ne_bytes().to_vec(), true).map(|_| ())
+    }
+
+    /// Compiles the functions of the given program, which can then be called by the
+    /// functions of the following programs.
+    pub fn compile(&mut self, source: &str) -> Result<(), CompileError> {
+        compiler::compile_program(&mut self.module, source, &mut self.functions, &self.symbols, true)
+    }
+
+    /// Returns the number of parameters of the compiled function with the given name.
+    pub fn arity(&self, name: &str) -> Option<usize> {
+        let id = *self.functions.get(name)?;
+
+        Some(self.module.declarations().get_function_decl(id).signature.params.len())
+    }
+
+    /// Returns the content of the object file containing the compiled functions.
+    pub fn finish(self) -> Result<Vec<u8>, CompileError> {
+        self.module.finish().emit().map_err(|_| CompileError::Compilation("Could not emit object file."))
+    }
+}
+
+/// Links the given object file with the runtime into an executable at the given path, using
+/// the C compiler given by the `CC` environment variable, or `cc`.
+pub fn link(object: &Path, output: &Path) -> io::Result<()> {
+    let mut runtime_name = output.file_name().unwrap_or_default().to_os_string();
+
+    runtime_name.push(".runtime.c");
+
+    let runtime = output.with_file_name(runtime_name);
+    let cc = std::env::var_os("CC").unwrap_or_else(|| "cc".into());
+
+    fs::write(&runtime, RUNTIME)?;
+
+    let status = Command::new(cc).arg(object).arg(&runtime).arg("-o").arg(output).status();
+
+    fs::remove_file(&runtime)?;
+
+    match status? {
+        status if status.success() => Ok(()),
+        status => Err(io::Error::other(format!("The C compiler failed with {}.", status)))
+    }
+}
@@ -0,0 +1,151 @@
+use std::io::{self, Write};
+use cranelift_jit_demo::kaleido::CraneliftJit;
+use kaleidoscope::backend::Backend;
+use kaleidoscope::lexer::{Lexer, Token};
+use kaleidoscope::optimizer::{self, OptimizeOptions};
+use kaleidoscope::runtime;
+use kaleidoscope::session::Session;
+
+// macro used to print & flush without printing a new line
+macro_rules! print_flush {
+    ( $( $x:expr ),* ) => {
+        print!( $($x, )* );
+        std::io::stdout().flush().expect("Could not flush to standard output.");
+    };
+}
+
+/// Defines the options of the REPL, set from the command line.
+struct Options {
+    display_lexer_output: bool,
+    display_parser_output: bool,
+    display_compiler_output: bool,
+    /// Whether to evaluate the prelude of operators at the start of the session.
+    prelude: bool,
+    optimize: bool,
+    optimize_options: OptimizeOptions
+}
+
+/// Runs the REPL, compiling each input with Cranelift and executing it with its JIT.
+fn repl(mut options: Options, backend: &mut dyn Backend) {
+    let mut session = Session::new();
+
+    if let Err(err) = runtime::declare(&mut session, backend) {
+        eprintln!("!> Could not declare the builtin functions: {}", err);
+    }
+
+    if options.prelude {
+        if let Err(err) = runtime::load_prelude(&mut session, backend) {
+            eprintln!("!> Could not load the prelude: {}", err);
+        }
+    }
+
+    loop {
+        println!();
+        print_flush!("?> ");
+
+        // Read input from stdin
+        let mut input = String::new();
+
+        if io::stdin().read_line(&mut input).expect("Could not read from standard input.") == 0 {
+            break;
+        }
+
+        if input.starts_with("exit") || input.starts_with("quit") {
+            break;
+        } else if input.chars().all(char::is_whitespace) {
+            continue;
+        } else if input.trim() == ":opt" {
+            options.optimize = !options.optimize;
+            println!("-> AST optimizations {}.", if options.optimize { "enabled" } else { "disabled" });
+            continue;
+        }
+
+        // Parse and (optionally) display input
+        if options.display_lexer_output {
+            println!("-> Attempting to parse lexed input: \n{:?}\n", Lexer::new(input.as_str()).collect::<Vec<Token>>());
+        }
+
+        let fun = match session.parse(input.as_str()) {
+            Ok(fun) => fun,
+            Err(err) => {
+                println!("!> Error parsing expression: {}", err);
+                continue;
+            }
+        };
+
+        if options.display_parser_output {
+            if fun.is_anon {
+                println!("-> Expression parsed: \n{}\n", fun.pretty(session.operators()));
+            } else {
+                println!("-> Function parsed: \n{}\n", fun.pretty(session.operators()));
+            }
+        }
+
+        let compiled = if options.optimize {
+            let optimized = optimizer::optimize(fun.clone(), session.functions(), &options.optimize_options);
+
+            if options.display_parser_output {
+                println!("-> Optimized to: \n{}\n", optimized.pretty(session.operators()));
+            }
+
+            optimized
+        } else {
+            fun.clone()
+        };
+
+        let result = backend.eval(&compiled);
+
+        if options.display_compiler_output {
+            if let Some(ir) = backend.ir() {
+                println!("-> Expression compiled to IR:\n{}", ir);
+            }
+        }
+
+        match result {
+            Ok(result) => {
+                // only add it now to ensure it is correct
+                if let Err(err) = session.define(fun) {
+                    println!("!> {}", err);
+                }
+
+                if let Some(result) = result {
+                    println!("=> {}", result);
+                }
+            },
+            Err(err) => println!("!> {}", err)
+        }
+    }
+}
+
+/// Entry point of the program; acts as the `kaleido` REPL, using the Cranelift backend.
+pub fn main() {
+    let mut cranelift_optimize = true;
+    let mut options = Options {
+        display_lexer_output: false,
+        display_parser_output: false,
+        display_compiler_output: false,
+        prelude: true,
+        optimize: false,
+        optimize_options: OptimizeOptions::default()
+    };
+
+    for arg in std::env::args() {
+        match arg.as_str() {
+            "--dl" => options.display_lexer_output = true,
+            "--dp" => options.display_parser_output = true,
+            "--dc" => options.display_compiler_output = true,
+            "--opt" => options.optimize = true,
+            "--no-prelude" => options.prelude = false,
+            "--no-cranelift-opt" => cranelift_optimize = false,
+            arg if arg.starts_with("--unroll=") => match arg["--unroll=".len()..].parse() {
+                Ok(limit) => options.optimize_options.unroll_limit = limit,
+                Err(_) => eprintln!("!> Expected a number after '--unroll='.")
+            },
+            _ => ()
+        }
+    }
+
+    let mut jit = CraneliftJit::new(cranelift_optimize);
+
+    repl(options, &mut jit)
+}
@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use cranelift_jit_demo::aot::{self, ObjectCompiler};
+
+/// Defines the options of the compiler, set from the command line.
+struct Options {
+    input: PathBuf,
+    output: PathBuf,
+    /// Whether to only emit the object file, rather than an executable.
+    object_only: bool
+}
+
+fn parse_options() -> Result<Options, &'static str> {
+    let mut input = None;
+    let mut output = None;
+    let mut object_only = false;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" => object_only = true,
+            "-o" => output = Some(PathBuf::from(args.next().ok_or("Expected a path after '-o'.")?)),
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => return Err("Expected a single input file.")
+        }
+    }
+
+    let input = input.ok_or("Usage: toyc <input> [-c] [-o <output>]")?;
+    let output = output.unwrap_or_else(|| {
+        let output = Path::new(input.file_stem().unwrap_or_default());
+
+        if object_only { output.with_extension("o") } else { output.to_path_buf() }
+    });
+
+    Ok(Options { input, output, object_only })
+}
+
+/// Compiles the input file to an object file, and links it with the runtime unless only
+/// the object file is requested.
+fn run(options: &Options) -> Result<(), String> {
+    let source = fs::read_to_string(&options.input).map_err(|err| format!("Could not read {}: {}", options.input.display(), err))?;
+    let name = options.input.file_stem().unwrap_or_default().to_string_lossy();
+    let mut compiler = ObjectCompiler::new(&name).map_err(|err| err.to_string())?;
+
+    compiler.compile(&source).map_err(|err| err.to_string())?;
+
+    if !options.object_only && compiler.arity("main") != Some(0) {
+        return Err("Expected a function 'main' without parameters.".to_string());
+    }
+
+    let object = compiler.finish().map_err(|err| err.to_string())?;
+
+    if options.object_only {
+        return fs::write(&options.output, object).map_err(|err| format!("Could not write {}: {}", options.output.display(), err));
+    }
+
+    let mut object_name = options.output.file_name().unwrap_or_default().to_os_string();
+
+    object_name.push(".o");
+
+    let object_path = options.output.with_file_name(object_name);
+
+    fs::write(&object_path, object).map_err(|err| format!("Could not write {}: {}", object_path.display(), err))?;
+
+    let linked = aot::link(&object_path, &options.output);
+    let _ = fs::remove_file(&object_path);
+
+    linked.map_err(|err| format!("Could not link {}: {}", options.output.display(), err))
+}
+
+/// Entry point of the program; compiles a program of the toy language of the Cranelift demo
+/// to an executable.
+pub fn main() {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("!> {}", err);
+            process::exit(2);
+        }
+    };
+
+    if let Err(err) = run(&options) {
+        eprintln!("!> {}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,415 @@
+//! Translation of the toy language of the `frontend` module to Cranelift IR, shared by the
+//! JIT and the object file compiler.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use cranelift::prelude::*;
+use cranelift_module::{DataDescription, DataId, FuncId, FuncOrDataId, Linkage, Module};
+use crate::frontend::{self, BinaryOp, Expr, Function, ParseError};
+
+/// Defines an error encountered while compiling a program.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompileError {
+    Parse(ParseError),
+    Compilation(&'static str)
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Parse(err) => write!(f, "Parsing error at {}", err),
+            CompileError::Compilation(err) => f.write_str(err)
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+impl From<ParseError> for CompileError {
+    fn from(err: ParseError) -> CompileError {
+        CompileError::Parse(err)
+    }
+}
+
+/// Host functions provided both to the JIT and to the object files, with their number of
+/// parameters.
+pub(crate) const BUILTIN_FUNCTIONS: &[(&str, usize)] = &[ ("println", 1), ("alloc", 1) ];
+
+/// Declares the builtin host functions in the given module, so that they are always called
+/// with their number of arguments.
+pub(crate) fn declare_builtins<M: Module>(module: &mut M) -> Result<(), CompileError> {
+    for (name, arg_count) in BUILTIN_FUNCTIONS {
+        let signature = signature(module, *arg_count);
+
+        module.declare_function(name, Linkage::Import, &signature)
+            .map_err(|_| CompileError::Compilation("Could not declare function."))?;
+    }
+
+    Ok(())
+}
+
+/// Returns the signature of a function taking the given number of `i64` arguments.
+pub(crate) fn signature<M: Module>(module: &M, arg_count: usize) -> Signature {
+    let mut signature = module.make_signature();
+
+    signature.params = vec![ AbiParam::new(types::I64); arg_count ];
+    signature.returns.push(AbiParam::new(types::I64));
+    signature
+}
+
+/// Defines a data object with the given name and contents in the given module.
+pub(crate) fn define_data<M: Module>(module: &mut M, name: &str, contents: Vec<u8>, writable: bool) -> Result<DataId, CompileError> {
+    if module.declarations().get_name(name).is_some() {
+        return Err(CompileError::Compilation("Name is already declared."));
+    }
+
+    let id = module.declare_data(name, Linkage::Export, writable, false)
+        .map_err(|_| CompileError::Compilation("Could not declare data object."))?;
+    let mut description = DataDescription::new();
+
+    description.define(contents.into_boxed_slice());
+    description.set_align(8);
+
+    module.define_data(id, &description).map_err(|_| CompileError::Compilation("Could not define data object."))?;
+
+    Ok(id)
+}
+
+/// Compiles the functions of the given program into the given module, adding them to the
+/// given previously compiled functions, which they can call along with the given host
+/// functions.
+///
+/// If `export` is true the functions are exported with their name, so that they can be
+/// linked with other code. Otherwise they can only be called by their identifier, and none
+/// of the functions of a program which cannot be compiled is ever visible.
+pub(crate) fn compile_program<M: Module>(
+    module: &mut M,
+    source: &str,
+    functions: &mut HashMap<String, FuncId>,
+    symbols: &HashSet<String>,
+    export: bool
+) -> Result<(), CompileError> {
+    let program = frontend::parse(source)?;
+    let mut declarations = HashMap::new();
+
+    for function in &program {
+        if functions.contains_key(&function.name) || declarations.contains_key(function.name.as_str()) {
+            return Err(CompileError::Compilation("Function is already defined."));
+        }
+
+        let signature = signature(module, function.params.len());
+        let id = if export {
+            module.declare_function(&function.name, Linkage::Export, &signature)
+        } else {
+            module.declare_anonymous_function(&signature)
+        };
+
+        declarations.insert(function.name.as_str(), id.map_err(|_| CompileError::Compilation("Name is already declared."))?);
+    }
+
+    // translate all the functions before defining any of them, so that a function which
+    // cannot be translated does not leave the module with part of the program
+    let mut contexts = Vec::with_capacity(program.len());
+    let mut builder_context = FunctionBuilderContext::new();
+
+    for function in &program {
+        let mut ctx = module.make_context();
+
+        ctx.func.signature = signature(module, function.params.len());
+
+        let translator = Translator {
+            builder: FunctionBuilder::new(&mut ctx.func, &mut builder_context),
+            module: &mut *module,
+            declarations: &declarations,
+            functions,
+            symbols,
+            variables: HashMap::new()
+        };
+
+        translator.translate(function).map_err(CompileError::Compilation)?;
+        contexts.push((declarations[function.name.as_str()], ctx));
+    }
+
+    for (id, mut ctx) in contexts {
+        module.define_function(id, &mut ctx).map_err(|_| CompileError::Compilation("Invalid generated function."))?;
+    }
+
+    functions.extend(declarations.into_iter().map(|(name, id)| (name.to_string(), id)));
+
+    Ok(())
+}
+
+/// Returns the global variable with the given name, which is a writable data object.
+pub(crate) fn global<M: Module>(module: &M, name: &str) -> Option<DataId> {
+    match module.declarations().get_name(name) {
+        Some(FuncOrDataId::Data(id)) if module.declarations().get_data_decl(id).writable => Some(id),
+        _ => None
+    }
+}
+
+/// Adds the names of the variables assigned by the given statements to the given set.
+fn assigned_variables<'a>(statements: &'a [Expr], variables: &mut HashSet<&'a str>) {
+    for statement in statements {
+        match statement {
+            Expr::Assign(name, _) => {
+                variables.insert(name);
+            },
+            Expr::IfElse(_, then_body, else_body) => {
+                assigned_variables(then_body, variables);
+                assigned_variables(else_body, variables);
+            },
+            Expr::While(_, body) => assigned_variables(body, variables),
+            _ => ()
+        }
+    }
+}
+
+/// Translates a function of the toy language to Cranelift IR.
+struct Translator<'a, M: Module> {
+    builder: FunctionBuilder<'a>,
+    module: &'a mut M,
+    /// Functions of the program being compiled.
+    declarations: &'a HashMap<&'a str, FuncId>,
+    /// Functions compiled before the program.
+    functions: &'a HashMap<String, FuncId>,
+    symbols: &'a HashSet<String>,
+    variables: HashMap<String, Variable>
+}
+
+impl<'a, M: Module> Translator<'a, M> {
+
+    fn translate(mut self, function: &Function) -> Result<(), &'static str> {
+        let entry = self.builder.create_block();
+
+        self.builder.append_block_params_for_function_params(entry);
+        self.builder.switch_to_block(entry);
+        self.builder.seal_block(entry);
+
+        for (index, param) in function.params.iter().enumerate() {
+            let value = self.builder.block_params(entry)[index];
+
+            self.declare(param, value);
+        }
+
+        // the other variables, including the returned one, start at 0
+        let mut locals = HashSet::new();
+
+        locals.insert(function.returns.as_str());
+        assigned_variables(&function.body, &mut locals);
+
+        for name in locals {
+            if !self.variables.contains_key(name) && global(self.module, name).is_none() {
+                let zero = self.builder.ins().iconst(types::I64, 0);
+
+                self.declare(name, zero);
+            }
+        }
+
+        self.compile_statements(&function.body)?;
+
+        let result = self.compile_expr(&Expr::Identifier(function.returns.clone()))?;
+
+        self.builder.ins().return_(&[ result ]);
+        self.builder.finalize();
+
+        Ok(())
+    }
+
+    fn declare(&mut self, name: &str, value: Value) {
+        let var = Variable::new(self.variables.len());
+
+        self.builder.declare_var(var, types::I64);
+        self.builder.def_var(var, value);
+        self.variables.insert(name.to_string(), var);
+    }
+
+    fn data_address(&mut self, id: DataId) -> Value {
+        let pointer_type = self.module.target_config().pointer_type();
+        let global = self.module.declare_data_in_func(id, self.builder.func);
+
+        self.builder.ins().symbol_value(pointer_type, global)
+    }
+
+    /// Divides `lhs` by `rhs` without trapping like `sdiv` does: a division by zero gives 0,
+    /// and `i64::MIN / -1` wraps around to `i64::MIN`.
+    fn divide(&mut self, lhs: Value, rhs: Value) -> Value {
+        let is_zero = self.builder.ins().icmp_imm(IntCC::Equal, rhs, 0);
+        let is_minus_one = self.builder.ins().icmp_imm(IntCC::Equal, rhs, -1);
+        let trapping = self.builder.ins().bor(is_zero, is_minus_one);
+        let one = self.builder.ins().iconst(types::I64, 1);
+        let divisor = self.builder.ins().select(trapping, one, rhs);
+        let quotient = self.builder.ins().sdiv(lhs, divisor);
+        let negated = self.builder.ins().ineg(lhs);
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        let quotient = self.builder.ins().select(is_minus_one, negated, quotient);
+
+        self.builder.ins().select(is_zero, zero, quotient)
+    }
+
+    /// Returns the function with the given name and number of parameters, declaring it if
+    /// it is a host function.
+    fn callee(&mut self, name: &str, arg_count: usize) -> Result<FuncId, &'static str> {
+        let id = match self.declarations.get(name).or_else(|| self.functions.get(name)) {
+            Some(id) => *id,
+            None if self.symbols.contains(name) => {
+                let signature = signature(self.module, arg_count);
+
+                // a host function other than the builtins is declared with the number of
+                // arguments of its first call
+                self.module.declare_function(name, Linkage::Import, &signature)
+                    .map_err(|_| "Incorrect number of arguments passed.")?
+            },
+            None => return Err("Unknown function.")
+        };
+
+        if self.module.declarations().get_function_decl(id).signature.params.len() != arg_count {
+            return Err("Incorrect number of arguments passed.");
+        }
+
+        Ok(id)
+    }
+
+    fn compile_statements(&mut self, statements: &[Expr]) -> Result<(), &'static str> {
+        for statement in statements {
+            self.compile_expr(statement)?;
+        }
+
+        Ok(())
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) -> Result<Value, &'static str> {
+        match expr {
+            Expr::Literal(value) => Ok(self.builder.ins().iconst(types::I64, *value)),
+
+            Expr::Str(value) => {
+                let id = self.module.declare_anonymous_data(false, false).map_err(|_| "Could not declare data object.")?;
+                let mut contents = value.as_bytes().to_vec();
+                let mut description = DataDescription::new();
+
+                contents.push(0);
+                description.define(contents.into_boxed_slice());
+                self.module.define_data(id, &description).map_err(|_| "Could not define data object.")?;
+
+                Ok(self.data_address(id))
+            },
+
+            Expr::Identifier(name) => match self.variables.get(name) {
+                Some(var) => Ok(self.builder.use_var(*var)),
+                None => {
+                    let id = global(self.module, name).ok_or("Undefined variable.")?;
+                    let address = self.data_address(id);
+
+                    Ok(self.builder.ins().load(types::I64, MemFlags::trusted(), address, 0))
+                }
+            },
+
+            Expr::GlobalDataAddr(name) => match self.module.declarations().get_name(name) {
+                Some(FuncOrDataId::Data(id)) => Ok(self.data_address(id)),
+                _ => Err("Undefined data object.")
+            },
+
+            Expr::Assign(name, value) => {
+                let value = self.compile_expr(value)?;
+
+                match self.variables.get(name) {
+                    Some(var) => self.builder.def_var(*var, value),
+                    None => {
+                        let id = global(self.module, name).ok_or("Undefined variable.")?;
+                        let address = self.data_address(id);
+
+                        self.builder.ins().store(MemFlags::trusted(), value, address, 0);
+                    }
+                }
+
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            },
+
+            Expr::Binary(op, left, right) => {
+                let lhs = self.compile_expr(left)?;
+                let rhs = self.compile_expr(right)?;
+
+                let cc = match op {
+                    BinaryOp::Add => return Ok(self.builder.ins().iadd(lhs, rhs)),
+                    BinaryOp::Sub => return Ok(self.builder.ins().isub(lhs, rhs)),
+                    BinaryOp::Mul => return Ok(self.builder.ins().imul(lhs, rhs)),
+                    BinaryOp::Div => return Ok(self.divide(lhs, rhs)),
+                    BinaryOp::Eq => IntCC::Equal,
+                    BinaryOp::Ne => IntCC::NotEqual,
+                    BinaryOp::Lt => IntCC::SignedLessThan,
+                    BinaryOp::Le => IntCC::SignedLessThanOrEqual,
+                    BinaryOp::Gt => IntCC::SignedGreaterThan,
+                    BinaryOp::Ge => IntCC::SignedGreaterThanOrEqual
+                };
+
+                let cmp = self.builder.ins().icmp(cc, lhs, rhs);
+
+                Ok(self.builder.ins().uextend(types::I64, cmp))
+            },
+
+            Expr::IfElse(cond, then_body, else_body) => {
+                let cond = self.compile_expr(cond)?;
+
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                let merge_block = self.builder.create_block();
+
+                self.builder.ins().brif(cond, then_block, &[], else_block, &[]);
+
+                self.builder.switch_to_block(then_block);
+                self.builder.seal_block(then_block);
+                self.compile_statements(then_body)?;
+                self.builder.ins().jump(merge_block, &[]);
+
+                self.builder.switch_to_block(else_block);
+                self.builder.seal_block(else_block);
+                self.compile_statements(else_body)?;
+                self.builder.ins().jump(merge_block, &[]);
+
+                self.builder.switch_to_block(merge_block);
+                self.builder.seal_block(merge_block);
+
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            },
+
+            Expr::While(cond, body) => {
+                let header_block = self.builder.create_block();
+                let body_block = self.builder.create_block();
+                let exit_block = self.builder.create_block();
+
+                self.builder.ins().jump(header_block, &[]);
+                self.builder.switch_to_block(header_block);
+
+                let cond = self.compile_expr(cond)?;
+
+                self.builder.ins().brif(cond, body_block, &[], exit_block, &[]);
+
+                self.builder.switch_to_block(body_block);
+                self.builder.seal_block(body_block);
+                self.compile_statements(body)?;
+                self.builder.ins().jump(header_block, &[]);
+
+                self.builder.switch_to_block(exit_block);
+
+                // the header can only be sealed once the back edge of the loop is known
+                self.builder.seal_block(header_block);
+                self.builder.seal_block(exit_block);
+
+                Ok(self.builder.ins().iconst(types::I64, 0))
+            },
+
+            Expr::Call(name, args) => {
+                let id = self.callee(name, args.len())?;
+                let mut values = Vec::with_capacity(args.len());
+
+                for arg in args {
+                    values.push(self.compile_expr(arg)?);
+                }
+
+                let callee = self.module.declare_func_in_func(id, self.builder.func);
+                let call = self.builder.ins().call(callee, &values);
+
+                Ok(self.builder.inst_results(call)[0])
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Parser of the toy language compiled by the Cranelift JIT demo.
+//!
+//! The language is a small imperative language whose only type is the 64-bit integer:
+//!
+//! ```text
+//! // returns the n-th Fibonacci number
+//! fn fib(n) -> (r) {
+//!     a = 0
+//!     b = 1
+//!     while n > 0 {
+//!         t = a + b
+//!         a = b
+//!         b = t
+//!         n = n - 1
+//!     }
+//!     r = a
+//! }
+//! ```
+//!
+//! A function names its return variable, whose value when the end of its body is reached is
+//! the value returned by the function. Statements end with a new line, variables are declared
+//! by assigning them, and conditions are true when they are not 0. Arithmetic wraps around on
+//! overflow, and division truncates toward zero, a division by zero giving 0, so that no
+//! expression traps.
+//!
+//! A string literal evaluates to the address of a constant NUL-terminated string, and `&name`
+//! to the address of the data object with the given name, which is defined by the host.
+
+use std::fmt;
+
+/// Defines a function of the toy language.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    pub returns: String,
+    pub body: Vec<Expr>
+}
+
+/// Defines an operator of a binary expression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div
+}
+
+/// Defines an expression of the toy language.
+///
+/// Assignments, conditions and loops are expressions so that they can be used as statements,
+/// but they evaluate to 0.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Literal(i64),
+    Str(String),
+    Identifier(String),
+    GlobalDataAddr(String),
+    Assign(String, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    IfElse(Box<Expr>, Vec<Expr>, Vec<Expr>),
+    While(Box<Expr>, Vec<Expr>),
+    Call(String, Vec<Expr>)
+}
+
+/// Defines an error encountered while parsing a program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    /// Line of the error, starting at 1.
+    pub line: usize,
+    /// Column of the error, starting at 1.
+    pub column: usize,
+    /// Description of what was expected at the location of the error.
+    pub expected: String
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: expected {}", self.line, self.column, self.expected)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn binary(op: BinaryOp, left: Expr, right: Expr) -> Expr {
+    Expr::Binary(op, Box::new(left), Box::new(right))
+}
+
+peg::parser!(grammar parser() for str {
+    pub rule program() -> Vec<Function>
+        = functions:function()* __ { functions }
+
+    rule function() -> Function
+        = __ "fn" !ident_char() _ name:identifier() _
+          "(" params:((_ p:identifier() _ { p }) ** ",") ")" _
+          "->" _ "(" _ returns:identifier() _ ")" _
+          body:block()
+          { Function { name, params, returns, body } }
+
+    rule block() -> Vec<Expr>
+        = "{" eol() statements:statement()* __ "}" { statements }
+
+    rule statement() -> Expr
+        = __ e:expression() eol() { e }
+
+    rule expression() -> Expr
+        = if_else()
+        / while_loop()
+        / assignment()
+        / binary_op()
+
+    rule if_else() -> Expr
+        = "if" !ident_char() _ cond:expression() _ then_body:block() else_body:(_ "else" !ident_char() _ b:block() { b })?
+          { Expr::IfElse(Box::new(cond), then_body, else_body.unwrap_or_default()) }
+
+    rule while_loop() -> Expr
+        = "while" !ident_char() _ cond:expression() _ body:block() { Expr::While(Box::new(cond), body) }
+
+    rule assignment() -> Expr
+        = name:identifier() _ "=" !"=" _ value:expression() { Expr::Assign(name, Box::new(value)) }
+
+    rule binary_op() -> Expr = precedence!{
+        a:@ _ "==" _ b:(@) { binary(BinaryOp::Eq, a, b) }
+        a:@ _ "!=" _ b:(@) { binary(BinaryOp::Ne, a, b) }
+        a:@ _ "<=" _ b:(@) { binary(BinaryOp::Le, a, b) }
+        a:@ _ "<" _ b:(@) { binary(BinaryOp::Lt, a, b) }
+        a:@ _ ">=" _ b:(@) { binary(BinaryOp::Ge, a, b) }
+        a:@ _ ">" _ b:(@) { binary(BinaryOp::Gt, a, b) }
+        --
+        a:(@) _ "+" _ b:@ { binary(BinaryOp::Add, a, b) }
+        a:(@) _ "-" _ b:@ { binary(BinaryOp::Sub, a, b) }
+        --
+        a:(@) _ "*" _ b:@ { binary(BinaryOp::Mul, a, b) }
+        a:(@) _ "/" _ b:@ { binary(BinaryOp::Div, a, b) }
+        --
+        name:identifier() _ "(" args:((_ e:expression() _ { e }) ** ",") ")" { Expr::Call(name, args) }
+        name:identifier() { Expr::Identifier(name) }
+        value:literal() { Expr::Literal(value) }
+        value:string() { Expr::Str(value) }
+        "&" _ name:identifier() { Expr::GlobalDataAddr(name) }
+        "(" _ e:expression() _ ")" { e }
+    }
+
+    rule identifier() -> String
+        = quiet!{ !keyword() name:$(['a'..='z' | 'A'..='Z' | '_'] ident_char()*) { name.to_string() } }
+        / expected!("identifier")
+
+    rule keyword()
+        = ("fn" / "if" / "else" / "while") !ident_char()
+
+    rule ident_char()
+        = ['a'..='z' | 'A'..='Z' | '0'..='9' | '_']
+
+    rule literal() -> i64
+        = digits:$(quiet!{ ['0'..='9']+ } / expected!("integer")) {? digits.parse().or(Err("64-bit integer")) }
+
+    rule string() -> String
+        = quiet!{ "\"" value:$((!['"' | '\n'] [_])*) "\"" { value.to_string() } }
+        / expected!("string")
+
+    rule comment()
+        = "//" (!"\n" [_])*
+
+    /// Spaces within a line.
+    rule _()
+        = quiet!{ [' ' | '\t']* }
+
+    /// Spaces, new lines and comments.
+    rule __()
+        = quiet!{ ([' ' | '\t' | '\r' | '\n'] / comment())* }
+
+    /// End of a line, optionally preceded by a comment.
+    rule eol()
+        = _ comment()? "\r"? "\n"
+});
+
+/// Parses the functions of the given program.
+pub fn parse(source: &str) -> Result<Vec<Function>, ParseError> {
+    parser::program(source).map_err(|err| ParseError {
+        line: err.location.line,
+        column: err.location.column,
+        expected: err.expected.to_string()
+    })
+}
@@ -0,0 +1,176 @@
+//! JIT compiling the toy language of the `frontend` module with Cranelift.
+//!
+//! Every value of the toy language is an `i64`, and every function is compiled with the
+//! signature `extern "C" fn(i64, ...) -> i64`. Compiled functions can call the functions
+//! compiled before them, as well as the host functions registered when creating the JIT,
+//! and can use the data objects defined by the host: writable globals are used like
+//! variables, and the address of any data object is given by `&name`.
+
+use std::alloc::{self, Layout};
+use std::collections::{HashMap, HashSet};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{FuncId, Module};
+use crate::compiler::{self, CompileError};
+
+/// Defines the type of a pointer to a compiled function, which is an `extern "C" fn` taking
+/// `ARITY` arguments of type `i64` and returning an `i64`.
+pub trait ToyFunction: Copy {
+    const ARITY: usize;
+
+    /// Converts the address of a compiled function to a function pointer.
+    ///
+    /// # Safety
+    ///
+    /// The address must be the address of a function taking `ARITY` arguments.
+    unsafe fn from_code(code: *const u8) -> Self;
+}
+
+macro_rules! toy_function {
+    ( $arity:expr => $( $arg:ty ),* ) => {
+        impl ToyFunction for extern "C" fn($( $arg ),*) -> i64 {
+            const ARITY: usize = $arity;
+
+            unsafe fn from_code(code: *const u8) -> Self {
+                std::mem::transmute::<*const u8, Self>(code)
+            }
+        }
+    };
+}
+
+toy_function!(0 => );
+toy_function!(1 => i64);
+toy_function!(2 => i64, i64);
+toy_function!(3 => i64, i64, i64);
+toy_function!(4 => i64, i64, i64, i64);
+
+/// Prints the given value on its own line, and returns it.
+extern "C" fn println(value: i64) -> i64 {
+    println!("{}", value);
+    value
+}
+
+/// Allocates the given number of zeroed bytes, returning their address or 0 if they could
+/// not be allocated. The memory is never freed.
+extern "C" fn alloc(size: i64) -> i64 {
+    match Layout::from_size_align(size.max(1) as usize, 8) {
+        Ok(layout) if size >= 0 => unsafe { alloc::alloc_zeroed(layout) as i64 },
+        _ => 0
+    }
+}
+
+/// Defines the JIT of the toy language.
+///
+/// All the functions and data objects are compiled into the same module, whose memory is
+/// never freed, so that the function pointers it returns are always valid.
+pub struct JIT {
+    module: JITModule,
+    /// Names of the host functions which can be called by compiled functions.
+    symbols: HashSet<String>,
+    /// Compiled functions, by name.
+    functions: HashMap<String, FuncId>
+}
+
+impl Default for JIT {
+    fn default() -> JIT {
+        JIT::new()
+    }
+}
+
+impl JIT {
+
+    /// Creates a new JIT, whose compiled functions can call the builtin host functions
+    /// `println(value)` and `alloc(size)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the host machine is not supported by Cranelift.
+    pub fn new() -> JIT {
+        // the builtin functions have the signature of the functions of the toy language
+        unsafe { JIT::with_symbols(&[]) }
+    }
+
+    /// Creates a new JIT, whose compiled functions can call the builtin host functions
+    /// and the given host functions.
+    ///
+    /// # Safety
+    ///
+    /// The address of each symbol must be the address of an `extern "C"` function whose
+    /// arguments and result are `i64` values, and which is called with the number of
+    /// arguments it expects.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the host machine is not supported by Cranelift.
+    pub unsafe fn with_symbols(symbols: &[(&str, *const u8)]) -> JIT {
+        let mut builder = JITBuilder::with_flags(&[ ("opt_level", "speed") ], cranelift_module::default_libcall_names())
+            .expect("Could not create JIT for the host machine.");
+
+        builder.symbol("println", println as extern "C" fn(i64) -> i64 as *const u8);
+        builder.symbol("alloc", alloc as extern "C" fn(i64) -> i64 as *const u8);
+
+        for (name, address) in symbols {
+            builder.symbol(*name, *address);
+        }
+
+        let mut module = JITModule::new(builder);
+
+        compiler::declare_builtins(&mut module).expect("Could not declare builtin functions.");
+
+        JIT {
+            module,
+            symbols: compiler::BUILTIN_FUNCTIONS.iter().map(|(name, _)| *name).chain(symbols.iter().map(|(name, _)| *name)).map(str::to_string).collect(),
+            functions: HashMap::new()
+        }
+    }
+
+    /// Defines a read-only data object with the given name, containing the given string
+    /// followed by a NUL character.
+    pub fn define_string(&mut self, name: &str, value: &str) -> Result<(), CompileError> {
+        let mut contents = value.as_bytes().to_vec();
+
+        contents.push(0);
+        self.define_data(name, contents, false)
+    }
+
+    /// Defines a global variable with the given name and initial value, which can be read
+    /// and assigned by compiled functions.
+    pub fn define_global(&mut self, name: &str, value: i64) -> Result<(), CompileError> {
+        self.define_data(name, value.to_ne_bytes().to_vec(), true)
+    }
+
+    fn define_data(&mut self, name: &str, contents: Vec<u8>, writable: bool) -> Result<(), CompileError> {
+        compiler::define_data(&mut self.module, name, contents, writable)?;
+        self.module.finalize_definitions().map_err(|_| CompileError::Compilation("Could not finalize module."))
+    }
+
+    /// Returns the current value of the global variable with the given name.
+    pub fn global(&self, name: &str) -> Option<i64> {
+        let id = compiler::global(&self.module, name)?;
+        let (address, _) = self.module.get_finalized_data(id);
+
+        // the global was defined with 8 bytes aligned to 8 bytes
+        Some(unsafe { *(address as *const i64) })
+    }
+
+    /// Compiles the functions of the given program, which can then be called by the
+    /// functions of the following programs.
+    ///
+    /// Either all the functions of the program are compiled, or none of them is.
+    pub fn compile(&mut self, source: &str) -> Result<(), CompileError> {
+        compiler::compile_program(&mut self.module, source, &mut self.functions, &self.symbols, false)?;
+        self.module.finalize_definitions().map_err(|_| CompileError::Compilation("Could not finalize module."))
+    }
+
+    /// Returns a pointer to the compiled function with the given name, whose type must
+    /// have the number of parameters of the function.
+    pub fn function<F: ToyFunction>(&self, name: &str) -> Result<F, CompileError> {
+        let id = *self.functions.get(name).ok_or(CompileError::Compilation("Unknown function."))?;
+
+        if self.module.declarations().get_function_decl(id).signature.params.len() != F::ARITY {
+            return Err(CompileError::Compilation("Incorrect number of arguments passed."));
+        }
+
+        // the function was compiled with the signature of `F`, and the module is never freed
+        Ok(unsafe { F::from_code(self.module.get_finalized_function(id)) })
+    }
+}
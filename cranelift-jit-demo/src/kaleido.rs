@@ -0,0 +1,513 @@
+//! Backend compiling the Kaleidoscope AST with Cranelift and executing it with its JIT, as an
+//! alternative to the LLVM backend of the `kaleidoscope` crate.
+//!
+//! The generated code has the exact semantics of the code generated by LLVM: comparisons are
+//! unordered (and thus true when an operand is NaN), conditions are ordered comparisons with 0,
+//! and loops evaluate their body before their end condition.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use cranelift::prelude::*;
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{FuncId, Linkage, Module};
+use kaleidoscope::backend::{self, Backend, BackendError, HostFunction};
+use kaleidoscope::parser::{Expr, Function};
+
+/// Defines a function which can be called by the compiled functions.
+struct Declaration {
+    arg_count: usize,
+    /// Address of the code of the function. Calls load it from this (boxed, and thus stable)
+    /// cell, so that a redefinition is seen by the functions calling it without compiling
+    /// them again.
+    address: Box<Cell<usize>>,
+    /// Module in which the function was compiled, or `None` for a host function.
+    module: Option<JITModule>
+}
+
+/// Defines the Cranelift JIT backend.
+///
+/// Every function is compiled to its own module once, when it is defined. A redefinition only
+/// compiles the new function, and frees the module of the previous one.
+pub struct CraneliftJit {
+    optimize: bool,
+    declarations: HashMap<String, Declaration>,
+    mappings: HashMap<String, usize>,
+    // number of parameters of the externs registered as host functions
+    arities: HashMap<String, usize>,
+    ir: Option<String>
+}
+
+impl CraneliftJit {
+
+    /// Creates a new JIT. Functions are optimized by Cranelift if `optimize` is true.
+    pub fn new(optimize: bool) -> CraneliftJit {
+        CraneliftJit {
+            optimize,
+            declarations: HashMap::new(),
+            mappings: HashMap::new(),
+            arities: HashMap::new(),
+            ir: None
+        }
+    }
+
+    /// Maps the function declared with `extern` and the given name to the host function at
+    /// the given address, instead of resolving it in the symbols of the process.
+    ///
+    /// Unlike `register_extern`, the number of parameters of the declaration is not checked.
+    pub fn add_global_mapping(&mut self, name: &str, address: usize) {
+        self.mappings.insert(name.to_string(), address);
+        self.arities.remove(name);
+    }
+
+    /// Creates an empty module.
+    pub fn create_module(&self) -> Result<JITModule, &'static str> {
+        let opt_level = if self.optimize { "speed" } else { "none" };
+        let builder = JITBuilder::with_flags(&[ ("opt_level", opt_level) ], cranelift_module::default_libcall_names())
+            .map_err(|_| "Could not create JIT for the host machine.")?;
+
+        Ok(JITModule::new(builder))
+    }
+
+    /// Compiles the given function into the given module, returning its identifier.
+    ///
+    /// The previously defined functions are called through the address of their code, and
+    /// are thus not compiled again. The function is compiled to machine code, but the module
+    /// must still be finalized before it can be called.
+    pub fn compile(&mut self, module: &mut JITModule, function: &Function) -> Result<FuncId, &'static str> {
+        let body = function.body.as_ref().ok_or("Cannot compile an external function.")?;
+        let arg_count = function.prototype.args.len();
+        let id = module.declare_function(&function.prototype.name, Linkage::Export, &signature(module, arg_count))
+            .map_err(|_| "Could not declare function.")?;
+
+        let mut ctx = module.make_context();
+        let mut builder_context = FunctionBuilderContext::new();
+
+        ctx.func.signature = signature(module, arg_count);
+
+        let mut builder = FunctionBuilder::new(&mut ctx.func, &mut builder_context);
+        let entry = builder.create_block();
+
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        builder.seal_block(entry);
+
+        let mut translator = Translator {
+            builder,
+            module: &mut *module,
+            declarations: &self.declarations,
+            function: (function.prototype.name.as_str(), id, arg_count),
+            variables: Vec::new(),
+            next_variable: 0
+        };
+
+        for (index, arg) in function.prototype.args.iter().enumerate() {
+            let value = translator.builder.block_params(entry)[index];
+
+            translator.bind(arg, value);
+        }
+
+        let value = translator.compile_expr(body)?;
+
+        translator.builder.ins().return_(&[ value ]);
+        translator.builder.finalize();
+
+        self.ir = Some(ctx.func.display().to_string());
+
+        module.define_function(id, &mut ctx).map_err(|_| "Invalid generated function.")?;
+        module.clear_context(&mut ctx);
+
+        Ok(id)
+    }
+
+    /// Returns the address of the host function declared with `extern` and the given name,
+    /// since Cranelift aborts when it cannot resolve a symbol.
+    fn resolve(&self, name: &str) -> Option<usize> {
+        self.mappings.get(name).copied().or_else(|| lookup_symbol(name))
+    }
+
+    /// Sets the address of the function with the given name, keeping the module in which it
+    /// was compiled, if any, until it is redefined.
+    fn declare(&mut self, function: &Function, address: usize, module: Option<JITModule>) {
+        let name = &function.prototype.name;
+
+        match self.declarations.get_mut(name) {
+            Some(declaration) => {
+                declaration.address.set(address);
+
+                if let Some(previous) = std::mem::replace(&mut declaration.module, module) {
+                    // no function is executing, and the previous code is no longer called
+                    unsafe { previous.free_memory() };
+                }
+            },
+            None => {
+                self.declarations.insert(name.clone(), Declaration {
+                    arg_count: function.prototype.args.len(),
+                    address: Box::new(Cell::new(address)),
+                    module
+                });
+            }
+        }
+    }
+}
+
+impl Drop for CraneliftJit {
+    fn drop(&mut self) {
+        for (_, declaration) in self.declarations.drain() {
+            if let Some(module) = declaration.module {
+                // none of the functions can be called anymore
+                unsafe { module.free_memory() };
+            }
+        }
+    }
+}
+
+impl Backend for CraneliftJit {
+    fn register_extern(&mut self, name: &str, function: HostFunction) -> Result<(), &'static str> {
+        let declared = self.declarations.get(name)
+            .filter(|declaration| declaration.module.is_none())
+            .map(|declaration| declaration.arg_count);
+
+        backend::check_registration(declared, function.arity())?;
+
+        self.add_global_mapping(name, function.address());
+        self.arities.insert(name.to_string(), function.arity());
+
+        Ok(())
+    }
+
+    fn eval(&mut self, function: &Function) -> Result<Option<f64>, BackendError> {
+        backend::check_extern(function, self.arities.get(&function.prototype.name).copied()).map_err(BackendError::Compilation)?;
+
+        // the functions calling the previous definition were compiled with its signature
+        if let Some(declaration) = self.declarations.get(&function.prototype.name) {
+            if declaration.arg_count != function.prototype.args.len() {
+                return Err(BackendError::Compilation("Cannot redefine a function with a different number of parameters."));
+            }
+        }
+
+        if function.body.is_none() {
+            let address = self.resolve(&function.prototype.name).ok_or(BackendError::Compilation("Unknown external function."))?;
+
+            self.declare(function, address, None);
+
+            return Ok(None);
+        }
+
+        let mut module = self.create_module().map_err(BackendError::Compilation)?;
+        let compiled = self.compile(&mut module, function).and_then(|id| {
+            module.finalize_definitions().map_err(|_| "Could not finalize module.")?;
+
+            Ok(module.get_finalized_function(id))
+        });
+
+        let code = match compiled {
+            Ok(code) => code,
+            Err(err) => {
+                // none of the functions of the module was called
+                unsafe { module.free_memory() };
+
+                return Err(BackendError::Compilation(err));
+            }
+        };
+
+        if !function.is_anon {
+            self.declare(function, code as usize, Some(module));
+
+            return Ok(None);
+        }
+
+        // the function was compiled with the signature `fn() -> f64`, and the module lives
+        // until it returns
+        let compiled_fn = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> f64>(code) };
+        let result = compiled_fn();
+
+        // no function of the module is executing, nor will ever be called again
+        unsafe { module.free_memory() };
+
+        Ok(Some(result))
+    }
+
+    fn ir(&self) -> Option<&str> {
+        self.ir.as_deref()
+    }
+}
+
+/// Returns the address of the symbol with the given name in the current process.
+#[cfg(unix)]
+fn lookup_symbol(name: &str) -> Option<usize> {
+    let name = std::ffi::CString::new(name).ok()?;
+    let address = unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr()) };
+
+    if address.is_null() {
+        None
+    } else {
+        Some(address as usize)
+    }
+}
+
+/// Symbols of the process are only looked up on Unix; elsewhere, host functions must be
+/// registered with `register_extern` or `add_global_mapping`.
+#[cfg(not(unix))]
+fn lookup_symbol(_name: &str) -> Option<usize> {
+    None
+}
+
+/// Returns the signature of a function taking the given number of `f64` arguments.
+fn signature(module: &JITModule, arg_count: usize) -> Signature {
+    let mut signature = module.make_signature();
+
+    signature.params = vec![ AbiParam::new(types::F64); arg_count ];
+    signature.returns.push(AbiParam::new(types::F64));
+    signature
+}
+
+/// Translates the body of a function to Cranelift IR.
+struct Translator<'a> {
+    builder: FunctionBuilder<'a>,
+    module: &'a mut JITModule,
+    /// Previously defined functions, which are called through the address of their code.
+    declarations: &'a HashMap<String, Declaration>,
+    /// Name, identifier and number of arguments of the translated function, which calls
+    /// itself directly.
+    function: (&'a str, FuncId, usize),
+    /// Variables in scope, the innermost last.
+    variables: Vec<(String, Variable)>,
+    next_variable: usize
+}
+
+impl<'a> Translator<'a> {
+
+    /// Binds a new variable with the given name and initial value, shadowing any variable
+    /// with the same name.
+    fn bind(&mut self, name: &str, value: Value) {
+        let var = Variable::new(self.next_variable);
+
+        self.next_variable += 1;
+        self.builder.declare_var(var, types::F64);
+        self.builder.def_var(var, value);
+        self.variables.push((name.to_string(), var));
+    }
+
+    fn lookup(&self, name: &str) -> Option<Variable> {
+        self.variables.iter().rev().find(|(var_name, _)| var_name == name).map(|(_, var)| *var)
+    }
+
+    /// Returns 1 if the given condition is true, and 0 otherwise.
+    fn bool_to_f64(&mut self, cond: Value) -> Value {
+        self.builder.ins().fcvt_from_uint(types::F64, cond)
+    }
+
+    /// Returns a value indicating whether the given value is considered true by a condition.
+    fn is_true(&mut self, value: Value) -> Value {
+        let zero = self.builder.ins().f64const(0.);
+
+        self.builder.ins().fcmp(FloatCC::OrderedNotEqual, value, zero)
+    }
+
+    /// Returns the number of arguments of the function with the given name, if it is defined.
+    fn arg_count(&self, name: &str) -> Option<usize> {
+        if name == self.function.0 {
+            Some(self.function.2)
+        } else {
+            self.declarations.get(name).map(|declaration| declaration.arg_count)
+        }
+    }
+
+    fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, &'static str> {
+        let arg_count = self.arg_count(name).ok_or("Unknown function.")?;
+
+        if arg_count != args.len() {
+            return Err("Incorrect number of arguments passed.");
+        }
+
+        let call = if name == self.function.0 {
+            let callee = self.module.declare_func_in_func(self.function.1, self.builder.func);
+
+            self.builder.ins().call(callee, args)
+        } else {
+            // load the address of the current definition of the function
+            let pointer_type = self.module.target_config().pointer_type();
+            let cell = self.declarations[name].address.as_ptr() as i64;
+            let cell = self.builder.ins().iconst(pointer_type, cell);
+            let callee = self.builder.ins().load(pointer_type, MemFlags::trusted(), cell, 0);
+            let signature = self.builder.import_signature(signature(self.module, arg_count));
+
+            self.builder.ins().call_indirect(signature, callee, args)
+        };
+
+        Ok(self.builder.inst_results(call)[0])
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) -> Result<Value, &'static str> {
+        match expr {
+            Expr::Number(nb) => Ok(self.builder.ins().f64const(*nb)),
+
+            Expr::Variable(name) => match self.lookup(name) {
+                Some(var) => Ok(self.builder.use_var(var)),
+                None => Err("Could not find a matching variable.")
+            },
+
+            Expr::Binary { op: '=', left, right } => {
+                let var_name = match left.as_ref() {
+                    Expr::Variable(var_name) => var_name,
+                    _ => return Err("Expected variable as left-hand operator of assignement.")
+                };
+
+                let value = self.compile_expr(right)?;
+                let var = self.lookup(var_name).ok_or("Undefined variable.")?;
+
+                self.builder.def_var(var, value);
+
+                Ok(value)
+            },
+
+            Expr::Binary { op, left, right } => {
+                let lhs = self.compile_expr(left)?;
+                let rhs = self.compile_expr(right)?;
+
+                match op {
+                    '+' => Ok(self.builder.ins().fadd(lhs, rhs)),
+                    '-' => Ok(self.builder.ins().fsub(lhs, rhs)),
+                    '*' => Ok(self.builder.ins().fmul(lhs, rhs)),
+                    '/' => Ok(self.builder.ins().fdiv(lhs, rhs)),
+                    '<' => {
+                        let cmp = self.builder.ins().fcmp(FloatCC::UnorderedOrLessThan, lhs, rhs);
+
+                        Ok(self.bool_to_f64(cmp))
+                    },
+                    '>' => {
+                        let cmp = self.builder.ins().fcmp(FloatCC::UnorderedOrLessThan, rhs, lhs);
+
+                        Ok(self.bool_to_f64(cmp))
+                    },
+                    custom => {
+                        let name = format!("binary{}", custom);
+
+                        match self.arg_count(name.as_str()) {
+                            Some(2) => self.call(name.as_str(), &[ lhs, rhs ]),
+                            _ => Err("Undefined binary operator.")
+                        }
+                    }
+                }
+            },
+
+            Expr::Call { func_name, args } => {
+                if let Some(arg_count) = self.arg_count(func_name) {
+                    if arg_count != args.len() {
+                        return Err("Incorrect number of arguments passed.");
+                    }
+                }
+
+                let mut values = Vec::with_capacity(args.len());
+
+                for arg in args {
+                    values.push(self.compile_expr(arg)?);
+                }
+
+                self.call(func_name, values.as_slice())
+            },
+
+            Expr::Conditional { cond, consequence, alternative } => {
+                let cond = self.compile_expr(cond)?;
+                let cond = self.is_true(cond);
+
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                let merge_block = self.builder.create_block();
+
+                self.builder.append_block_param(merge_block, types::F64);
+                self.builder.ins().brif(cond, then_block, &[], else_block, &[]);
+
+                self.builder.switch_to_block(then_block);
+                self.builder.seal_block(then_block);
+
+                let then_value = self.compile_expr(consequence)?;
+
+                self.builder.ins().jump(merge_block, &[ then_value ]);
+
+                self.builder.switch_to_block(else_block);
+                self.builder.seal_block(else_block);
+
+                let else_value = self.compile_expr(alternative)?;
+
+                self.builder.ins().jump(merge_block, &[ else_value ]);
+
+                self.builder.switch_to_block(merge_block);
+                self.builder.seal_block(merge_block);
+
+                Ok(self.builder.block_params(merge_block)[0])
+            },
+
+            Expr::For { var_name, start, end, step, body } => {
+                let start = self.compile_expr(start)?;
+
+                self.bind(var_name, start);
+
+                let loop_block = self.builder.create_block();
+                let after_block = self.builder.create_block();
+
+                self.builder.ins().jump(loop_block, &[]);
+                self.builder.switch_to_block(loop_block);
+
+                // like the compiled loop, evaluate the body before checking the end condition,
+                // which is given the value of the variable before it is incremented
+                let result = self.compile_loop_body(var_name, end, step.as_deref(), body);
+
+                self.variables.pop();
+
+                let end_cond = result?;
+
+                self.builder.ins().brif(end_cond, loop_block, &[], after_block, &[]);
+                self.builder.seal_block(loop_block);
+
+                self.builder.switch_to_block(after_block);
+                self.builder.seal_block(after_block);
+
+                Ok(self.builder.ins().f64const(0.))
+            },
+
+            Expr::VarIn { variables, body } => {
+                let depth = self.variables.len();
+                let result = self.compile_var_in(variables, body);
+
+                self.variables.truncate(depth);
+                result
+            }
+        }
+    }
+
+    /// Compiles the body, the step and the end condition of a loop whose variable is the
+    /// innermost one, returning the end condition.
+    fn compile_loop_body(&mut self, var_name: &str, end: &Expr, step: Option<&Expr>, body: &Expr) -> Result<Value, &'static str> {
+        self.compile_expr(body)?;
+
+        let step = match step {
+            Some(step) => self.compile_expr(step)?,
+            None => self.builder.ins().f64const(1.)
+        };
+
+        let end = self.compile_expr(end)?;
+        let end_cond = self.is_true(end);
+        let var = self.lookup(var_name).expect("Loop variable is not in scope.");
+        let current = self.builder.use_var(var);
+        let next = self.builder.ins().fadd(current, step);
+
+        self.builder.def_var(var, next);
+
+        Ok(end_cond)
+    }
+
+    /// Compiles the body of a `var..in` expression, after binding its variables.
+    fn compile_var_in(&mut self, variables: &[(String, Option<Expr>)], body: &Expr) -> Result<Value, &'static str> {
+        for (var_name, initializer) in variables {
+            let value = match initializer {
+                Some(init) => self.compile_expr(init)?,
+                None => self.builder.ins().f64const(0.)
+            };
+
+            self.bind(var_name, value);
+        }
+
+        self.compile_expr(body)
+    }
+}
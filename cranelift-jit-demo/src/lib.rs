@@ -1,7 +1,5 @@
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
-    }
-}
+pub mod aot;
+pub mod compiler;
+pub mod frontend;
+pub mod jit;
+pub mod kaleido;
@@ -0,0 +1,89 @@
+//! Compilation of the toy language to object files, linked with the runtime into executables.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use cranelift_jit_demo::aot::{self, ObjectCompiler};
+use cranelift_jit_demo::compiler::CompileError;
+
+const PROGRAM: &str = "
+fn fib(n) -> (r) {
+    a = 0
+    b = 1
+
+    while n > 0 {
+        t = a + b
+        a = b
+        b = t
+        n = n - 1
+    }
+    r = a
+}
+
+fn main() -> (r) {
+    i = 0
+
+    while i < 5 {
+        println(fib(i * 10))
+        i = i + 1
+    }
+
+    counter = counter + 1
+    p = alloc(8)
+    if p != 0 {
+        r = counter
+    }
+}
+";
+
+/// Returns an empty directory for the files of the given test.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("toy-aot-{}-{}", std::process::id(), name));
+
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("Cannot create test directory.");
+    dir
+}
+
+#[test]
+fn programs_are_compiled_to_executables() {
+    let mut compiler = ObjectCompiler::new("program").unwrap();
+
+    compiler.define_global("counter", 41).unwrap();
+    compiler.compile(PROGRAM).expect("Cannot compile program.");
+
+    assert_eq!(compiler.arity("main"), Some(0));
+    assert_eq!(compiler.arity("fib"), Some(1));
+
+    let object = compiler.finish().expect("Cannot emit object file.");
+
+    if cfg!(target_os = "linux") {
+        assert_eq!(&object[..4], b"\x7fELF");
+    }
+
+    let dir = test_dir("executables");
+    let object_path = dir.join("program.o");
+    let executable = dir.join("program");
+
+    fs::write(&object_path, object).unwrap();
+    aot::link(&object_path, &executable).expect("Cannot link program.");
+
+    let output = Command::new(&executable).output().expect("Cannot run program.");
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0\n55\n6765\n832040\n102334155\n");
+    assert_eq!(output.status.code(), Some(42));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn invalid_programs_are_errors() {
+    let mut compiler = ObjectCompiler::new("program").unwrap();
+
+    assert_eq!(compiler.compile("fn f() -> (r) {\n r = g()\n}\n"), Err(CompileError::Compilation("Unknown function.")));
+    assert_eq!(compiler.compile("fn f() -> (r) {\n r = println()\n}\n"), Err(CompileError::Compilation("Incorrect number of arguments passed.")));
+
+    compiler.compile("fn f() -> (r) {\n r = println(1)\n}\n").expect("Cannot compile program.");
+
+    assert_eq!(compiler.compile("fn f() -> (r) {\n r = 1\n}\n"), Err(CompileError::Compilation("Function is already defined.")));
+}
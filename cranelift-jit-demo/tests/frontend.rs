@@ -0,0 +1,108 @@
+//! Parsing of the toy language compiled by the Cranelift JIT demo.
+
+use cranelift_jit_demo::frontend::{self, BinaryOp, Expr, Function, ParseError};
+
+fn lit(value: i64) -> Box<Expr> {
+    Box::new(Expr::Literal(value))
+}
+
+fn id(name: &str) -> Box<Expr> {
+    Box::new(Expr::Identifier(name.to_string()))
+}
+
+#[test]
+fn functions_are_parsed() {
+    let source = "
+        // returns the n-th Fibonacci number
+        fn fib(n) -> (r) {
+            a = 0
+            b = 1
+
+            while n > 0 {
+                t = a + b // next number
+                a = b
+                b = t
+                n = n - 1
+            }
+            r = a
+        }
+
+        fn main() -> (r) {
+            if fib(10) == 55 {
+                r = 1
+            } else {
+                r = 0
+            }
+        }
+    ";
+
+    let functions = frontend::parse(source).expect("Cannot parse program.");
+
+    assert_eq!(functions.len(), 2);
+    assert_eq!(functions[0].name, "fib");
+    assert_eq!(functions[0].params, vec![ "n".to_string() ]);
+    assert_eq!(functions[0].returns, "r");
+    assert_eq!(functions[0].body[2], Expr::While(
+        Box::new(Expr::Binary(BinaryOp::Gt, id("n"), lit(0))),
+        vec![
+            Expr::Assign("t".to_string(), Box::new(Expr::Binary(BinaryOp::Add, id("a"), id("b")))),
+            Expr::Assign("a".to_string(), id("b")),
+            Expr::Assign("b".to_string(), id("t")),
+            Expr::Assign("n".to_string(), Box::new(Expr::Binary(BinaryOp::Sub, id("n"), lit(1))))
+        ]
+    ));
+
+    assert_eq!(functions[1], Function {
+        name: "main".to_string(),
+        params: Vec::new(),
+        returns: "r".to_string(),
+        body: vec![
+            Expr::IfElse(
+                Box::new(Expr::Binary(BinaryOp::Eq, Box::new(Expr::Call("fib".to_string(), vec![ Expr::Literal(10) ])), lit(55))),
+                vec![ Expr::Assign("r".to_string(), lit(1)) ],
+                vec![ Expr::Assign("r".to_string(), lit(0)) ]
+            )
+        ]
+    });
+}
+
+#[test]
+fn operators_have_the_usual_precedence() {
+    let functions = frontend::parse("fn f(a, b) -> (r) {\n r = a - b - 1 * (2 + a) / b < 3\n}\n").unwrap();
+
+    // ((a - b) - ((1 * (2 + a)) / b)) < 3
+    let product = Expr::Binary(BinaryOp::Mul, lit(1), Box::new(Expr::Binary(BinaryOp::Add, lit(2), id("a"))));
+    let difference = Expr::Binary(
+        BinaryOp::Sub,
+        Box::new(Expr::Binary(BinaryOp::Sub, id("a"), id("b"))),
+        Box::new(Expr::Binary(BinaryOp::Div, Box::new(product), id("b")))
+    );
+
+    assert_eq!(functions[0].body, vec![
+        Expr::Assign("r".to_string(), Box::new(Expr::Binary(BinaryOp::Lt, Box::new(difference), lit(3))))
+    ]);
+}
+
+#[test]
+fn keywords_are_not_identifiers() {
+    let functions = frontend::parse("fn f() -> (r) {\n iffy = 1\n if iffy {\n r = iffy\n }\n}").unwrap();
+
+    assert_eq!(functions[0].body[0], Expr::Assign("iffy".to_string(), lit(1)));
+    assert_eq!(functions[0].body[1], Expr::IfElse(id("iffy"), vec![ Expr::Assign("r".to_string(), id("iffy")) ], Vec::new()));
+
+    assert!(frontend::parse("fn while() -> (r) {\n}").is_err());
+    assert!(frontend::parse("fn f() -> (r) {\n if = 1\n}").is_err());
+}
+
+#[test]
+fn errors_have_a_location() {
+    let err = frontend::parse("fn f(a) -> (r) {\n    r = a +\n}\n").unwrap_err();
+
+    assert_eq!((err.line, err.column), (2, 12));
+    assert!(err.to_string().starts_with("2:12: expected "), "{}", err);
+
+    let err = frontend::parse("fn f() -> (r) {\n    r = 99999999999999999999\n}\n").unwrap_err();
+
+    // the error is reported at the end of the literal
+    assert_eq!(err, ParseError { line: 2, column: 29, expected: "64-bit integer".to_string() });
+}
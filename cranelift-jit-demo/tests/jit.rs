@@ -0,0 +1,119 @@
+//! Compilation and execution of the toy language with the Cranelift JIT.
+
+use cranelift_jit_demo::compiler::CompileError;
+use cranelift_jit_demo::jit::JIT;
+
+const FIB: &str = "
+fn fib(n) -> (r) {
+    a = 0
+    b = 1
+
+    while n > 0 {
+        t = a + b
+        a = b
+        b = t
+        n = n - 1
+    }
+    r = a
+}
+";
+
+extern "C" fn add3(a: i64, b: i64, c: i64) -> i64 {
+    a + b + c
+}
+
+#[test]
+fn functions_are_compiled() {
+    let mut jit = JIT::new();
+
+    jit.compile(FIB).expect("Cannot compile program.");
+    jit.compile("fn main(n) -> (r) {\n if n < 0 {\n r = 0 - 1\n } else {\n r = fib(n) * 2 / 2\n }\n}\n").expect("Cannot compile program.");
+
+    let fib = jit.function::<extern "C" fn(i64) -> i64>("fib").unwrap();
+    let main = jit.function::<extern "C" fn(i64) -> i64>("main").unwrap();
+
+    assert_eq!(fib(10), 55);
+    assert_eq!(main(50), 12_586_269_025);
+    assert_eq!(main(-5), -1);
+
+    // functions cannot be redefined, and are called with their number of arguments
+    assert_eq!(jit.compile(FIB), Err(CompileError::Compilation("Function is already defined.")));
+    assert_eq!(jit.function::<extern "C" fn() -> i64>("fib").err(), Some(CompileError::Compilation("Incorrect number of arguments passed.")));
+    assert_eq!(jit.function::<extern "C" fn() -> i64>("nope").err(), Some(CompileError::Compilation("Unknown function.")));
+}
+
+#[test]
+fn divisions_do_not_trap() {
+    let mut jit = JIT::new();
+
+    jit.compile("fn div(a, b) -> (r) {\n r = a / b\n}\n").expect("Cannot compile program.");
+
+    let div = jit.function::<extern "C" fn(i64, i64) -> i64>("div").unwrap();
+
+    assert_eq!(div(7, 2), 3);
+    assert_eq!(div(-7, 2), -3);
+    assert_eq!(div(7, -1), -7);
+    assert_eq!(div(7, 0), 0);
+    assert_eq!(div(i64::MIN, -1), i64::MIN);
+}
+
+#[test]
+fn invalid_programs_are_not_compiled() {
+    let mut jit = JIT::new();
+
+    assert!(matches!(jit.compile("fn f() -> (r) {\n r = \n}\n"), Err(CompileError::Parse(_))));
+    assert_eq!(jit.compile("fn f() -> (r) {\n r = x\n}\n"), Err(CompileError::Compilation("Undefined variable.")));
+    assert_eq!(jit.compile("fn f() -> (r) {\n r = g(1)\n}\n"), Err(CompileError::Compilation("Unknown function.")));
+    assert_eq!(jit.compile("fn f(a) -> (r) {\n r = a\n}\nfn g() -> (r) {\n r = f()\n}\n"), Err(CompileError::Compilation("Incorrect number of arguments passed.")));
+
+    // none of the functions of a program is compiled if one of them is invalid
+    assert!(jit.function::<extern "C" fn(i64) -> i64>("f").is_err());
+
+    jit.compile("fn f(a) -> (r) {\n r = a + 1\n}\n").expect("Cannot compile program.");
+
+    assert_eq!(jit.function::<extern "C" fn(i64) -> i64>("f").unwrap()(1), 2);
+}
+
+#[test]
+fn host_functions_can_be_called() {
+    let mut jit = unsafe {
+        JIT::with_symbols(&[
+            ("add3", add3 as extern "C" fn(i64, i64, i64) -> i64 as *const u8),
+            ("strlen", libc::strlen as unsafe extern "C" fn(*const libc::c_char) -> libc::size_t as *const u8)
+        ])
+    };
+
+    jit.compile("fn f(a) -> (r) {\n r = add3(a, 2, 3) + strlen(\"hello\")\n p = alloc(16)\n if p == 0 {\n r = 0\n }\n}\n")
+        .expect("Cannot compile program.");
+
+    assert_eq!(jit.function::<extern "C" fn(i64) -> i64>("f").unwrap()(1), 11);
+
+    // a host function is always called with the same number of arguments
+    assert_eq!(jit.compile("fn g() -> (r) {\n r = add3(1, 2)\n}\n"), Err(CompileError::Compilation("Incorrect number of arguments passed.")));
+    assert_eq!(jit.compile("fn g() -> (r) {\n r = puts(\"hello\")\n}\n"), Err(CompileError::Compilation("Unknown function.")));
+}
+
+#[test]
+fn data_objects_can_be_used() {
+    let mut jit = unsafe {
+        JIT::with_symbols(&[
+            ("strlen", libc::strlen as unsafe extern "C" fn(*const libc::c_char) -> libc::size_t as *const u8)
+        ])
+    };
+
+    jit.define_string("greeting", "hello world").unwrap();
+    jit.define_global("counter", 40).unwrap();
+
+    assert_eq!(jit.define_global("counter", 0), Err(CompileError::Compilation("Name is already declared.")));
+    assert_eq!(jit.define_string("counter", ""), Err(CompileError::Compilation("Name is already declared.")));
+
+    jit.compile("fn count(n) -> (r) {\n counter = counter + n\n r = counter + strlen(&greeting)\n}\n")
+        .expect("Cannot compile program.");
+
+    let count = jit.function::<extern "C" fn(i64) -> i64>("count").unwrap();
+
+    assert_eq!(count(1), 52);
+    assert_eq!(count(1), 53);
+    assert_eq!(jit.global("counter"), Some(42));
+    assert_eq!(jit.global("greeting"), None);
+}
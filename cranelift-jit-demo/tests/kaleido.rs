@@ -0,0 +1,147 @@
+//! Runs the programs of the `kaleidoscope` crate with the Cranelift backend, and checks that
+//! they produce the output produced by the LLVM backend and the interpreter.
+
+use std::cell::RefCell;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use cranelift_jit_demo::kaleido::CraneliftJit;
+use kaleidoscope::backend::{Backend, BackendError};
+use kaleidoscope::operator::OperatorTable;
+use kaleidoscope::parser::{Function, Parser};
+use kaleidoscope::runtime;
+use kaleidoscope::session::Session;
+use kaleidoscope::syntax;
+
+thread_local! {
+    static OUTPUT: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+extern "C" fn putchard(x: f64) -> f64 {
+    OUTPUT.with(|output| output.borrow_mut().push(x as u8 as char));
+    x
+}
+
+extern "C" fn printd(x: f64) -> f64 {
+    OUTPUT.with(|output| output.borrow_mut().push_str(format!("{}\n", x).as_str()));
+    x
+}
+
+fn jit(optimize: bool) -> CraneliftJit {
+    let mut jit = CraneliftJit::new(optimize);
+
+    jit.add_global_mapping("putchard", putchard as extern "C" fn(f64) -> f64 as usize);
+    jit.add_global_mapping("printd", printd as extern "C" fn(f64) -> f64 as usize);
+    jit
+}
+
+/// Runs the given program, returning its output followed by the result of each top-level
+/// expression (or the error it produced), in the format of the REPL.
+fn run(source: &str, backend: &mut dyn Backend) -> String {
+    let functions = syntax::parse(source, &OperatorTable::new()).functions().expect("Cannot parse program.");
+    let mut transcript = String::new();
+
+    OUTPUT.with(|output| output.borrow_mut().clear());
+
+    for function in functions {
+        let result = backend.eval(&function);
+
+        OUTPUT.with(|output| transcript.push_str(output.borrow_mut().drain(..).as_str()));
+
+        match result {
+            Ok(Some(result)) => transcript.push_str(format!("=> {}\n", result).as_str()),
+            Ok(None) => (),
+            Err(err) => transcript.push_str(format!("!> {}\n", err).as_str())
+        }
+    }
+
+    transcript
+}
+
+fn programs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../kaleidoscope/tests/programs");
+    let mut programs = fs::read_dir(dir).expect("Cannot read the programs directory.")
+        .map(|entry| entry.expect("Cannot read the programs directory.").path())
+        .filter(|path| path.extension() == Some(OsStr::new("ks")))
+        .collect::<Vec<PathBuf>>();
+
+    programs.sort();
+    programs
+}
+
+fn parse(input: &str) -> Function {
+    Parser::new(input.to_string(), &mut OperatorTable::new()).parse().expect("Cannot parse test input.")
+}
+
+#[test]
+fn programs_produce_the_expected_output() {
+    for program in programs() {
+        let source = fs::read_to_string(&program).expect("Cannot read program.");
+        let expected = fs::read_to_string(program.with_extension("out")).expect("Cannot read expected output.");
+
+        for optimize in [ false, true ].iter() {
+            assert_eq!(run(source.as_str(), &mut jit(*optimize)), expected, "{} (optimize: {})", program.display(), optimize);
+        }
+    }
+}
+
+#[test]
+fn functions_can_be_redefined() {
+    let mut jit = jit(true);
+
+    assert_eq!(jit.eval(&parse("def f(x) x + 1")), Ok(None));
+    assert_eq!(jit.eval(&parse("def g(x) f(x) * 2")), Ok(None));
+    assert_eq!(jit.eval(&parse("g(1)")), Ok(Some(4.)));
+    assert_eq!(jit.eval(&parse("def f(x) x + 2")), Ok(None));
+    assert_eq!(jit.eval(&parse("g(1)")), Ok(Some(6.)));
+    assert!(jit.ir().expect("Missing IR.").contains("function"));
+
+    // only the redefined function is compiled, and its callers load the address of its code
+    assert_eq!(jit.eval(&parse("def f(x) x + 3")), Ok(None));
+    assert!(!jit.ir().expect("Missing IR.").contains("call_indirect"));
+    assert_eq!(jit.eval(&parse("g(1)")), Ok(Some(8.)));
+    assert!(jit.ir().expect("Missing IR.").contains("call_indirect"));
+
+    // recursive functions call themselves directly
+    assert_eq!(jit.eval(&parse("def fib(x) if x < 3 then 1 else fib(x - 1) + fib(x - 2)")), Ok(None));
+    assert_eq!(jit.eval(&parse("fib(10)")), Ok(Some(55.)));
+
+    // an extern can be replaced by a definition, and the other way around
+    assert_eq!(jit.eval(&parse("extern putchard(x)")), Ok(None));
+    assert_eq!(jit.eval(&parse("def h(x) putchard(x)")), Ok(None));
+    assert_eq!(jit.eval(&parse("def putchard(x) x * 10")), Ok(None));
+    assert_eq!(jit.eval(&parse("h(4)")), Ok(Some(40.)));
+
+    // the callers were compiled with the previous signature
+    assert_eq!(
+        jit.eval(&parse("def f(x, y) x + y")),
+        Err(BackendError::Compilation("Cannot redefine a function with a different number of parameters."))
+    );
+    assert_eq!(jit.eval(&parse("g(1)")), Ok(Some(8.)));
+}
+
+#[test]
+fn invalid_functions_are_errors() {
+    let mut jit = jit(false);
+
+    assert_eq!(jit.eval(&parse("def f(x) x")), Ok(None));
+    assert_eq!(jit.eval(&parse("f(1, 2)")), Err(BackendError::Compilation("Incorrect number of arguments passed.")));
+    assert_eq!(jit.eval(&parse("g(1)")), Err(BackendError::Compilation("Unknown function.")));
+    assert_eq!(jit.eval(&parse("def h() y")), Err(BackendError::Compilation("Could not find a matching variable.")));
+    assert_eq!(jit.eval(&parse("extern does_not_exist(x)")), Err(BackendError::Compilation("Unknown external function.")));
+
+    // failed definitions are not recorded
+    assert_eq!(jit.eval(&parse("h()")), Err(BackendError::Compilation("Unknown function.")));
+    assert_eq!(jit.eval(&parse("f(3)")), Ok(Some(3.)));
+}
+
+#[test]
+fn builtins_are_called() {
+    let mut jit = jit(true);
+    let mut session = Session::new();
+
+    assert_eq!(runtime::declare(&mut session, &mut jit), Ok(()));
+    assert_eq!(jit.eval(&session.parse("sqrt(16) + pow(2, 10)").unwrap()), Ok(Some(1028.)));
+    assert_eq!(jit.eval(&session.parse("floor(0 - 1.5) + abs(0 - 3)").unwrap()), Ok(Some(1.)));
+    assert_eq!(jit.eval(&session.parse("extern sin(x, y)").unwrap()), Err(BackendError::Compilation("Incorrect number of parameters for host function.")));
+}
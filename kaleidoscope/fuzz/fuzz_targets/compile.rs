@@ -0,0 +1,73 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use kaleidoscope::backend::Backend;
+use kaleidoscope::interp::Interpreter;
+use kaleidoscope::operator::OperatorTable;
+use kaleidoscope::optimizer::{self, OptimizeOptions};
+use kaleidoscope::parser::{Function, Parser};
+use kaleidoscope_fuzz::Program;
+
+/// Evaluates each function with the given backend, returning the results of the top-level
+/// expressions, or the first error.
+fn run(functions: &[Function], backend: &mut dyn Backend) -> Vec<f64> {
+    let mut results = Vec::new();
+
+    for function in functions {
+        match backend.eval(function) {
+            Ok(Some(result)) => results.push(result),
+            Ok(None) => (),
+            Err(err) => panic!("Cannot evaluate generated function {}: {}", function, err)
+        }
+    }
+
+    results
+}
+
+/// Returns a value indicating whether both lists contain the same numbers, considering
+/// that all NaNs are equal.
+fn same_results(left: &[f64], right: &[f64]) -> bool {
+    left.len() == right.len() && left.iter().zip(right).all(|(l, r)| l == r || l.is_nan() && r.is_nan())
+}
+
+fuzz_target!(|program: Program| {
+    let lines = program.lines();
+    let mut operators = OperatorTable::new();
+
+    // the generated program must survive a round-trip through its source code
+    for (line, function) in lines.iter().zip(&program.functions) {
+        let parsed = Parser::new(line.clone(), &mut operators).parse();
+
+        assert_eq!(parsed.as_ref(), Ok(function), "Cannot parse generated function {:?}.", line);
+    }
+
+    let expected = run(&program.functions, &mut Interpreter::new());
+
+    let mut defined = Vec::new();
+    let mut optimized = Vec::new();
+
+    for function in &program.functions {
+        let options = OptimizeOptions { unroll_limit: 8, ..OptimizeOptions::default() };
+
+        optimized.push(optimizer::optimize(function.clone(), defined.as_slice(), &options));
+        defined.push(function.clone());
+    }
+
+    let results = run(&optimized, &mut Interpreter::new());
+
+    assert!(same_results(&results, &expected), "Optimized program returned {:?} instead of {:?}:\n{}", results, expected, lines.join("\n"));
+
+    #[cfg(feature = "llvm")]
+    {
+        use inkwell::OptimizationLevel;
+        use inkwell::context::Context;
+        use kaleidoscope::jit::Jit;
+
+        for optimization in [ OptimizationLevel::None, OptimizationLevel::Default ].iter() {
+            let context = Context::create();
+            let results = run(&program.functions, &mut Jit::new(&context, *optimization));
+
+            assert!(same_results(&results, &expected), "LLVM ({:?}) returned {:?} instead of {:?}:\n{}", optimization, results, expected, lines.join("\n"));
+        }
+    }
+});
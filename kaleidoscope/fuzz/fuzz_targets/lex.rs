@@ -0,0 +1,28 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use kaleidoscope::lexer::{Lexer, Token};
+
+fuzz_target!(|input: &str| {
+    let mut lexer = Lexer::new(input);
+
+    while let Ok(token) = lexer.lexer() {
+        if token == Token::EOF {
+            break;
+        }
+    }
+
+    // the tokens of the lossless lexer must cover the whole input, without gap
+    let mut lexer = Lexer::lossless(input);
+    let mut end = 0;
+
+    while let Ok((token, span)) = lexer.lex_spanned() {
+        if token == Token::EOF {
+            assert_eq!(end, input.len(), "Lossless lexer did not reach the end of the input.");
+            break;
+        }
+
+        assert_eq!(span.start, end, "Lossless lexer skipped part of the input.");
+        end = span.end;
+    }
+});
@@ -0,0 +1,37 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use kaleidoscope::format::{self, FormatOptions};
+use kaleidoscope::operator::OperatorTable;
+use kaleidoscope::parser::{Function, Parser};
+use kaleidoscope::syntax;
+use kaleidoscope_fuzz::Program;
+
+/// Parses each of the given lines, like the REPL does.
+fn parse_lines(lines: &[String]) -> Vec<Function> {
+    let mut operators = OperatorTable::new();
+
+    lines.iter()
+        .map(|line| match Parser::new(line.clone(), &mut operators).parse() {
+            Ok(function) => function,
+            Err(err) => panic!("Cannot parse printed function {:?}: {}", line, err)
+        })
+        .collect()
+}
+
+fuzz_target!(|input: &str| {
+    let _ = Parser::new(input.to_string(), &mut OperatorTable::new()).parse();
+    let _ = format::format(input, &OperatorTable::new(), &FormatOptions::default());
+
+    let functions = match syntax::parse(input, &OperatorTable::new()).functions() {
+        Ok(functions) => functions,
+        Err(_) => return
+    };
+
+    // numbers such as infinity are printed as expressions, so the functions are only
+    // expected to be unchanged by printing them after a first round-trip
+    let reparsed = parse_lines(&Program { functions }.lines());
+    let lines = Program { functions: reparsed.clone() }.lines();
+
+    assert_eq!(parse_lines(&lines), reparsed, "Printing changed the parsed functions: {:?}", lines);
+});
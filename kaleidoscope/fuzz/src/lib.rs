@@ -0,0 +1,372 @@
+//! Structure-aware generator of random Kaleidoscope programs, used by the fuzz targets.
+//!
+//! Generated programs are valid and always terminate: functions only call the functions
+//! defined before them (and thus are never recursive), loops have constant bounds which
+//! are never modified by their body, and programs whose estimated number of evaluated
+//! expressions is too high are simplified.
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use kaleidoscope::operator::{Associativity, OperatorTable};
+use kaleidoscope::parser::{Expr, Function, Prototype};
+
+/// Maximum nesting depth of generated expressions.
+const MAX_DEPTH: usize = 5;
+
+/// Maximum number of iterations of a generated loop.
+const MAX_ITERATIONS: u8 = 4;
+
+/// Maximum estimated number of expressions evaluated by a single function.
+const MAX_COST: u64 = 100_000;
+
+/// Characters which can be defined as binary operators.
+const BINARY_OPERATORS: &[char] = &[ '|', '&', '^', '%' ];
+
+/// Characters which can be defined as unary operators.
+const UNARY_OPERATORS: &[char] = &[ '!', '~' ];
+
+/// Defines a generated program, made of function definitions and top-level expressions.
+#[derive(Debug)]
+pub struct Program {
+    pub functions: Vec<Function>
+}
+
+impl Program {
+
+    /// Returns the source code of each function of the program, in order.
+    ///
+    /// Like the inputs of the REPL, each function must be parsed separately: in a single
+    /// file, a top-level expression starting with an operator or a parenthesis would
+    /// continue the previous function.
+    pub fn lines(&self) -> Vec<String> {
+        let mut operators = OperatorTable::new();
+        let mut lines = Vec::new();
+
+        for function in &self.functions {
+            lines.push(function.pretty(&operators).to_string());
+
+            if let Some(op) = function.prototype.binary_operator() {
+                operators.insert(op, function.prototype.prec as i32, Associativity::Left);
+            }
+        }
+
+        lines
+    }
+}
+
+impl<'a> Arbitrary<'a> for Program {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Generator::default().program(u)
+    }
+}
+
+/// Defines a variable in scope, which may not be assigned if it is a loop variable.
+struct Variable {
+    name: String,
+    assignable: bool
+}
+
+/// Defines a function which can be called by the generated code.
+struct Callable {
+    name: String,
+    arity: usize,
+    cost: u64
+}
+
+#[derive(Default)]
+struct Generator {
+    functions: Vec<Callable>,
+    binary_operators: Vec<(char, u64)>,
+    scope: Vec<Variable>,
+    next_variable: usize
+}
+
+impl Generator {
+
+    fn program(mut self, u: &mut Unstructured) -> Result<Program> {
+        let mut functions = Vec::new();
+        let count = u.int_in_range(1..=8)?;
+
+        for index in 0..count {
+            let function = match u.int_in_range(0..=5)? {
+                0 | 1 => self.function(u, format!("f{}", index))?,
+                2 => match self.binary_operator(u)? {
+                    Some(function) => function,
+                    None => continue
+                },
+                3 => match self.unary_operator(u)? {
+                    Some(function) => function,
+                    None => continue
+                },
+                _ => self.top_level(u)?
+            };
+
+            functions.push(function);
+        }
+
+        // always evaluate something
+        if !matches!(functions.last(), Some(function) if function.is_anon) {
+            functions.push(self.top_level(u)?);
+        }
+
+        Ok(Program { functions })
+    }
+
+    /// Generates a function with the given prototype, and makes it callable by the
+    /// following functions.
+    fn define(&mut self, u: &mut Unstructured, prototype: Prototype) -> Result<Function> {
+        self.scope = prototype.args.iter().map(|name| Variable { name: name.clone(), assignable: true }).collect();
+
+        let mut body = self.expr(u, 0)?;
+        let mut cost = self.cost(&body);
+
+        if cost > MAX_COST {
+            body = Expr::Number(0.);
+            cost = 1;
+        }
+
+        // binary operators are used by binary expressions rather than called by name
+        match prototype.binary_operator() {
+            Some(op) => self.binary_operators.push((op, cost)),
+            None => self.functions.push(Callable { name: prototype.name.clone(), arity: prototype.args.len(), cost })
+        }
+
+        Ok(Function {
+            prototype,
+            body: Some(body),
+            is_anon: false,
+            doc: None,
+            locations: None
+        })
+    }
+
+    fn function(&mut self, u: &mut Unstructured, name: String) -> Result<Function> {
+        let arity = u.int_in_range(0..=3)?;
+        let prototype = Prototype {
+            name,
+            args: (0..arity).map(|index| format!("a{}", index)).collect(),
+            is_op: false,
+            prec: 0
+        };
+
+        self.define(u, prototype)
+    }
+
+    fn binary_operator(&mut self, u: &mut Unstructured) -> Result<Option<Function>> {
+        let available = BINARY_OPERATORS.iter().filter(|op| self.binary_operators.iter().all(|(defined, _)| defined != *op)).collect::<Vec<_>>();
+
+        if available.is_empty() {
+            return Ok(None);
+        }
+
+        let op = **u.choose(&available)?;
+        let prototype = Prototype {
+            name: format!("binary{}", op),
+            args: vec![ "l".to_string(), "r".to_string() ],
+            is_op: true,
+            prec: u.int_in_range(1..=50)?
+        };
+
+        self.define(u, prototype).map(Some)
+    }
+
+    fn unary_operator(&mut self, u: &mut Unstructured) -> Result<Option<Function>> {
+        let available = UNARY_OPERATORS.iter().filter(|op| self.functions.iter().all(|fun| fun.name != format!("unary{}", op))).collect::<Vec<_>>();
+
+        if available.is_empty() {
+            return Ok(None);
+        }
+
+        let op = **u.choose(&available)?;
+        let prototype = Prototype {
+            name: format!("unary{}", op),
+            args: vec![ "v".to_string() ],
+            is_op: true,
+            prec: 0
+        };
+
+        self.define(u, prototype).map(Some)
+    }
+
+    /// Generates an anonymous function, which cannot be called by the following functions.
+    fn top_level(&mut self, u: &mut Unstructured) -> Result<Function> {
+        let prototype = Prototype {
+            name: kaleidoscope::ANONYMOUS_FUNCTION_NAME.to_string(),
+            args: Vec::new(),
+            is_op: false,
+            prec: 0
+        };
+
+        let mut function = self.define(u, prototype)?;
+
+        self.functions.pop();
+        function.is_anon = true;
+
+        Ok(function)
+    }
+
+    fn fresh_variable(&mut self) -> String {
+        self.next_variable += 1;
+
+        format!("v{}", self.next_variable)
+    }
+
+    fn number(&self, u: &mut Unstructured) -> Result<Expr> {
+        // negative numbers are parsed as a subtraction, and thus would not round-trip
+        Ok(Expr::Number(f64::from(u.int_in_range(0u16..=400)?) / 4.))
+    }
+
+    fn expr(&mut self, u: &mut Unstructured, depth: usize) -> Result<Expr> {
+        if depth >= MAX_DEPTH || u.is_empty() {
+            return self.leaf(u);
+        }
+
+        let depth = depth + 1;
+
+        Ok(match u.int_in_range(0..=9)? {
+            0 | 1 => self.leaf(u)?,
+
+            2 | 3 => Expr::Binary {
+                op: *u.choose(&[ '+', '-', '*', '/', '<', '>' ])?,
+                left: Box::new(self.expr(u, depth)?),
+                right: Box::new(self.expr(u, depth)?)
+            },
+
+            4 => {
+                let assignable = self.scope.iter().filter(|var| var.assignable).map(|var| var.name.clone()).collect::<Vec<_>>();
+
+                if !self.binary_operators.is_empty() && (assignable.is_empty() || u.arbitrary()?) {
+                    Expr::Binary {
+                        op: u.choose(&self.binary_operators)?.0,
+                        left: Box::new(self.expr(u, depth)?),
+                        right: Box::new(self.expr(u, depth)?)
+                    }
+                } else if !assignable.is_empty() {
+                    Expr::Binary {
+                        op: '=',
+                        left: Box::new(Expr::Variable(u.choose(&assignable)?.clone())),
+                        right: Box::new(self.expr(u, depth)?)
+                    }
+                } else {
+                    self.leaf(u)?
+                }
+            },
+
+            5 => {
+                if self.functions.is_empty() {
+                    return self.leaf(u);
+                }
+
+                let index = u.choose_index(self.functions.len())?;
+                let func_name = self.functions[index].name.clone();
+                let mut args = Vec::new();
+
+                for _ in 0..self.functions[index].arity {
+                    args.push(self.expr(u, depth)?);
+                }
+
+                Expr::Call { func_name, args }
+            },
+
+            6 => Expr::Conditional {
+                cond: Box::new(self.expr(u, depth)?),
+                consequence: Box::new(self.expr(u, depth)?),
+                alternative: Box::new(self.expr(u, depth)?)
+            },
+
+            7 | 8 => {
+                let var_name = self.fresh_variable();
+                let start = f64::from(u.int_in_range(0..=MAX_ITERATIONS)?);
+                let end = f64::from(u.int_in_range(0..=MAX_ITERATIONS)?);
+                let step = match u.arbitrary()? {
+                    true => Some(Box::new(Expr::Number(f64::from(u.int_in_range(1u8..=2)?)))),
+                    false => None
+                };
+
+                // the loop variable is not assignable, so that the loop always terminates
+                self.scope.push(Variable { name: var_name.clone(), assignable: false });
+
+                let body = self.expr(u, depth);
+
+                self.scope.pop();
+
+                Expr::For {
+                    var_name: var_name.clone(),
+                    start: Box::new(Expr::Number(start)),
+                    end: Box::new(Expr::Binary {
+                        op: '<',
+                        left: Box::new(Expr::Variable(var_name)),
+                        right: Box::new(Expr::Number(end))
+                    }),
+                    step,
+                    body: Box::new(body?)
+                }
+            },
+
+            _ => {
+                let depth_before = self.scope.len();
+                let mut variables = Vec::new();
+
+                for _ in 0..u.int_in_range(1..=3)? {
+                    let initializer = match u.arbitrary()? {
+                        true => Some(self.expr(u, depth)?),
+                        false => None
+                    };
+                    let var_name = self.fresh_variable();
+
+                    self.scope.push(Variable { name: var_name.clone(), assignable: true });
+                    variables.push((var_name, initializer));
+                }
+
+                let body = self.expr(u, depth);
+
+                self.scope.truncate(depth_before);
+
+                Expr::VarIn { variables, body: Box::new(body?) }
+            }
+        })
+    }
+
+    fn leaf(&mut self, u: &mut Unstructured) -> Result<Expr> {
+        if !self.scope.is_empty() && u.arbitrary()? {
+            Ok(Expr::Variable(self.scope[u.choose_index(self.scope.len())?].name.clone()))
+        } else {
+            self.number(u)
+        }
+    }
+
+    /// Returns an upper bound of the number of expressions evaluated by the given expression.
+    fn cost(&self, expr: &Expr) -> u64 {
+        match expr {
+            Expr::Number(_) | Expr::Variable(_) => 1,
+
+            Expr::Binary { op, left, right } => {
+                let call = self.binary_operators.iter().find(|(defined, _)| defined == op).map_or(0, |(_, cost)| *cost);
+
+                1 + self.cost(left).saturating_add(self.cost(right)).saturating_add(call)
+            },
+
+            Expr::Call { func_name, args } => {
+                let call = self.functions.iter().find(|fun| &fun.name == func_name).map_or(0, |fun| fun.cost);
+
+                args.iter().fold(1 + call, |cost, arg| cost.saturating_add(self.cost(arg)))
+            },
+
+            Expr::Conditional { cond, consequence, alternative } => {
+                1 + self.cost(cond).saturating_add(self.cost(consequence).max(self.cost(alternative)))
+            },
+
+            Expr::For { start, end, step, body, .. } => {
+                let iteration = self.cost(body).saturating_add(self.cost(end)).saturating_add(step.as_ref().map_or(0, |step| self.cost(step)));
+
+                // the body is evaluated at least once, and the step is at least 1
+                1 + self.cost(start).saturating_add(iteration.saturating_mul(u64::from(MAX_ITERATIONS) + 1))
+            },
+
+            Expr::VarIn { variables, body } => {
+                variables.iter()
+                    .filter_map(|(_, initializer)| initializer.as_ref())
+                    .fold(1 + self.cost(body), |cost, init| cost.saturating_add(self.cost(init)))
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Common interface of the execution backends (the LLVM JIT and the interpreter), so that
+//! the REPL and the tests can run code without knowing how it is executed.
+
+use std::fmt;
+use crate::interp::Interpreter;
+use crate::parser::Function;
+
+/// Defines an error which occurred while compiling or executing a function.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackendError {
+    Compilation(&'static str),
+    Execution(&'static str)
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::Compilation(err) => write!(f, "Error compiling function: {}", err),
+            BackendError::Execution(err) => write!(f, "Error during execution: {}", err)
+        }
+    }
+}
+
+/// Defines a host function which can be called by Kaleidoscope functions, given its number
+/// of parameters.
+#[derive(Debug, Clone, Copy)]
+pub enum HostFunction {
+    Args0(extern "C" fn() -> f64),
+    Args1(extern "C" fn(f64) -> f64),
+    Args2(extern "C" fn(f64, f64) -> f64),
+    Args3(extern "C" fn(f64, f64, f64) -> f64)
+}
+
+impl HostFunction {
+    /// Returns the number of parameters of the function.
+    pub fn arity(&self) -> usize {
+        match self {
+            HostFunction::Args0(_) => 0,
+            HostFunction::Args1(_) => 1,
+            HostFunction::Args2(_) => 2,
+            HostFunction::Args3(_) => 3
+        }
+    }
+
+    /// Returns the address of the function.
+    pub fn address(&self) -> usize {
+        match *self {
+            HostFunction::Args0(function) => function as usize,
+            HostFunction::Args1(function) => function as usize,
+            HostFunction::Args2(function) => function as usize,
+            HostFunction::Args3(function) => function as usize
+        }
+    }
+
+    /// Calls the function with the given arguments, whose number must be its arity.
+    pub fn call(&self, args: &[f64]) -> f64 {
+        match *self {
+            HostFunction::Args0(function) => function(),
+            HostFunction::Args1(function) => function(args[0]),
+            HostFunction::Args2(function) => function(args[0], args[1]),
+            HostFunction::Args3(function) => function(args[0], args[1], args[2])
+        }
+    }
+}
+
+impl From<extern "C" fn() -> f64> for HostFunction {
+    fn from(function: extern "C" fn() -> f64) -> Self {
+        HostFunction::Args0(function)
+    }
+}
+
+impl From<extern "C" fn(f64) -> f64> for HostFunction {
+    fn from(function: extern "C" fn(f64) -> f64) -> Self {
+        HostFunction::Args1(function)
+    }
+}
+
+impl From<extern "C" fn(f64, f64) -> f64> for HostFunction {
+    fn from(function: extern "C" fn(f64, f64) -> f64) -> Self {
+        HostFunction::Args2(function)
+    }
+}
+
+impl From<extern "C" fn(f64, f64, f64) -> f64> for HostFunction {
+    fn from(function: extern "C" fn(f64, f64, f64) -> f64) -> Self {
+        HostFunction::Args3(function)
+    }
+}
+
+/// Checks that the given function, if it is an extern declaration of a host function with
+/// the given arity, declares as many parameters.
+pub fn check_extern(function: &Function, arity: Option<usize>) -> Result<(), &'static str> {
+    match arity {
+        Some(arity) if function.body.is_none() && function.prototype.args.len() != arity => {
+            Err("Incorrect number of parameters for host function.")
+        },
+        _ => Ok(())
+    }
+}
+
+/// Checks that a host function with the given arity can be registered, given the number of
+/// parameters of the `extern` declaration with the same name, if any.
+pub fn check_registration(declared: Option<usize>, arity: usize) -> Result<(), &'static str> {
+    match declared {
+        Some(arg_count) if arg_count != arity => Err("Incorrect number of parameters for host function."),
+        _ => Ok(())
+    }
+}
+
+/// Defines a backend, which compiles (or interprets) and executes functions.
+pub trait Backend {
+    /// Registers a host function, which is called by the function declared with `extern` and
+    /// the given name. Such declarations must declare as many parameters as the host function,
+    /// including the one already evaluated, if any (in which case the registration fails).
+    ///
+    /// Rust functions must be wrapped in `extern "C"` functions, e.g.
+    /// `extern "C" fn sin(x: f64) -> f64 { x.sin() }`, registered with `sin as extern "C" fn(f64) -> f64`.
+    fn register_extern(&mut self, name: &str, function: HostFunction) -> Result<(), &'static str>;
+
+    /// Defines the given function, replacing any previous function with the same name, and
+    /// evaluates it if it is anonymous, returning its result.
+    fn eval(&mut self, function: &Function) -> Result<Option<f64>, BackendError>;
+
+    /// Returns the intermediate representation of the last compiled function, if the backend
+    /// produces any.
+    fn ir(&self) -> Option<&str> {
+        None
+    }
+
+    /// Returns the intermediate representation of every defined function, if the backend
+    /// produces any.
+    fn module_ir(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the native assembly of the defined function with the given name.
+    fn asm(&self, _name: &str) -> Result<String, &'static str> {
+        Err("Native code is not produced by this backend.")
+    }
+}
+
+impl Backend for Interpreter {
+    fn register_extern(&mut self, name: &str, function: HostFunction) -> Result<(), &'static str> {
+        self.register_host_function(name, function)
+    }
+
+    fn eval(&mut self, function: &Function) -> Result<Option<f64>, BackendError> {
+        self.define(function).map_err(BackendError::Compilation)?;
+
+        if function.is_anon {
+            self.call(function.prototype.name.as_str(), &[]).map(Some).map_err(BackendError::Execution)
+        } else {
+            Ok(None)
+        }
+    }
+}
@@ -1,11 +1,29 @@
+#[cfg(feature = "llvm")]
 use inkwell::OptimizationLevel;
-use inkwell::passes::PassManager;
+#[cfg(feature = "llvm")]
 use inkwell::context::Context;
 use std::io::{self, Write};
-use std::collections::HashMap;
-use kaleidoscope::compiler::Compiler;
-use kaleidoscope::parser::Parser;
+use std::path::{Path, PathBuf};
+use kaleidoscope::backend::Backend;
+use kaleidoscope::completion;
+use kaleidoscope::format::{self, FormatOptions};
+use kaleidoscope::interp::Interpreter;
+#[cfg(feature = "llvm")]
+use kaleidoscope::jit::Jit;
 use kaleidoscope::lexer::{Lexer, Token};
+use kaleidoscope::module::Loader;
+use kaleidoscope::operator::OperatorTable;
+use kaleidoscope::optimizer::{self, OptimizeOptions};
+use kaleidoscope::parser::{Function, Item};
+use kaleidoscope::runtime;
+use kaleidoscope::session::Session;
+use rustyline::{Editor, Helper};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
 // macro used to print & flush without printing a new line
 macro_rules! print_flush {
     ( $( $x:expr ),* ) => {
@@ -14,130 +32,377 @@ macro_rules! print_flush {
     };
 }
 
-#[no_mangle]
-pub extern fn putchard(x: f64) -> f64 {
-    print_flush!("{}", x as u8 as char);
-    x
+/// Formats the given files in place (or the standard input if no file is given), and returns
+/// the exit code of the program. With `--check`, files are only checked, for use in CI.
+fn fmt(args: &[String]) -> i32 {
+    let mut check = false;
+    let mut options = FormatOptions::default();
+    let mut files = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--width" => match args.next().and_then(|width| width.parse().ok()) {
+                Some(width) => options.width = width,
+                None => {
+                    eprintln!("!> Expected a number after '--width'.");
+                    return 2;
+                }
+            },
+            file => files.push(file)
+        }
+    }
+
+    if files.is_empty() {
+        let mut input = String::new();
+
+        io::Read::read_to_string(&mut io::stdin(), &mut input).expect("Could not read from standard input.");
+
+        return match format::format(input.as_str(), &OperatorTable::new(), &options) {
+            Ok(formatted) if check && formatted != input => 1,
+            Ok(formatted) => {
+                if !check {
+                    print_flush!("{}", formatted);
+                }
+
+                0
+            },
+            Err(err) => {
+                let (line, column) = err.span.location(input.as_str());
+
+                eprintln!("!> Error parsing <stdin>:{}:{}: {}", line, column, err.error);
+                1
+            }
+        };
+    }
+
+    let mut status = 0;
+
+    for file in files {
+        let source = match std::fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("!> Could not read {}: {}", file, err);
+                status = 1;
+                continue;
+            }
+        };
+
+        let formatted = match format::format(source.as_str(), &OperatorTable::new(), &options) {
+            Ok(formatted) => formatted,
+            Err(err) => {
+                let (line, column) = err.span.location(source.as_str());
+
+                eprintln!("!> Error parsing {}:{}:{}: {}", file, line, column, err.error);
+                status = 1;
+                continue;
+            }
+        };
+
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            println!("{} is not formatted.", file);
+            status = 1;
+        } else if let Err(err) = std::fs::write(file, formatted) {
+            eprintln!("!> Could not write {}: {}", file, err);
+            status = 1;
+        }
+    }
+
+    status
 }
 
-#[no_mangle]
-pub extern fn printd(x: f64) -> f64 {
-    println!("{}", x);
-    x
+/// Defines the options of the REPL, set from the command line.
+struct Options {
+    display_lexer_output: bool,
+    display_parser_output: bool,
+    display_compiler_output: bool,
+    /// Whether to emit debug information, with the LLVM backend.
+    debug_info: bool,
+    /// Whether to evaluate the prelude of operators at the start of the session.
+    prelude: bool,
+    optimize: bool,
+    optimize_options: OptimizeOptions
+}
 
+/// Returns the path of the file in which the history of the REPL is kept.
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".kaleido_history"))
 }
 
-// Adding the functions above to a global array,
-// so Rust compiler won't remove them.
-#[used]
-static EXTERNAL_FNS: [extern fn(f64) -> f64; 2] = [putchard, printd];
+/// Defines the helper of the line editor, which completes the words of the input.
+struct ReplHelper {
+    /// Lines of the input read so far, if it spans several lines.
+    pending: String,
+    /// Names of the functions defined in the session.
+    functions: Vec<String>
+}
 
-/// Entry point of the program; acts as a REPL.
-pub fn main() {
-    // use self::inkwell::support::add_symbol;
-    let mut display_lexer_output = false;
-    let mut display_parser_output = false;
-    let mut display_compiler_output = false;
+impl Completer for ReplHelper {
+    type Candidate = String;
 
-    for arg in std::env::args() {
-        match arg.as_str() {
-            "--dl" => display_lexer_output = true,
-            "--dp" => display_parser_output = true,
-            "--dc" => display_compiler_output = true,
-            _ => ()
+    fn complete(&self, line: &str, pos: usize, _: &rustyline::Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let input = format!("{}{}", self.pending, &line[..pos]);
+        let (start, candidates) = completion::complete(input.as_str(), self.functions.as_slice());
+
+        // the completed word is always on the current line
+        Ok((start - self.pending.len(), candidates))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+type ReplEditor = Editor<ReplHelper, DefaultHistory>;
+
+/// Reads an input, prompting for more lines while it is incomplete. Returns `None` at the end
+/// of the input, and an empty input if it is cancelled with Ctrl-C.
+fn read_input(editor: &mut ReplEditor, session: &Session) -> Option<String> {
+    let mut input = String::new();
+
+    if let Some(helper) = editor.helper_mut() {
+        helper.functions = session.functions().iter().map(|function| function.prototype.name.clone()).collect();
+    }
+
+    loop {
+        if let Some(helper) = editor.helper_mut() {
+            helper.pending = input.clone();
+        }
+
+        let prompt = if input.is_empty() { "?> " } else { ".. " };
+
+        match editor.readline(prompt) {
+            Ok(line) => {
+                input.push_str(line.as_str());
+                input.push('\n');
+            },
+            Err(ReadlineError::Interrupted) => return Some(String::new()),
+            Err(_) => return None
+        }
+
+        if !session.is_incomplete(input.as_str()) {
+            let _ = editor.add_history_entry(input.trim_end());
+
+            return Some(input);
         }
     }
+}
 
-    let context = Context::create();
-    let module = context.create_module("repl");
-    let builder = context.create_builder();
+/// Defines the state of the REPL which is discarded by `:reset`.
+struct State<'a> {
+    session: Session,
+    backend: Box<dyn Backend + 'a>,
+    loader: Loader,
+    /// Functions defined at the start of the session (builtins and prelude), which are not saved.
+    initial: Vec<Function>
+}
+
+impl<'a> State<'a> {
+
+    /// Creates the state of a new session evaluated by the given backend, in which the builtins
+    /// and (unless disabled) the prelude are defined.
+    fn new(options: &Options, mut backend: Box<dyn Backend + 'a>) -> State<'a> {
+        let mut session = Session::new();
+
+        if let Err(err) = runtime::declare(&mut session, backend.as_mut()) {
+            eprintln!("!> Could not declare the builtin functions: {}", err);
+        }
+
+        if options.prelude {
+            if let Err(err) = runtime::load_prelude(&mut session, backend.as_mut()) {
+                eprintln!("!> Could not load the prelude: {}", err);
+            }
+        }
+
+        // modules imported by the REPL are searched in the current directory, then in KALEIDO_PATH
+        let search_path = std::env::var_os("KALEIDO_PATH").map(|path| std::env::split_paths(&path).collect()).unwrap_or_default();
+        let initial = session.functions().to_vec();
+
+        State { session, backend, loader: Loader::new(search_path), initial }
+    }
+}
+
+const HELP: &str = "\
+:help          Print this message.
+:help <name>   Print the declaration and documentation of a function.
+:list          List the defined functions.
+:ir            Print the IR of every defined function.
+:asm <name>    Print the native assembly of a function.
+:load <file>   Evaluate the given file.
+:save <file>   Save the functions defined in the session to the given file.
+:reset         Discard every definition, and start a new session.
+:dl :dp :dc    Toggle the display of the lexer, parser and compiler output.
+:opt           Toggle the AST optimizations.
+:quit          Exit the REPL.";
+
+/// Warning printed when debug information is requested along with the AST optimizations,
+/// which do not keep the locations of the expressions they rewrite.
+const OPTIMIZED_DEBUG_INFO: &str = "!> Debug information is not emitted for functions optimized by the AST optimizer.";
+
+/// Returns a message describing whether a display option is enabled.
+fn toggled(option: &mut bool, name: &str) -> String {
+    *option = !*option;
+    format!("-> {} {}.", name, if *option { "enabled" } else { "disabled" })
+}
+
+/// Returns the declaration of the given function, as `def` or `extern` followed by its prototype.
+fn declaration(function: &Function, operators: &OperatorTable) -> String {
+    let kind = if function.body.is_some() { "def" } else { "extern" };
+
+    format!("{} {}", kind, function.prototype.pretty(operators))
+}
 
-    // Create FPM
-    let fpm = PassManager::create(&module);
+/// Runs the given meta-command (without its leading ':'). Returns `false` if the REPL must exit.
+fn run_command<'a>(command: &str, options: &mut Options, state: &mut State<'a>, new_backend: &dyn Fn() -> Box<dyn Backend + 'a>) -> bool {
+    let (name, arg) = match command.trim().split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (command.trim(), "")
+    };
 
-    fpm.add_instruction_combining_pass();
-    fpm.add_reassociate_pass();
-    fpm.add_gvn_pass();
-    fpm.add_cfg_simplification_pass();
-    fpm.add_basic_alias_analysis_pass();
-    fpm.add_promote_memory_to_register_pass();
-    fpm.add_instruction_combining_pass();
-    fpm.add_reassociate_pass();
+    match (name, arg) {
+        ("help", "") => println!("{}", HELP),
+        ("help", name) => match state.session.get_function(name) {
+            Some(function) => {
+                // the documentation is printed like the doc comments it comes from
+                for line in function.doc.iter().flat_map(|doc| doc.lines()) {
+                    if line.is_empty() {
+                        println!("##");
+                    } else {
+                        println!("## {}", line);
+                    }
+                }
 
-    fpm.initialize();
+                println!("{}", declaration(function, state.session.operators()));
+            },
+            None => println!("!> Unknown function '{}'.", name)
+        },
+        ("quit", _) | ("exit", _) => return false,
+        ("dl", _) => println!("{}", toggled(&mut options.display_lexer_output, "Lexer output")),
+        ("dp", _) => println!("{}", toggled(&mut options.display_parser_output, "Parser output")),
+        ("dc", _) => println!("{}", toggled(&mut options.display_compiler_output, "Compiler output")),
+        ("opt", _) => {
+            println!("{}", toggled(&mut options.optimize, "AST optimizations"));
 
-    let mut previous_exprs = Vec::new();
+            if options.optimize && options.debug_info {
+                eprintln!("{}", OPTIMIZED_DEBUG_INFO);
+            }
+        },
+        ("list", _) => {
+            for function in state.session.functions() {
+                println!("{}", declaration(function, state.session.operators()));
+            }
+        },
+        ("ir", _) => match state.backend.module_ir() {
+            Some(ir) => println!("{}", ir),
+            None => println!("!> The backend does not produce any IR.")
+        },
+        ("asm", "") => println!("!> Expected a function name after ':asm'."),
+        ("asm", name) => match state.backend.asm(name) {
+            Ok(asm) => println!("{}", asm),
+            Err(err) => println!("!> {}", err)
+        },
+        ("load", "") => println!("!> Expected a file after ':load'."),
+        ("load", path) => match state.loader.load(Path::new(path), &mut state.session, state.backend.as_mut()) {
+            Ok(results) => {
+                for result in results {
+                    println!("=> {}", result);
+                }
+            },
+            Err(err) => println!("!> {}", err)
+        },
+        ("save", "") => println!("!> Expected a file after ':save'."),
+        ("save", path) => {
+            // imported modules are imported again, since their private functions cannot be
+            // written in source code, and the other functions follow the functions they use
+            let imported = state.loader.imported();
+            let imports = imported.iter()
+                .map(|module| format!("import \"{}\"\n", module.path.display()))
+                .collect::<String>();
+            let functions = state.session.functions_in_dependency_order().into_iter()
+                .filter(|function| !state.initial.contains(function))
+                .filter(|function| imported.iter().all(|module| !module.functions.contains(function)))
+                .map(|function| format!("{}\n", function.pretty(state.session.operators())));
+            let source = std::iter::once(imports)
+                .filter(|imports| !imports.is_empty())
+                .chain(functions)
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            match std::fs::write(path, source) {
+                Ok(()) => println!("-> Session saved to {}.", path),
+                Err(err) => println!("!> Could not write {}: {}", path, err)
+            }
+        },
+        ("reset", _) => {
+            *state = State::new(options, new_backend());
+            println!("-> Session reset.");
+        },
+        (name, _) => println!("!> Unknown command ':{}', type ':help' for a list of commands.", name)
+    }
+
+    true
+}
+
+/// Runs the REPL, evaluating each input with a backend created by the given function, which
+/// creates a new one whenever the session is reset.
+fn repl<'a>(mut options: Options, new_backend: &dyn Fn() -> Box<dyn Backend + 'a>) {
+    let mut state = State::new(&options, new_backend());
+    let mut editor = ReplEditor::new().expect("Could not initialize the line editor.");
+    let history = history_path();
+
+    editor.set_helper(Some(ReplHelper { pending: String::new(), functions: Vec::new() }));
+
+    if let Some(history) = &history {
+        // the history does not exist on the first run
+        let _ = editor.load_history(history);
+    }
 
     loop {
         println!();
-        print_flush!("?> ");
 
-        // Read input from stdin
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).expect("Could not read from standard input.");
+        let input = match read_input(&mut editor, &state.session) {
+            Some(input) => input,
+            None => break
+        };
 
         if input.starts_with("exit") || input.starts_with("quit") {
             break;
         } else if input.chars().all(char::is_whitespace) {
             continue;
-        }
+        } else if let Some(command) = input.trim_start().strip_prefix(':') {
+            if run_command(command, &mut options, &mut state, new_backend) {
+                continue;
+            }
 
-        // Build precedence map
-        let mut prec = HashMap::with_capacity(6);
+            break;
+        }
 
-        prec.insert('=', 2);
-        prec.insert('<', 10);
-        prec.insert('+', 20);
-        prec.insert('-', 20);
-        prec.insert('*', 40);
-        prec.insert('/', 40);
+        let State { session, backend, loader, .. } = &mut state;
 
         // Parse and (optionally) display input
-        if display_lexer_output {
+        if options.display_lexer_output {
             println!("-> Attempting to parse lexed input: \n{:?}\n", Lexer::new(input.as_str()).collect::<Vec<Token>>());
         }
 
-        // make module
-        let module = context.create_module("tmp");
-
-        // recompile every previously parsed function into the new module
-        for prev in &previous_exprs {
-            Compiler::compile(&context, &builder, &fpm, &module, prev).expect("Cannot re-add previously compiled function.");
-        }
-
-        let (name, is_anonymous) = match Parser::new(input, &mut prec).parse() {
-            Ok(fun) => {
-                let is_anon = fun.is_anon;
-
-                if display_parser_output {
-                    if is_anon {
-                        println!("-> Expression parsed: \n{:?}\n", fun.body);
-                    } else {
-                        println!("-> Function parsed: \n{:?}\n", fun);
-                    }
+        let fun = match session.parse_item(input.as_str()) {
+            Ok(Item::Function { function, .. }) => function,
+            Ok(Item::Import(import)) => {
+                if let Err(err) = loader.import(&import, None, session, backend.as_mut()) {
+                    println!("!> {}", err);
                 }
 
-                match Compiler::compile(&context, &builder, &fpm, &module, &fun) {
-                    Ok(function) => {
-                        if display_compiler_output {
-                            // Not printing a new line since LLVM automatically
-                            // prefixes the generated string with one
-                            print_flush!("-> Expression compiled to IR:");
-                            function.print_to_stderr();
-                        }
-
-                        if !is_anon {
-                            // only add it now to ensure it is correct
-                            previous_exprs.push(fun);
-                        }
-
-                        (function.get_name().to_str().unwrap().to_string(), is_anon)
-                    },
-                    Err(err) => {
-                        println!("!> Error compiling function: {}", err);
-                        continue;
-                    }
-                }
+                continue;
             },
             Err(err) => {
                 println!("!> Error parsing expression: {}", err);
@@ -145,21 +410,137 @@ pub fn main() {
             }
         };
 
-        if is_anonymous {
-            let ee = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+        if options.display_parser_output {
+            if fun.is_anon {
+                println!("-> Expression parsed: \n{}\n", fun.pretty(session.operators()));
+            } else {
+                println!("-> Function parsed: \n{}\n", fun.pretty(session.operators()));
+            }
+        }
 
-            let maybe_fn = unsafe { ee.get_function::<unsafe extern "C" fn() -> f64>(name.as_str()) };
-            let compiled_fn = match maybe_fn {
-                Ok(f) => f,
-                Err(err) => {
-                    println!("!> Error during execution: {:?}", err);
-                    continue;
-                }
-            };
+        let compiled = if options.optimize {
+            let optimized = optimizer::optimize(fun.clone(), session.functions(), &options.optimize_options);
 
-            unsafe {
-                println!("=> {}", compiled_fn.call());
+            if options.display_parser_output {
+                println!("-> Optimized to: \n{}\n", optimized.pretty(session.operators()));
+            }
+
+            optimized
+        } else {
+            fun.clone()
+        };
+
+        let result = backend.eval(&compiled);
+
+        if options.display_compiler_output {
+            if let Some(ir) = backend.ir() {
+                println!("-> Expression compiled to IR:\n{}", ir);
             }
         }
+
+        match result {
+            Ok(result) => {
+                // only add it now to ensure it is correct
+                if let Err(err) = session.define(fun) {
+                    println!("!> {}", err);
+                }
+
+                if let Some(result) = result {
+                    println!("=> {}", result);
+                }
+            },
+            Err(err) => println!("!> {}", err)
+        }
     }
-}
\ No newline at end of file
+
+    if let Some(history) = &history {
+        if let Err(err) = editor.save_history(history) {
+            eprintln!("!> Could not save the history: {}", err);
+        }
+    }
+}
+
+/// Runs the REPL, compiling each input with LLVM and executing it with its JIT.
+#[cfg(feature = "llvm")]
+fn run_llvm(options: Options) {
+    let context = Context::create();
+
+    // debug information is only accurate for unoptimized code
+    let optimization = if options.debug_info { OptimizationLevel::None } else { OptimizationLevel::Default };
+    let debug_info = options.debug_info;
+
+    if options.debug_info && options.optimize {
+        eprintln!("{}", OPTIMIZED_DEBUG_INFO);
+    }
+
+    repl(options, &|| {
+        let mut jit = Jit::new(&context, optimization);
+
+        if debug_info {
+            jit.enable_debug_info("<stdin>");
+        }
+
+        Box::new(jit)
+    })
+}
+
+/// Runs the REPL, evaluating each input with the interpreter.
+fn run_interp(options: Options) {
+    if options.debug_info {
+        eprintln!("!> Debug information is only emitted by the LLVM backend.");
+    }
+
+    repl(options, &|| Box::new(Interpreter::new()))
+}
+
+/// Entry point of the program; acts as a REPL, or as a formatter when invoked as `kaleido fmt`.
+pub fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.get(1).map(String::as_str) == Some("fmt") {
+        std::process::exit(fmt(&args[2..]));
+    }
+
+    let mut backend = if cfg!(feature = "llvm") { "llvm" } else { "interp" };
+    let mut options = Options {
+        display_lexer_output: false,
+        display_parser_output: false,
+        display_compiler_output: false,
+        debug_info: false,
+        prelude: true,
+        optimize: false,
+        optimize_options: OptimizeOptions::default()
+    };
+
+    for arg in &args {
+        match arg.as_str() {
+            "--dl" => options.display_lexer_output = true,
+            "--dp" => options.display_parser_output = true,
+            "--dc" => options.display_compiler_output = true,
+            "-g" | "--debug" => options.debug_info = true,
+            "--opt" => options.optimize = true,
+            "--no-prelude" => options.prelude = false,
+            arg if arg.starts_with("--unroll=") => match arg["--unroll=".len()..].parse() {
+                Ok(limit) => options.optimize_options.unroll_limit = limit,
+                Err(_) => eprintln!("!> Expected a number after '--unroll='.")
+            },
+            arg if arg.starts_with("--backend=") => backend = &arg["--backend=".len()..],
+            _ => ()
+        }
+    }
+
+    match backend {
+        #[cfg(feature = "llvm")]
+        "llvm" => run_llvm(options),
+        "interp" => run_interp(options),
+        #[cfg(not(feature = "llvm"))]
+        "llvm" => {
+            eprintln!("!> The LLVM backend is not available, since the 'llvm' feature is disabled.");
+            std::process::exit(2);
+        },
+        backend => {
+            eprintln!("!> Unknown backend '{}', expected 'llvm' or 'interp'.", backend);
+            std::process::exit(2);
+        }
+    }
+}
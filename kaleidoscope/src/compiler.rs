@@ -1,13 +1,15 @@
 use inkwell::context::Context;
 use inkwell::builder::Builder;
 use inkwell::passes::PassManager;
-use inkwell::values::{FunctionValue, PointerValue, FloatValue, BasicValueEnum, BasicValue};
+use inkwell::values::{FunctionValue, PointerValue, FloatValue, BasicMetadataValueEnum};
 use inkwell::module::Module;
-use crate::parser::{Function, Expr, Prototype};
+use inkwell::debug_info::DISubprogram;
+use crate::debug::{self, DebugInfo};
+use crate::parser::{Function, Expr, Location, Prototype};
 use std::collections::HashMap;
 use std::borrow::Borrow;
 use inkwell::FloatPredicate;
-use inkwell::types::BasicTypeEnum;
+use inkwell::types::BasicMetadataTypeEnum;
 
 /// Defines the `Expr` compiler.
 pub struct Compiler<'a, 'ctx> {
@@ -16,9 +18,15 @@ pub struct Compiler<'a, 'ctx> {
     pub fpm: &'a PassManager<FunctionValue<'ctx>>,
     pub module: &'a Module<'ctx>,
     pub function: &'a Function,
+    pub debug_info: Option<&'a DebugInfo<'ctx>>,
 
     variables: HashMap<String, PointerValue<'ctx>>,
-    fn_value_opt: Option<FunctionValue<'ctx>>
+    fn_value_opt: Option<FunctionValue<'ctx>>,
+
+    // subprogram of the function, if debug information is emitted for it
+    subprogram: Option<DISubprogram<'ctx>>,
+    locations: HashMap<*const Expr, Location>,
+    location: Option<Location>
 }
 
 impl<'a, 'ctx> Compiler<'a, 'ctx> {
@@ -48,8 +56,47 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
         builder.build_alloca(self.context.f64_type(), name)
     }
 
-    /// Compiles the specified `Expr` into an LLVM `FloatValue`.
+    /// Sets the source location of the instructions built from now on, returning the previous one.
+    fn set_location(&mut self, location: Option<Location>) -> Option<Location> {
+        if let (Some(debug_info), Some(subprogram)) = (self.debug_info, self.subprogram) {
+            match location {
+                Some(location) => self.builder.set_current_debug_location(debug_info.create_location(location, subprogram)),
+                None => self.builder.unset_current_debug_location()
+            }
+        }
+
+        std::mem::replace(&mut self.location, location)
+    }
+
+    /// Describes the variable with the given name stored by the given allocation, if debug
+    /// information is emitted. Parameters are given their (1-based) number.
+    fn declare_variable(&self, name: &str, alloca: PointerValue<'ctx>, parameter: Option<u32>) {
+        if let (Some(debug_info), Some(subprogram), Some(location)) = (self.debug_info, self.subprogram, self.location) {
+            let block = self.builder.get_insert_block().unwrap();
+
+            debug_info.declare_variable(subprogram, name, parameter, alloca, location, block);
+        }
+    }
+
+    /// Compiles the specified `Expr` into an LLVM `FloatValue`, located at the expression if
+    /// debug information is emitted.
     fn compile_expr(&mut self, expr: &Expr) -> Result<FloatValue<'ctx>, &'static str> {
+        let location = match self.locations.get(&(expr as *const Expr)) {
+            Some(location) => *location,
+            None => return self.compile_expr_kind(expr)
+        };
+
+        let parent = self.set_location(Some(location));
+        let value = self.compile_expr_kind(expr);
+
+        // the instructions following this expression belong to its parent
+        self.set_location(parent);
+
+        value
+    }
+
+    /// Compiles the specified `Expr` into an LLVM `FloatValue`, given its kind.
+    fn compile_expr_kind(&mut self, expr: &Expr) -> Result<FloatValue<'ctx>, &'static str> {
         match *expr {
             Expr::Number(nb) => Ok(self.context.f64_type().const_float(nb)),
 
@@ -63,7 +110,7 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
             Expr::VarIn { ref variables, ref body } => {
                 let mut old_bindings = Vec::new();
 
-                for &(ref var_name, ref initializer) in variables {
+                for (var_name, initializer) in variables {
                     let var_name = var_name.as_str();
 
                     let initial_val = match *initializer {
@@ -74,18 +121,19 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
                     let alloca = self.create_entry_block_alloca(var_name);
 
                     self.builder.build_store(alloca, initial_val);
+                    self.declare_variable(var_name, alloca, None);
 
-                    if let Some(old_binding) = self.variables.remove(var_name) {
-                        old_bindings.push(old_binding);
-                    }
-
-                    self.variables.insert(var_name.to_string(), alloca);
+                    old_bindings.push((var_name, self.variables.insert(var_name.to_string(), alloca)));
                 }
 
                 let body = self.compile_expr(body)?;
 
-                for binding in old_bindings {
-                    self.variables.insert(binding.get_name().to_str().unwrap().to_string(), binding);
+                // restore the bindings in reverse order, since a variable may be bound several times
+                for (var_name, old_binding) in old_bindings.into_iter().rev() {
+                    match old_binding {
+                        Some(binding) => self.variables.insert(var_name.to_string(), binding),
+                        None => self.variables.remove(var_name)
+                    };
                 }
 
                 Ok(body)
@@ -133,14 +181,14 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
                             name.push(custom);
 
                             match self.get_function(name.as_str()) {
-                                Some(fun) => {
+                                Some(fun) if fun.count_params() == 2 => {
                                     match self.builder.build_call(fun, &[lhs.into(), rhs.into()], "tmpbin").try_as_basic_value().left() {
                                         Some(value) => Ok(value.into_float_value()),
                                         None => Err("Invalid call produced.")
                                     }
                                 },
 
-                                None => Err("Undefined binary operator.")
+                                _ => Err("Undefined binary operator.")
                             }
                         }
                     }
@@ -149,6 +197,7 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
 
             Expr::Call { ref func_name, ref args } => {
                 match self.get_function(func_name.as_str()) {
+                    Some(fun) if fun.count_params() as usize != args.len() => Err("Incorrect number of arguments passed."),
                     Some(fun) => {
                         let mut compiled_args = Vec::with_capacity(args.len());
 
@@ -156,7 +205,7 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
                             compiled_args.push(self.compile_expr(arg)?);
                         }
 
-                        let argsv: Vec<BasicValueEnum> = compiled_args.iter().by_ref().map(|&val| val.into()).collect();
+                        let argsv: Vec<BasicMetadataValueEnum> = compiled_args.iter().by_ref().map(|&val| val.into()).collect();
 
                         match self.builder.build_call(fun, argsv.as_slice(), "tmp").try_as_basic_value().left() {
                             Some(value) => Ok(value.into_float_value()),
@@ -216,6 +265,7 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
                 let start = self.compile_expr(start)?;
 
                 self.builder.build_store(start_alloca, start);
+                self.declare_variable(var_name, start_alloca, None);
 
                 // go from current block to loop block
                 let loop_bb = self.context.append_basic_block(parent, "loop");
@@ -264,10 +314,7 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
     /// Compiles the specified `Prototype` into an extern LLVM `FunctionValue`.
     fn compile_prototype(&self, proto: &Prototype) -> Result<FunctionValue<'ctx>, &'static str> {
         let ret_type = self.context.f64_type();
-        let args_types = std::iter::repeat(ret_type)
-            .take(proto.args.len())
-            .map(|f| f.into())
-            .collect::<Vec<BasicTypeEnum>>();
+        let args_types: Vec<BasicMetadataTypeEnum> = vec![ ret_type.into(); proto.args.len() ];
         let args_types = args_types.as_slice();
 
         let fn_type = self.context.f64_type().fn_type(args_types, false);
@@ -299,6 +346,17 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
         // update fn field
         self.fn_value_opt = Some(function);
 
+        // the builder may still be located in the previously compiled function
+        self.builder.unset_current_debug_location();
+
+        if let Some(debug_info) = self.debug_info {
+            if let Some((definition, locations)) = debug::locations(self.function) {
+                self.subprogram = Some(debug_info.create_function(function, self.function, definition));
+                self.locations = locations;
+                self.set_location(Some(definition));
+            }
+        }
+
         // build variables map
         self.variables.reserve(proto.args.len());
 
@@ -307,6 +365,7 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
             let alloca = self.create_entry_block_alloca(arg_name);
 
             self.builder.build_store(alloca, arg);
+            self.declare_variable(arg_name, alloca, Some(i as u32 + 1));
 
             self.variables.insert(proto.args[i].clone(), alloca);
         }
@@ -316,6 +375,10 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
 
         self.builder.build_return(Some(&body));
 
+        if let (Some(debug_info), Some(subprogram)) = (self.debug_info, self.subprogram) {
+            debug_info.finalize_function(subprogram);
+        }
+
         // return the whole thing after verification and optimization
         if function.verify(true) {
             self.fpm.run_on(&function);
@@ -331,12 +394,14 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
     }
 
     /// Compiles the specified `Function` in the given `Context` and using the specified `Builder`, `PassManager`, and `Module`.
+    /// Debug information is emitted for the functions whose locations are known if `DebugInfo` of the module is given.
     pub fn compile(
         context: &'ctx Context,
         builder: &'a Builder<'ctx>,
         pass_manager: &'a PassManager<FunctionValue<'ctx>>,
         module: &'a Module<'ctx>,
         function: &Function,
+        debug_info: Option<&'a DebugInfo<'ctx>>
     ) -> Result<FunctionValue<'ctx>, &'static str> {
         let mut compiler = Compiler {
             context,
@@ -344,8 +409,12 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
             fpm: pass_manager,
             module,
             function,
+            debug_info,
             fn_value_opt: None,
-            variables: HashMap::new()
+            variables: HashMap::new(),
+            subprogram: None,
+            locations: HashMap::new(),
+            location: None
         };
 
         compiler.compile_fn()
@@ -0,0 +1,101 @@
+//! Completion of the words typed in the REPL.
+
+use crate::lexer::{Lexer, Token, KEYWORDS};
+
+/// Returns the completions of the word at the end of the given input, along with the offset at
+/// which the word starts.
+///
+/// The candidates are the keywords, the given names of the defined functions (except the
+/// private functions of modules), and the parameters and local variables of the function if
+/// the input ends in the body of a definition.
+pub fn complete(input: &str, functions: &[String]) -> (usize, Vec<String>) {
+    let start = input.char_indices()
+        .rev()
+        .take_while(|&(_, ch)| ch == '_' || ch.is_alphanumeric())
+        .last()
+        .map_or(input.len(), |(index, _)| index);
+
+    let word = &input[start..];
+
+    let mut candidates = KEYWORDS.iter()
+        .map(|(keyword, _)| keyword.to_string())
+        .chain(functions.iter().filter(|name| !name.contains('.')).cloned())
+        .chain(parameters(&input[..start]))
+        .filter(|candidate| candidate.starts_with(word))
+        .collect::<Vec<String>>();
+
+    candidates.sort();
+    candidates.dedup();
+
+    (start, candidates)
+}
+
+/// Returns the parameters of the last definition of the given input, if it ends in its body,
+/// along with the variables bound by the `var..in` and `for..in` expressions of the body.
+fn parameters(input: &str) -> Vec<String> {
+    let tokens = Lexer::new(input).collect::<Vec<Token>>();
+
+    let def = match tokens.iter().rposition(|token| *token == Token::Def) {
+        Some(def) => def,
+        None => return Vec::new()
+    };
+
+    // the parameters are between the first parentheses following 'def'
+    let mut tokens = tokens[def..].iter().skip_while(|token| **token != Token::LParen).skip(1);
+    let mut parameters = Vec::new();
+
+    loop {
+        match tokens.next() {
+            Some(Token::Ident(name)) => parameters.push(name.clone()),
+            Some(Token::Comma) => (),
+            Some(Token::RParen) => break,
+            // the input ends in the prototype
+            _ => return Vec::new()
+        }
+    }
+
+    parameters.extend(variables(tokens.cloned().collect::<Vec<Token>>().as_slice()));
+    parameters
+}
+
+/// Returns the names bound by the `var..in` and `for..in` expressions of the given tokens.
+fn variables(tokens: &[Token]) -> Vec<String> {
+    // loops and variable declarations whose 'in' keyword has not been reached yet, along with
+    // the depth of parentheses at which they appear
+    let mut bindings = Vec::new();
+    let mut depth = 0;
+    let mut variables = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        let bound = match token {
+            Token::For | Token::Var => {
+                bindings.push((token, depth));
+                true
+            },
+            // the following variables of a declaration are separated by commas
+            Token::Comma => bindings.last() == Some(&(&Token::Var, depth)),
+            Token::In => {
+                if bindings.last().is_some_and(|&(_, binding_depth)| binding_depth == depth) {
+                    bindings.pop();
+                }
+
+                false
+            },
+            Token::LParen => {
+                depth += 1;
+                false
+            },
+            Token::RParen => {
+                depth -= 1;
+                false
+            },
+            _ => false
+        };
+
+        if let (true, Some(Token::Ident(name))) = (bound, tokens.get(i + 1)) {
+            variables.push(name.clone());
+        }
+    }
+
+    variables
+}
@@ -0,0 +1,173 @@
+//! Emission of DWARF debug information for the functions compiled by the `Compiler`, from the
+//! locations recorded by the `Parser` (as in chapter 9 of the LLVM tutorial).
+//!
+//! Each function gets a subprogram whose parameters and local variables are described, and its
+//! instructions are located at the expression they were compiled from.
+
+use std::collections::HashMap;
+use std::path::Path;
+use inkwell::basic_block::BasicBlock;
+use inkwell::context::Context;
+use inkwell::debug_info::{
+    self, AsDIScope, DIBasicType, DICompileUnit, DIFlags, DIFlagsConstants, DILocation, DISubprogram,
+    DWARFEmissionKind, DWARFSourceLanguage, DebugInfoBuilder
+};
+use inkwell::module::{FlagBehavior, Module};
+use inkwell::values::{FunctionValue, PointerValue};
+use llvm_sys::debuginfo::LLVMDIBuilderFinalizeSubprogram;
+use crate::parser::{Expr, Function, Location};
+use crate::visit::{self, Visitor};
+
+/// DWARF encoding of floating-point types (`DW_ATE_float`).
+const DW_ATE_FLOAT: u32 = 0x04;
+
+/// Defines the debug information of a module, shared by the functions compiled into it.
+///
+/// The debug information must be finalized once every function has been compiled, before the
+/// module is given to an execution engine.
+pub struct DebugInfo<'ctx> {
+    context: &'ctx Context,
+    builder: DebugInfoBuilder<'ctx>,
+    compile_unit: DICompileUnit<'ctx>,
+    double_type: DIBasicType<'ctx>
+}
+
+impl<'ctx> DebugInfo<'ctx> {
+
+    /// Creates the debug information of the given module, whose functions are defined in the
+    /// source file at the given path.
+    pub fn new(context: &'ctx Context, module: &Module<'ctx>, path: &str) -> DebugInfo<'ctx> {
+        let path = Path::new(path);
+        let filename = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        let directory = match path.parent().map(|parent| parent.to_string_lossy()) {
+            Some(parent) if !parent.is_empty() => parent,
+            _ => ".".into()
+        };
+
+        // debug information is dropped from modules which do not declare its version
+        module.add_basic_value_flag(
+            "Debug Info Version",
+            FlagBehavior::Warning,
+            context.i32_type().const_int(debug_info::debug_metadata_version() as u64, false)
+        );
+
+        let (builder, compile_unit) = module.create_debug_info_builder(
+            true,
+            DWARFSourceLanguage::C,
+            &filename,
+            &directory,
+            "Kaleidoscope Compiler",
+            false,
+            "",
+            0,
+            "",
+            DWARFEmissionKind::Full,
+            0,
+            false,
+            false,
+            "",
+            ""
+        );
+
+        let double_type = builder.create_basic_type("double", 64, DW_ATE_FLOAT, DIFlags::PUBLIC)
+            .expect("Cannot create debug information of type double.");
+
+        DebugInfo { context, builder, compile_unit, double_type }
+    }
+
+    /// Creates the subprogram of the given compiled function, defined at the given location.
+    pub fn create_function(&self, function_value: FunctionValue<'ctx>, function: &Function, location: Location) -> DISubprogram<'ctx> {
+        let file = self.compile_unit.get_file();
+        let double_type = self.double_type.as_type();
+        let parameters = vec![ double_type; function.prototype.args.len() ];
+        let subroutine_type = self.builder.create_subroutine_type(file, Some(double_type), &parameters, DIFlags::PUBLIC);
+
+        let subprogram = self.builder.create_function(
+            self.compile_unit.as_debug_info_scope(),
+            &function.prototype.name,
+            None,
+            file,
+            location.line as u32,
+            subroutine_type,
+            false,
+            true,
+            location.line as u32,
+            DIFlags::PROTOTYPED,
+            false
+        );
+
+        function_value.set_subprogram(subprogram);
+        subprogram
+    }
+
+    /// Returns the debug location of the given location in the given function.
+    pub fn create_location(&self, location: Location, scope: DISubprogram<'ctx>) -> DILocation<'ctx> {
+        self.builder.create_debug_location(self.context, location.line as u32, location.column as u32, scope.as_debug_info_scope(), None)
+    }
+
+    /// Describes the variable with the given name declared at the given location in the given
+    /// function, and stored at the given address. Parameters are given their (1-based) number.
+    ///
+    /// The declaration is inserted at the end of the given block.
+    pub fn declare_variable(
+        &self,
+        scope: DISubprogram<'ctx>,
+        name: &str,
+        parameter: Option<u32>,
+        address: PointerValue<'ctx>,
+        location: Location,
+        block: BasicBlock<'ctx>
+    ) {
+        let file = self.compile_unit.get_file();
+        let di_scope = scope.as_debug_info_scope();
+        let line = location.line as u32;
+        let double_type = self.double_type.as_type();
+
+        let variable = match parameter {
+            Some(number) => self.builder.create_parameter_variable(di_scope, name, number, file, line, double_type, true, DIFlags::ZERO),
+            None => self.builder.create_auto_variable(di_scope, name, file, line, double_type, true, DIFlags::ZERO, 0)
+        };
+
+        self.builder.insert_declare_at_end(address, Some(variable), None, self.create_location(location, scope), block);
+    }
+
+    /// Finalizes the subprogram of a function once its body has been compiled, so that the
+    /// function can be verified.
+    pub fn finalize_function(&self, subprogram: DISubprogram<'ctx>) {
+        unsafe { LLVMDIBuilderFinalizeSubprogram(self.builder.as_mut_ptr(), subprogram.as_mut_ptr()) };
+    }
+
+    /// Finalizes the debug information, once every function of the module has been compiled.
+    pub fn finalize(&self) {
+        self.builder.finalize();
+    }
+}
+
+/// Collects the expressions of a function in the order of their locations.
+struct Expressions {
+    expressions: Vec<*const Expr>
+}
+
+impl Visitor for Expressions {
+    fn visit_expr(&mut self, expr: &Expr) {
+        self.expressions.push(expr);
+        visit::walk_expr(self, expr);
+    }
+}
+
+/// Returns the location of the definition of the given function and the locations of its
+/// expressions by address, or `None` if they are unknown or do not match its body.
+pub fn locations(function: &Function) -> Option<(Location, HashMap<*const Expr, Location>)> {
+    let locations = function.locations.as_ref()?;
+    let mut expressions = Expressions { expressions: Vec::new() };
+
+    if let Some(body) = &function.body {
+        expressions.visit_expr(body);
+    }
+
+    if expressions.expressions.len() != locations.expressions.len() {
+        return None;
+    }
+
+    Some((locations.definition, expressions.expressions.into_iter().zip(locations.expressions.iter().copied()).collect()))
+}
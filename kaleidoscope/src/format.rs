@@ -0,0 +1,435 @@
+//! Source code formatter, working on the lossless syntax tree so that comments are preserved.
+//!
+//! The syntax tree is first converted to a `Doc`, which describes the possible layouts of the
+//! code (in the fashion of Wadler's "prettier printer"), and then rendered to fit the
+//! requested line width.
+
+use crate::operator::OperatorTable;
+use crate::syntax::{self, SyntaxElement, SyntaxError, SyntaxKind, SyntaxNode};
+
+/// Defines the options of the formatter.
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// Maximum width of a line, which the formatter tries not to exceed.
+    pub width: usize,
+    /// Number of spaces used for each level of indentation.
+    pub indent: usize
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            width: 80,
+            indent: 4
+        }
+    }
+}
+
+/// Formats the given source code, failing if it contains a syntax error.
+pub fn format(input: &str, operators: &OperatorTable, options: &FormatOptions) -> Result<String, SyntaxError> {
+    let tree = syntax::parse(input, operators);
+
+    if let Some(err) = tree.errors().first() {
+        return Err(err.clone());
+    }
+
+    let formatter = Formatter { options };
+    let doc = formatter.source(tree.root());
+
+    Ok(render(&doc, options.width))
+}
+
+// ======================================================================================
+// DOCUMENTS ============================================================================
+// ======================================================================================
+
+/// Defines a document, which describes the layouts the formatted code can take.
+#[derive(Debug, Clone)]
+enum Doc {
+    Text(String),
+    /// A space, or a new line if the enclosing group does not fit on a single line.
+    Line,
+    /// Nothing, or a new line if the enclosing group does not fit on a single line.
+    SoftLine,
+    /// A new line, which forces all enclosing groups to be broken.
+    HardLine,
+    Nest(usize, Box<Doc>),
+    Group(Box<Doc>),
+    Concat(Vec<Doc>)
+}
+
+fn text<S: Into<String>>(text: S) -> Doc {
+    Doc::Text(text.into())
+}
+
+fn nest(indent: usize, doc: Doc) -> Doc {
+    Doc::Nest(indent, Box::new(doc))
+}
+
+fn group(doc: Doc) -> Doc {
+    Doc::Group(Box::new(doc))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Flat,
+    Break
+}
+
+/// Returns a value indicating whether the given document fits in the given width
+/// when printed on a single line.
+fn fits(doc: &Doc, width: isize) -> bool {
+    let mut remaining = width;
+    let mut stack = vec![doc];
+
+    while let Some(doc) = stack.pop() {
+        match doc {
+            Doc::Text(text) => remaining -= text.chars().count() as isize,
+            Doc::Line => remaining -= 1,
+            Doc::SoftLine => (),
+            Doc::HardLine => return false,
+            Doc::Nest(_, doc) | Doc::Group(doc) => stack.push(doc),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev())
+        }
+
+        if remaining < 0 {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Renders the given document, breaking the groups which do not fit in the given width.
+fn render(doc: &Doc, width: usize) -> String {
+    let mut out = String::new();
+    let mut column = 0;
+    let mut stack = vec![(0, Mode::Break, doc)];
+
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => {
+                out.push_str(text);
+                column += text.chars().count();
+            },
+
+            Doc::Line | Doc::SoftLine if mode == Mode::Flat => {
+                if let Doc::Line = doc {
+                    out.push(' ');
+                    column += 1;
+                }
+            },
+
+            Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                out.push('\n');
+                out.push_str(&" ".repeat(indent));
+                column = indent;
+            },
+
+            Doc::Nest(nested, doc) => stack.push((indent + nested, mode, doc)),
+
+            Doc::Group(doc) => {
+                let mode = if mode == Mode::Flat || fits(doc, width as isize - column as isize) {
+                    Mode::Flat
+                } else {
+                    Mode::Break
+                };
+
+                stack.push((indent, mode, doc));
+            },
+
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)))
+        }
+    }
+
+    // remove the indentation left on empty lines
+    let mut formatted = out.lines().map(str::trim_end).collect::<Vec<&str>>().join("\n");
+
+    formatted.push('\n');
+    formatted
+}
+
+// ======================================================================================
+// FORMATTING ===========================================================================
+// ======================================================================================
+
+/// Defines a significant element of a node, along with the comments which precede it.
+struct Part<'a> {
+    comments: Vec<Doc>,
+    element: SyntaxElement<'a>
+}
+
+struct Formatter<'a> {
+    options: &'a FormatOptions
+}
+
+impl<'a> Formatter<'a> {
+
+    /// Returns the document corresponding to a comment.
+    fn comment(&self, text: &str) -> Doc {
+        if text.starts_with("#[") {
+            Doc::Concat(vec![ self::text(text), self::text(" ") ])
+        } else {
+            Doc::Concat(vec![ self::text(text.trim_end()), Doc::HardLine ])
+        }
+    }
+
+    /// Splits the children of the given node in significant parts, attaching comments
+    /// to the element which follows them.
+    fn parts<'n>(&self, node: SyntaxNode<'n>) -> Vec<Part<'n>> {
+        let mut parts = Vec::new();
+        let mut comments = Vec::new();
+
+        for child in node.children_with_tokens() {
+            match child {
+                SyntaxElement::Token(token) if token.kind() == SyntaxKind::Whitespace => (),
+                SyntaxElement::Token(token) if token.kind().is_trivia() => comments.push(self.comment(token.text())),
+                element => parts.push(Part { comments: std::mem::take(&mut comments), element })
+            }
+        }
+
+        // comments cannot trail a node, since trivia are always added before the next token
+        parts
+    }
+
+    /// Returns the document of a part, including its comments.
+    fn part(&self, part: &Part) -> Doc {
+        let mut docs = part.comments.clone();
+
+        docs.push(match part.element {
+            SyntaxElement::Node(node) => self.node(node),
+            SyntaxElement::Token(token) => text(token.text())
+        });
+
+        Doc::Concat(docs)
+    }
+
+    /// Returns the indented body of a definition or an expression, which starts on a
+    /// new line if it does not fit on the current one.
+    fn body(&self, part: &Part) -> Doc {
+        nest(self.options.indent, Doc::Concat(vec![ Doc::Line, self.part(part) ]))
+    }
+
+    /// Returns the document of the whole source code.
+    fn source(&self, root: SyntaxNode) -> Doc {
+        let mut docs = Vec::new();
+        let mut newlines = 0;
+        let mut first = true;
+
+        for child in root.children_with_tokens() {
+            match child {
+                SyntaxElement::Token(token) if token.kind() == SyntaxKind::Whitespace => {
+                    newlines += token.text().matches('\n').count();
+                },
+
+                SyntaxElement::Token(token) if !first && newlines == 0 => {
+                    // comment trailing an item
+                    docs.push(text(" "));
+                    docs.push(text(token.text().trim_end()));
+                },
+
+                element => {
+                    if !first {
+                        docs.push(Doc::HardLine);
+
+                        if newlines > 1 {
+                            docs.push(Doc::HardLine);
+                        }
+                    }
+
+                    docs.push(match element {
+                        SyntaxElement::Node(node) => self.node(node),
+                        SyntaxElement::Token(token) => text(token.text().trim_end())
+                    });
+
+                    newlines = 0;
+                    first = false;
+                }
+            }
+        }
+
+        Doc::Concat(docs)
+    }
+
+    /// Returns the document of the given node.
+    fn node(&self, node: SyntaxNode) -> Doc {
+        let parts = self.parts(node);
+        let indent = self.options.indent;
+
+        match node.kind() {
+            SyntaxKind::Definition | SyntaxKind::ExternDecl => {
+                // doc comments are kept outside of the group, since they always end with a new line
+                let mut docs = vec![ self.part(&Part { comments: Vec::new(), element: parts[0].element }) ];
+
+                // the prototype follows the keyword, itself preceded by 'export' if exported
+                let prototype = if parts[0].element.kind() == SyntaxKind::ExportKw { 2 } else { 1 };
+
+                for part in &parts[1..=prototype] {
+                    docs.push(text(" "));
+                    docs.push(self.part(part));
+                }
+
+                if let Some(body) = parts.get(prototype + 1) {
+                    docs.push(self.body(body));
+                }
+
+                let mut item = parts[0].comments.clone();
+
+                item.push(group(Doc::Concat(docs)));
+
+                Doc::Concat(item)
+            },
+
+            SyntaxKind::ImportDecl => Doc::Concat(vec![ self.part(&parts[0]), text(" "), self.part(&parts[1]) ]),
+
+            SyntaxKind::Prototype => {
+                let mut docs = Vec::new();
+
+                for part in &parts {
+                    if part.element.kind() == SyntaxKind::Number {
+                        // precedence of a binary operator, e.g. 'binary| 5 (a, b)'
+                        docs.push(text(" "));
+                        docs.push(self.part(part));
+                        docs.push(text(" "));
+                    } else {
+                        docs.push(self.part(part));
+                    }
+                }
+
+                Doc::Concat(docs)
+            },
+
+            SyntaxKind::ParamList | SyntaxKind::ArgList => {
+                let mut items = Vec::new();
+
+                for part in &parts[1..parts.len() - 1] {
+                    match part.element.kind() {
+                        SyntaxKind::Comma => {
+                            items.extend(part.comments.iter().cloned());
+                            items.push(text(","));
+                            items.push(Doc::Line);
+                        },
+                        _ => items.push(self.part(part))
+                    }
+                }
+
+                let close = self.part(&parts[parts.len() - 1]);
+
+                if items.is_empty() {
+                    return Doc::Concat(vec![ self.part(&parts[0]), close ]);
+                }
+
+                group(Doc::Concat(vec![
+                    self.part(&parts[0]),
+                    nest(indent, Doc::Concat(vec![ Doc::SoftLine, Doc::Concat(items) ])),
+                    Doc::SoftLine,
+                    close
+                ]))
+            },
+
+            SyntaxKind::TopLevelExpr | SyntaxKind::NumberExpr | SyntaxKind::VariableExpr |
+            SyntaxKind::CallExpr | SyntaxKind::ParenExpr | SyntaxKind::UnaryExpr => {
+                Doc::Concat(parts.iter().map(|part| self.part(part)).collect())
+            },
+
+            // break after the assignment operator rather than before it
+            SyntaxKind::BinaryExpr if node.child_token(SyntaxKind::Op).map(|op| op.text()) == Some("=") => {
+                group(Doc::Concat(vec![
+                    self.part(&parts[0]),
+                    text(" "),
+                    self.part(&parts[1]),
+                    self.body(&parts[2])
+                ]))
+            },
+
+            SyntaxKind::BinaryExpr => group(Doc::Concat(vec![
+                self.part(&parts[0]),
+                nest(indent, Doc::Concat(vec![
+                    Doc::Line,
+                    self.part(&parts[1]),
+                    text(" "),
+                    self.part(&parts[2])
+                ]))
+            ])),
+
+            SyntaxKind::IfExpr => {
+                let alternative = if parts[5].element.kind() == SyntaxKind::IfExpr && parts[5].comments.is_empty() {
+                    // do not indent 'else if' chains
+                    Doc::Concat(vec![ text(" "), self.part(&parts[5]) ])
+                } else {
+                    self.body(&parts[5])
+                };
+
+                group(Doc::Concat(vec![
+                    self.part(&parts[0]),
+                    text(" "),
+                    self.part(&parts[1]),
+                    text(" "),
+                    self.part(&parts[2]),
+                    self.body(&parts[3]),
+                    Doc::Line,
+                    self.part(&parts[4]),
+                    alternative
+                ]))
+            },
+
+            SyntaxKind::ForExpr => {
+                // for <ident> = <start>, <end> [, <step>] in <body>
+                let (body, header) = parts.split_last().expect("Invalid for loop.");
+                let mut docs = Vec::new();
+
+                for part in header {
+                    match part.element.kind() {
+                        SyntaxKind::ForKw => docs.push(self.part(part)),
+                        SyntaxKind::Comma => docs.push(self.part(part)),
+                        _ => {
+                            docs.push(text(" "));
+                            docs.push(self.part(part));
+                        }
+                    }
+                }
+
+                docs.push(self.body(body));
+
+                group(Doc::Concat(docs))
+            },
+
+            SyntaxKind::VarExpr => {
+                // var <binding> [, <binding>]* in <body>
+                let (body, header) = parts.split_last().expect("Invalid var..in expression.");
+                let mut docs = Vec::new();
+
+                for part in header {
+                    match part.element.kind() {
+                        SyntaxKind::VarKw | SyntaxKind::Comma => docs.push(self.part(part)),
+                        _ => {
+                            docs.push(text(" "));
+                            docs.push(self.part(part));
+                        }
+                    }
+                }
+
+                docs.push(self.body(body));
+
+                group(Doc::Concat(docs))
+            },
+
+            SyntaxKind::VarBinding => {
+                let mut docs = Vec::new();
+
+                for (i, part) in parts.iter().enumerate() {
+                    if i > 0 {
+                        docs.push(text(" "));
+                    }
+
+                    docs.push(self.part(part));
+                }
+
+                Doc::Concat(docs)
+            },
+
+            // other nodes never appear in a tree without errors; keep them untouched
+            _ => text(node.text())
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! Registration of JIT-compiled code with debuggers, through the GDB JIT interface.
+//!
+//! The object linking layer of the `Jit` registers every object file it loads with the
+//! interface implemented by LLVM, which is also understood by LLDB: the debugger sets a
+//! breakpoint on `__jit_debug_register_code`, and reads the objects listed by
+//! `__jit_debug_descriptor` whenever it is called. Breakpoints can thus be set on the functions
+//! defined in the REPL, and backtraces through compiled code are symbolized (with source
+//! locations if debug information is emitted).
+//!
+//! The object of a function is registered once the function is first called, and stays
+//! registered until the function is redefined. The object of a top-level expression is only
+//! registered while the expression is executed.
+
+use std::slice;
+
+/// Defines an object file registered with the interface.
+#[repr(C)]
+#[allow(dead_code)]
+struct JitCodeEntry {
+    next_entry: *const JitCodeEntry,
+    prev_entry: *const JitCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64
+}
+
+/// Defines the list of registered object files, read by debuggers.
+#[repr(C)]
+#[allow(dead_code)]
+struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *const JitCodeEntry,
+    first_entry: *const JitCodeEntry
+}
+
+// defined by the GDB registration listener of LLVM
+extern "C" {
+    static __jit_debug_descriptor: JitDescriptor;
+}
+
+/// Returns a copy of the object files currently registered with the GDB JIT interface.
+///
+/// The registered objects must not change during the call, which is the case when it is
+/// called from compiled code or when no other thread evaluates code.
+pub fn registered_objects() -> Vec<Vec<u8>> {
+    let mut objects = Vec::new();
+
+    unsafe {
+        let mut entry = std::ptr::addr_of!(__jit_debug_descriptor).read_volatile().first_entry;
+
+        while let Some(code) = entry.as_ref() {
+            objects.push(slice::from_raw_parts(code.symfile_addr, code.symfile_size as usize).to_vec());
+            entry = code.next_entry;
+        }
+    }
+
+    objects
+}
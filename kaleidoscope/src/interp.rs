@@ -0,0 +1,332 @@
+//! Tree-walking interpreter, which evaluates the AST directly with the same semantics as the
+//! code generated by the `Compiler`, and thus does not require LLVM.
+//!
+//! Like the `Compiler`, the interpreter rejects functions which use undefined variables or
+//! functions when they are defined, rather than when (and if) the faulty code is reached.
+
+use std::collections::HashMap;
+use crate::backend::{self, HostFunction};
+use crate::parser::{Expr, Function, Prototype};
+
+/// Returns a value indicating whether the given value is considered true by a condition.
+pub(crate) fn is_true(value: f64) -> bool {
+    // conditions are compiled to an ordered comparison ('one') with 0
+    !value.is_nan() && value != 0.
+}
+
+/// Returns the result of a builtin binary operator, or `None` if the operator is not builtin.
+pub(crate) fn builtin_binary(op: char, left: f64, right: f64) -> Option<f64> {
+    let bool_to_f64 = |value: bool| if value { 1. } else { 0. };
+    let unordered = left.is_nan() || right.is_nan();
+
+    match op {
+        '+' => Some(left + right),
+        '-' => Some(left - right),
+        '*' => Some(left * right),
+        '/' => Some(left / right),
+        // comparisons are compiled to unordered comparisons ('ult'), which are true for NaN
+        '<' => Some(bool_to_f64(unordered || left < right)),
+        '>' => Some(bool_to_f64(unordered || left > right)),
+        _ => None
+    }
+}
+
+/// Defines a function provided by the host, callable from Kaleidoscope once declared with `extern`.
+pub type ExternalFunction = Box<dyn Fn(&[f64]) -> f64>;
+
+/// Defines the interpreter, which holds the defined functions.
+pub struct Interpreter {
+    functions: HashMap<String, Function>,
+    externs: HashMap<String, ExternalFunction>,
+    // number of parameters of the externs registered as host functions
+    arities: HashMap<String, usize>
+}
+
+impl Interpreter {
+
+    /// Creates a new interpreter, without any function.
+    pub fn new() -> Interpreter {
+        Interpreter {
+            functions: HashMap::new(),
+            externs: HashMap::new(),
+            arities: HashMap::new()
+        }
+    }
+
+    /// Registers a closure as a host function, which is called by the Kaleidoscope function
+    /// declared with `extern` and the given name.
+    ///
+    /// Unlike `Backend::register_extern`, the number of parameters of the declaration is not
+    /// checked, since the closure is given all the arguments of the call.
+    pub fn register_closure<F: Fn(&[f64]) -> f64 + 'static>(&mut self, name: &str, function: F) {
+        self.externs.insert(name.to_string(), Box::new(function));
+        self.arities.remove(name);
+    }
+
+    /// Registers a host function like `register_closure`, whose declaration must declare as
+    /// many parameters as it has.
+    pub(crate) fn register_host_function(&mut self, name: &str, function: HostFunction) -> Result<(), &'static str> {
+        let declared = self.functions.get(name).filter(|fun| fun.body.is_none()).map(|fun| fun.prototype.args.len());
+
+        backend::check_registration(declared, function.arity())?;
+
+        self.register_closure(name, move |args| function.call(args));
+        self.arities.insert(name.to_string(), function.arity());
+
+        Ok(())
+    }
+
+    /// Defines the given function or extern declaration, replacing any previous function
+    /// with the same name.
+    pub fn define(&mut self, function: &Function) -> Result<(), &'static str> {
+        backend::check_extern(function, self.arities.get(&function.prototype.name).copied())?;
+
+        if let Some(body) = &function.body {
+            let mut scope = function.prototype.args.iter().map(String::as_str).collect();
+
+            self.check(body, &function.prototype, &mut scope)?;
+        }
+
+        self.functions.insert(function.prototype.name.clone(), function.clone());
+
+        Ok(())
+    }
+
+    /// Calls the function with the given name.
+    pub fn call(&self, name: &str, args: &[f64]) -> Result<f64, &'static str> {
+        let function = self.functions.get(name).ok_or("Unknown function.")?;
+
+        if function.prototype.args.len() != args.len() {
+            return Err("Incorrect number of arguments passed.");
+        }
+
+        match &function.body {
+            Some(body) => {
+                let mut variables = function.prototype.args.iter().cloned().zip(args.iter().copied()).collect();
+
+                self.eval_expr(body, &mut variables)
+            },
+
+            None => match self.externs.get(name) {
+                Some(external) => Ok(external(args)),
+                None => Err("Unknown external function.")
+            }
+        }
+    }
+
+    /// Returns a value indicating whether the given function can be called with the given
+    /// number of arguments, given the function which is being defined.
+    fn check_call(&self, name: &str, arg_count: usize, current: &Prototype) -> Result<(), &'static str> {
+        let proto = if name == current.name {
+            current
+        } else {
+            &self.functions.get(name).ok_or("Unknown function.")?.prototype
+        };
+
+        if proto.args.len() == arg_count {
+            Ok(())
+        } else {
+            Err("Incorrect number of arguments passed.")
+        }
+    }
+
+    /// Checks that the given expression only uses defined variables and functions, like
+    /// the `Compiler` does.
+    fn check<'a>(&self, expr: &'a Expr, current: &Prototype, scope: &mut Vec<&'a str>) -> Result<(), &'static str> {
+        match expr {
+            Expr::Number(_) => Ok(()),
+
+            Expr::Variable(name) if scope.contains(&name.as_str()) => Ok(()),
+            Expr::Variable(_) => Err("Could not find a matching variable."),
+
+            Expr::Binary { op: '=', left, right } => {
+                let var_name = match left.as_ref() {
+                    Expr::Variable(var_name) => var_name,
+                    _ => return Err("Expected variable as left-hand operator of assignement.")
+                };
+
+                self.check(right, current, scope)?;
+
+                if scope.contains(&var_name.as_str()) {
+                    Ok(())
+                } else {
+                    Err("Undefined variable.")
+                }
+            },
+
+            Expr::Binary { op, left, right } => {
+                self.check(left, current, scope)?;
+                self.check(right, current, scope)?;
+
+                if builtin_binary(*op, 0., 0.).is_none() {
+                    self.check_call(format!("binary{}", op).as_str(), 2, current).map_err(|_| "Undefined binary operator.")?;
+                }
+
+                Ok(())
+            },
+
+            Expr::Call { func_name, args } => {
+                self.check_call(func_name, args.len(), current)?;
+
+                args.iter().try_for_each(|arg| self.check(arg, current, scope))
+            },
+
+            Expr::Conditional { cond, consequence, alternative } => {
+                self.check(cond, current, scope)?;
+                self.check(consequence, current, scope)?;
+                self.check(alternative, current, scope)
+            },
+
+            Expr::For { var_name, start, end, step, body } => {
+                self.check(start, current, scope)?;
+
+                scope.push(var_name);
+
+                let result = self.check(body, current, scope)
+                    .and_then(|_| step.as_ref().map_or(Ok(()), |step| self.check(step, current, scope)))
+                    .and_then(|_| self.check(end, current, scope));
+
+                scope.pop();
+                result
+            },
+
+            Expr::VarIn { variables, body } => {
+                let depth = scope.len();
+                let mut result = Ok(());
+
+                for (var_name, initializer) in variables {
+                    if let Some(init) = initializer {
+                        result = result.and_then(|_| self.check(init, current, scope));
+                    }
+
+                    scope.push(var_name);
+                }
+
+                result = result.and_then(|_| self.check(body, current, scope));
+
+                scope.truncate(depth);
+                result
+            }
+        }
+    }
+
+    /// Evaluates the given expression, given the variables in scope (the innermost last).
+    fn eval_expr(&self, expr: &Expr, variables: &mut Vec<(String, f64)>) -> Result<f64, &'static str> {
+        match expr {
+            Expr::Number(nb) => Ok(*nb),
+
+            Expr::Variable(name) => Interpreter::lookup(variables, name).map(|var| *var),
+
+            Expr::Binary { op: '=', left, right } => {
+                let var_name = match left.as_ref() {
+                    Expr::Variable(var_name) => var_name,
+                    _ => return Err("Expected variable as left-hand operator of assignement.")
+                };
+
+                let value = self.eval_expr(right, variables)?;
+
+                *Interpreter::lookup(variables, var_name)? = value;
+
+                Ok(value)
+            },
+
+            Expr::Binary { op, left, right } => {
+                let left = self.eval_expr(left, variables)?;
+                let right = self.eval_expr(right, variables)?;
+
+                match builtin_binary(*op, left, right) {
+                    Some(value) => Ok(value),
+                    None => self.call(format!("binary{}", op).as_str(), &[ left, right ])
+                }
+            },
+
+            Expr::Call { func_name, args } => {
+                let mut values = Vec::with_capacity(args.len());
+
+                for arg in args {
+                    values.push(self.eval_expr(arg, variables)?);
+                }
+
+                self.call(func_name, values.as_slice())
+            },
+
+            Expr::Conditional { cond, consequence, alternative } => {
+                if is_true(self.eval_expr(cond, variables)?) {
+                    self.eval_expr(consequence, variables)
+                } else {
+                    self.eval_expr(alternative, variables)
+                }
+            },
+
+            Expr::For { var_name, start, end, step, body } => {
+                let start = self.eval_expr(start, variables)?;
+
+                variables.push((var_name.clone(), start));
+
+                let result = self.eval_loop(end, step.as_deref(), body, variables);
+
+                variables.pop();
+                result.map(|_| 0.)
+            },
+
+            Expr::VarIn { variables: bindings, body } => {
+                let depth = variables.len();
+                let result = self.eval_var_in(bindings, body, variables);
+
+                variables.truncate(depth);
+                result
+            }
+        }
+    }
+
+    /// Evaluates a loop whose variable is the innermost one.
+    fn eval_loop(&self, end: &Expr, step: Option<&Expr>, body: &Expr, variables: &mut Vec<(String, f64)>) -> Result<(), &'static str> {
+        // like the compiled loop, evaluate the body before checking the end condition,
+        // which is given the value of the variable before it is incremented
+        loop {
+            self.eval_expr(body, variables)?;
+
+            let step = match step {
+                Some(step) => self.eval_expr(step, variables)?,
+                None => 1.
+            };
+
+            let end = self.eval_expr(end, variables)?;
+            let var = &mut variables.last_mut().expect("Loop variable is not in scope.").1;
+
+            *var += step;
+
+            if !is_true(end) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Evaluates the body of a `var..in` expression, after binding its variables.
+    fn eval_var_in(&self, bindings: &[(String, Option<Expr>)], body: &Expr, variables: &mut Vec<(String, f64)>) -> Result<f64, &'static str> {
+        for (var_name, initializer) in bindings {
+            let value = match initializer {
+                Some(init) => self.eval_expr(init, variables)?,
+                None => 0.
+            };
+
+            variables.push((var_name.clone(), value));
+        }
+
+        self.eval_expr(body, variables)
+    }
+
+    /// Returns the innermost variable with the given name.
+    fn lookup<'v>(variables: &'v mut [(String, f64)], name: &str) -> Result<&'v mut f64, &'static str> {
+        match variables.iter_mut().rev().find(|(var_name, _)| var_name == name) {
+            Some((_, value)) => Ok(value),
+            None => Err("Could not find a matching variable.")
+        }
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
@@ -0,0 +1,651 @@
+//! Backend compiling functions with LLVM and executing them with its LLJIT.
+//!
+//! Every function is compiled to its own module, in which the other functions are declared.
+//! Functions do not call each other directly, but through a stub defined once per name, which
+//! jumps to the address stored in a cell owned by the `Jit`. This address is the one of a lazy
+//! re-export of the body of the function, which is thus only compiled to machine code when it
+//! is first called. A redefinition adds the module of the new body and updates the cell, then
+//! removes the module of the previous body (and its code) with its resource tracker, without
+//! compiling any other function again.
+//!
+//! The host functions are defined as absolute symbols in a dedicated `builtins` JITDylib,
+//! which also resolves the other symbols of the process, and are called through lazy
+//! re-exports as well. Top-level expressions are compiled when they are evaluated, and their
+//! module is removed once they have been executed.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::mem;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use inkwell::{AddressSpace, OptimizationLevel};
+use inkwell::builder::Builder;
+use inkwell::context::Context;
+use inkwell::module::Module;
+use inkwell::passes::PassManager;
+use inkwell::targets::{CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine};
+use inkwell::values::{AnyValue, BasicMetadataValueEnum, CallableValue, FunctionValue};
+use llvm_sys::core::LLVMDisposeMessage;
+use llvm_sys::error::{LLVMConsumeError, LLVMErrorRef};
+use llvm_sys::execution_engine::LLVMCreateGDBRegistrationListener;
+use llvm_sys::orc2::{
+    LLVMJITCSymbolMapPair, LLVMJITEvaluatedSymbol, LLVMJITSymbolFlags, LLVMJITSymbolGenericFlags, LLVMOrcAbsoluteSymbols,
+    LLVMOrcCSymbolAliasMapEntry, LLVMOrcCSymbolAliasMapPair, LLVMOrcCreateDynamicLibrarySearchGeneratorForProcess,
+    LLVMOrcCreateLocalIndirectStubsManager, LLVMOrcCreateLocalLazyCallThroughManager, LLVMOrcCreateNewThreadSafeContext,
+    LLVMOrcCreateNewThreadSafeModule, LLVMOrcDisposeIndirectStubsManager, LLVMOrcDisposeLazyCallThroughManager,
+    LLVMOrcDisposeMaterializationUnit, LLVMOrcDisposeThreadSafeContext, LLVMOrcExecutionSessionCreateBareJITDylib,
+    LLVMOrcExecutionSessionRef, LLVMOrcIndirectStubsManagerRef, LLVMOrcJITDylibAddGenerator, LLVMOrcJITDylibClear,
+    LLVMOrcJITDylibCreateResourceTracker, LLVMOrcJITDylibDefine, LLVMOrcJITDylibRef, LLVMOrcJITTargetMachineBuilderCreateFromTargetMachine,
+    LLVMOrcLazyCallThroughManagerRef, LLVMOrcLazyReexports, LLVMOrcMaterializationUnitRef, LLVMOrcObjectLayerRef,
+    LLVMOrcReleaseResourceTracker, LLVMOrcResourceTrackerRef, LLVMOrcResourceTrackerRemove, LLVMOrcSymbolStringPoolEntryRef,
+    LLVMOrcThreadSafeContextRef
+};
+use llvm_sys::orc2::ee::{
+    LLVMOrcCreateRTDyldObjectLinkingLayerWithSectionMemoryManager, LLVMOrcRTDyldObjectLinkingLayerRegisterJITEventListener
+};
+use llvm_sys::orc2::lljit::{
+    LLVMOrcCreateLLJIT, LLVMOrcCreateLLJITBuilder, LLVMOrcDisposeLLJIT, LLVMOrcLLJITAddLLVMIRModuleWithRT,
+    LLVMOrcLLJITBuilderSetJITTargetMachineBuilder, LLVMOrcLLJITBuilderSetObjectLinkingLayerCreator,
+    LLVMOrcLLJITGetExecutionSession, LLVMOrcLLJITGetGlobalPrefix, LLVMOrcLLJITGetMainJITDylib, LLVMOrcLLJITGetTripleString,
+    LLVMOrcLLJITLookup, LLVMOrcLLJITMangleAndIntern, LLVMOrcLLJITRef
+};
+use llvm_sys::support::{LLVMLoadLibraryPermanently, LLVMSearchForAddressOfSymbol};
+use llvm_sys::target_machine::{
+    LLVMCodeGenOptLevel, LLVMCodeModel, LLVMCreateTargetMachine, LLVMGetDefaultTargetTriple, LLVMGetHostCPUFeatures,
+    LLVMGetHostCPUName, LLVMGetTargetFromTriple, LLVMRelocMode
+};
+use crate::backend::{self, Backend, BackendError, HostFunction};
+use crate::compiler::Compiler;
+use crate::debug::DebugInfo;
+use crate::parser::Function;
+
+/// Defines a function which can be called by compiled code, through its stub.
+struct Definition {
+    arg_count: usize,
+    /// Address jumped to by the stub of the function. Boxed, since the address of the cell is
+    /// compiled into the stub.
+    address: Box<Cell<u64>>,
+    /// Tracker of the module of the stub of the function.
+    stub: LLVMOrcResourceTrackerRef,
+    /// Tracker of the module of the body of the function, unless it is external.
+    tracker: Option<LLVMOrcResourceTrackerRef>
+}
+
+/// Defines the LLVM JIT backend.
+pub struct Jit<'ctx> {
+    context: &'ctx Context,
+    builder: Builder<'ctx>,
+    fpm: PassManager<FunctionValue<'ctx>>,
+    // must be dropped after the pass manager, which was created for it
+    _module: Module<'ctx>,
+    lljit: LLVMOrcLLJITRef,
+    // JITDylib of the host functions and the symbols of the process
+    builtins: LLVMOrcJITDylibRef,
+    // only guards the modules given to the JIT, which are created in the context of the `Jit`
+    thread_safe_context: LLVMOrcThreadSafeContextRef,
+    stubs_manager: LLVMOrcIndirectStubsManagerRef,
+    call_through_manager: LLVMOrcLazyCallThroughManagerRef,
+    // optimization level of the machine code, also used when printing assembly
+    optimization: OptimizationLevel,
+    functions: Vec<Function>,
+    definitions: HashMap<String, Definition>,
+    // number of the next definition, which gives a unique name to its body
+    version: usize,
+    mappings: HashMap<String, usize>,
+    // number of parameters of the externs registered as host functions
+    arities: HashMap<String, usize>,
+    // path of the source file in the debug information, if it is emitted
+    debug_file: Option<String>,
+    ir: Option<String>
+}
+
+impl<'ctx> Jit<'ctx> {
+
+    /// Creates a new JIT in the given context. Functions are optimized by LLVM unless the
+    /// given optimization level is `None`.
+    pub fn new(context: &'ctx Context, optimization: OptimizationLevel) -> Jit<'ctx> {
+        let module = context.create_module("repl");
+        let fpm = function_passes(&module, optimization);
+        let lljit = create_jit(optimization);
+
+        unsafe {
+            let session = LLVMOrcLLJITGetExecutionSession(lljit);
+            let builtins = LLVMOrcExecutionSessionCreateBareJITDylib(session, b"builtins\0".as_ptr() as *const c_char);
+
+            // the main JITDylib also resolves the symbols of the process, since LLVM may call
+            // library functions which are not declared (such as `exp2` instead of `pow`)
+            for dylib in [ builtins, LLVMOrcLLJITGetMainJITDylib(lljit) ].iter() {
+                let mut generator = ptr::null_mut();
+
+                check(
+                    LLVMOrcCreateDynamicLibrarySearchGeneratorForProcess(&mut generator, LLVMOrcLLJITGetGlobalPrefix(lljit), None, ptr::null_mut()),
+                    "Cannot search the symbols of the process."
+                ).unwrap();
+                LLVMOrcJITDylibAddGenerator(*dylib, generator);
+            }
+
+            let triple = LLVMOrcLLJITGetTripleString(lljit);
+            let mut call_through_manager = ptr::null_mut();
+
+            check(
+                LLVMOrcCreateLocalLazyCallThroughManager(triple, session, lazy_compilation_failed as extern "C" fn() as usize as u64, &mut call_through_manager),
+                "Cannot create the lazy call-through manager."
+            ).unwrap();
+
+            Jit {
+                context,
+                builder: context.create_builder(),
+                fpm,
+                _module: module,
+                lljit,
+                builtins,
+                thread_safe_context: LLVMOrcCreateNewThreadSafeContext(),
+                stubs_manager: LLVMOrcCreateLocalIndirectStubsManager(triple),
+                call_through_manager,
+                optimization,
+                functions: Vec::new(),
+                definitions: HashMap::new(),
+                version: 0,
+                mappings: HashMap::new(),
+                arities: HashMap::new(),
+                debug_file: None,
+                ir: None
+            }
+        }
+    }
+
+    /// Maps the function declared with `extern` and the given name to the host function at
+    /// the given address, instead of resolving it in the symbols of the process. Declarations
+    /// evaluated before the mapping keep calling their previous host function.
+    ///
+    /// Unlike `register_extern`, the number of parameters of the declaration is not checked.
+    pub fn add_global_mapping(&mut self, name: &str, address: usize) {
+        self.mappings.insert(name.to_string(), address);
+        self.arities.remove(name);
+        self.define_builtins().expect("Cannot define host function.");
+    }
+
+    /// Emits DWARF debug information for the functions whose locations are known, as defined
+    /// in the source file at the given path, so that they can be debugged at the level of
+    /// the source code. Locations are only accurate if functions are not optimized.
+    pub fn enable_debug_info(&mut self, path: &str) {
+        self.debug_file = Some(path.to_string());
+    }
+
+    /// Compiles the given function into a new module, in which the other defined functions
+    /// are declared.
+    fn compile(&self, function: &Function) -> Result<(Module<'ctx>, FunctionValue<'ctx>), &'static str> {
+        let module = self.context.create_module(&function.prototype.name);
+        let debug_info = self.debug_file.as_ref().map(|path| DebugInfo::new(self.context, &module, path));
+
+        for other in self.functions.iter().filter(|other| other.prototype.name != function.prototype.name) {
+            let declaration = Function {
+                prototype: other.prototype.clone(),
+                body: None,
+                is_anon: false,
+                doc: None,
+                locations: None
+            };
+
+            Compiler::compile(self.context, &self.builder, &self.fpm, &module, &declaration, None)
+                .expect("Cannot declare previously compiled function.");
+        }
+
+        let compiled = Compiler::compile(self.context, &self.builder, &self.fpm, &module, function, debug_info.as_ref());
+
+        if let Some(debug_info) = &debug_info {
+            debug_info.finalize();
+        }
+
+        compiled.map(|compiled| (module, compiled))
+    }
+
+    /// Compiles the stub of the function with the given name and number of parameters, which
+    /// calls the function at the address stored in the given cell.
+    fn compile_stub(&self, name: &str, arg_count: usize, address: &Cell<u64>) -> Module<'ctx> {
+        let module = self.context.create_module(name);
+        let f64_type = self.context.f64_type();
+        let fn_type = f64_type.fn_type(&vec![ f64_type.into(); arg_count ], false);
+        let function = module.add_function(name, fn_type, None);
+
+        self.builder.position_at_end(self.context.append_basic_block(function, "entry"));
+
+        let cell = self.context.i64_type().const_int(address.as_ptr() as u64, false)
+            .const_to_pointer(fn_type.ptr_type(AddressSpace::default()).ptr_type(AddressSpace::default()));
+        let callee = self.builder.build_load(cell, "callee").into_pointer_value();
+        let args = function.get_param_iter().map(|arg| arg.into()).collect::<Vec<BasicMetadataValueEnum>>();
+        let call = self.builder.build_call(CallableValue::try_from(callee).unwrap(), args.as_slice(), "call");
+
+        call.set_tail_call(true);
+        self.builder.build_return(Some(&call.try_as_basic_value().left().unwrap()));
+
+        module
+    }
+
+    /// Gives the given module to the JIT, which compiles its functions once they are looked
+    /// up. Its code is owned by the given resource tracker.
+    fn add_module(&self, module: Module<'ctx>, tracker: LLVMOrcResourceTrackerRef) -> Result<(), &'static str> {
+        unsafe {
+            let thread_safe_module = LLVMOrcCreateNewThreadSafeModule(module.as_mut_ptr(), self.thread_safe_context);
+
+            // the module is owned by the thread safe module from now on, which is itself owned
+            // by the JIT, even if it cannot be added (in which case the JIT disposes of it)
+            mem::forget(module);
+
+            check(LLVMOrcLLJITAddLLVMIRModuleWithRT(self.lljit, tracker, thread_safe_module), "Could not add module to the JIT.")
+        }
+    }
+
+    /// Returns a new resource tracker of the main JITDylib.
+    fn create_tracker(&self) -> LLVMOrcResourceTrackerRef {
+        unsafe { LLVMOrcJITDylibCreateResourceTracker(LLVMOrcLLJITGetMainJITDylib(self.lljit)) }
+    }
+
+    /// Returns the address of the symbol with the given name in the main JITDylib, compiling
+    /// it if needed.
+    fn lookup(&self, name: &str) -> Result<u64, &'static str> {
+        let name = CString::new(name).map_err(|_| "Could not find compiled function.")?;
+        let mut address = 0;
+
+        unsafe {
+            check(LLVMOrcLLJITLookup(self.lljit, &mut address, name.as_ptr()), "Could not find compiled function.")?;
+        }
+
+        Ok(address)
+    }
+
+    /// Returns the mangled name of the given symbol, retained for the caller.
+    fn intern(&self, name: &str) -> LLVMOrcSymbolStringPoolEntryRef {
+        let name = CString::new(name).expect("Invalid symbol name.");
+
+        unsafe { LLVMOrcLLJITMangleAndIntern(self.lljit, name.as_ptr()) }
+    }
+
+    /// Defines the host functions given to `add_global_mapping` as absolute symbols of the
+    /// JITDylib of the builtins, replacing its previous symbols.
+    fn define_builtins(&self) -> Result<(), &'static str> {
+        let mut symbols = self.mappings.iter()
+            .map(|(name, address)| LLVMJITCSymbolMapPair {
+                Name: self.intern(name),
+                Sym: LLVMJITEvaluatedSymbol { Address: *address as u64, Flags: function_flags() }
+            })
+            .collect::<Vec<LLVMJITCSymbolMapPair>>();
+
+        unsafe {
+            check(LLVMOrcJITDylibClear(self.builtins), "Could not remove host functions.")?;
+            define_symbols(self.builtins, LLVMOrcAbsoluteSymbols(symbols.as_mut_ptr(), symbols.len()))
+        }
+    }
+
+    /// Defines a lazy re-export with the given name in the main JITDylib, which calls the
+    /// function with the given name in the given JITDylib (and thus compiles it when it is
+    /// first called), returning its address.
+    fn reexport(&self, name: &str, source: LLVMOrcJITDylibRef, target: &str) -> Result<u64, &'static str> {
+        let mut aliases = [
+            LLVMOrcCSymbolAliasMapPair {
+                Name: self.intern(name),
+                Entry: LLVMOrcCSymbolAliasMapEntry { Name: self.intern(target), Flags: function_flags() }
+            }
+        ];
+
+        unsafe {
+            let unit = LLVMOrcLazyReexports(self.call_through_manager, self.stubs_manager, source, aliases.as_mut_ptr(), aliases.len());
+
+            define_symbols(LLVMOrcLLJITGetMainJITDylib(self.lljit), unit)?;
+        }
+
+        self.lookup(name)
+    }
+
+    /// Defines the given function, which calls the code at the given address, defined by the
+    /// module of the given tracker (unless it is external). The module of the previous body
+    /// of the function, if any, is removed.
+    fn define(&mut self, function: &Function, address: u64, tracker: Option<LLVMOrcResourceTrackerRef>) -> Result<(), &'static str> {
+        let name = &function.prototype.name;
+
+        match self.definitions.get_mut(name) {
+            Some(definition) => {
+                definition.address.set(address);
+
+                if let Some(previous) = mem::replace(&mut definition.tracker, tracker) {
+                    unsafe { remove(previous)? };
+                }
+            },
+            None => {
+                let arg_count = function.prototype.args.len();
+                let address = Box::new(Cell::new(address));
+                let stub = self.create_tracker();
+
+                // the stub is only removed with the JIT, since other functions may be linked to it
+                if let Err(err) = self.add_module(self.compile_stub(name, arg_count, &address), stub) {
+                    unsafe { remove(stub)? };
+
+                    return Err(err);
+                }
+
+                self.definitions.insert(name.clone(), Definition { arg_count, address, stub, tracker });
+            }
+        }
+
+        match self.functions.iter().position(|fun| fun.prototype.name == *name) {
+            Some(index) => self.functions[index] = function.clone(),
+            None => self.functions.push(function.clone())
+        }
+
+        Ok(())
+    }
+
+    /// Adds the module of the body of the given function to the JIT, with a unique name, and
+    /// defines the function.
+    fn define_body(&mut self, function: &Function, module: Module<'ctx>, compiled: FunctionValue<'ctx>) -> Result<(), &'static str> {
+        let body = format!("{}.{}", function.prototype.name, self.version);
+        let tracker = self.create_tracker();
+
+        self.version += 1;
+        compiled.as_global_value().set_name(&body);
+
+        let defined = self.add_module(module, tracker)
+            .and_then(|_| self.reexport(&format!("{}.lazy", body), unsafe { LLVMOrcLLJITGetMainJITDylib(self.lljit) }, &body))
+            .and_then(|address| self.define(function, address, Some(tracker)));
+
+        if defined.is_err() {
+            let owned = self.definitions.get(&function.prototype.name).is_some_and(|definition| definition.tracker == Some(tracker));
+
+            if !owned {
+                unsafe { remove(tracker)? };
+            }
+        }
+
+        defined
+    }
+
+    /// Defines the given external function, which calls the host function with the same name.
+    fn define_extern(&mut self, function: &Function) -> Result<(), &'static str> {
+        let name = &function.prototype.name;
+        let address = self.reexport(&format!("{}.{}.lazy", name, self.version), self.builtins, name)?;
+
+        self.version += 1;
+        self.define(function, address, None)
+    }
+
+    /// Returns a value indicating whether the host function declared with `extern` and the
+    /// given name can be called.
+    fn resolves(&self, name: &str) -> bool {
+        if self.mappings.contains_key(name) {
+            return true;
+        }
+
+        match CString::new(name) {
+            Ok(name) => !unsafe { LLVMSearchForAddressOfSymbol(name.as_ptr()) }.is_null(),
+            Err(_) => false
+        }
+    }
+
+    /// Runs the anonymous function with the given name, compiled to the given module, then
+    /// removes the module.
+    fn run(&self, module: Module<'ctx>, name: &str) -> Result<f64, &'static str> {
+        let tracker = self.create_tracker();
+        let result = self.add_module(module, tracker).and_then(|_| self.lookup(name)).map(|address| {
+            // the function was compiled with the signature `fn() -> f64`
+            let function: extern "C" fn() -> f64 = unsafe { mem::transmute(address as usize) };
+
+            function()
+        });
+
+        unsafe { remove(tracker)? };
+
+        result
+    }
+}
+
+impl<'ctx> Drop for Jit<'ctx> {
+    fn drop(&mut self) {
+        unsafe {
+            // trackers are removed rather than only released, which would transfer their code
+            // to the default tracker of the JITDylib
+            for definition in self.definitions.values() {
+                for tracker in definition.tracker.iter().chain(std::iter::once(&definition.stub)) {
+                    let _ = remove(*tracker);
+                }
+            }
+
+            // the managers of the lazy re-exports are used by the execution session of the JIT
+            // until they are disposed of, and must thus be disposed of first
+            LLVMOrcDisposeIndirectStubsManager(self.stubs_manager);
+            LLVMOrcDisposeLazyCallThroughManager(self.call_through_manager);
+
+            let _ = check(LLVMOrcDisposeLLJIT(self.lljit), "Could not dispose of the JIT.");
+
+            LLVMOrcDisposeThreadSafeContext(self.thread_safe_context);
+        }
+    }
+}
+
+impl<'ctx> Backend for Jit<'ctx> {
+    fn register_extern(&mut self, name: &str, function: HostFunction) -> Result<(), &'static str> {
+        let declared = self.functions.iter()
+            .find(|fun| fun.prototype.name == name && fun.body.is_none())
+            .map(|fun| fun.prototype.args.len());
+
+        backend::check_registration(declared, function.arity())?;
+
+        self.add_global_mapping(name, function.address());
+        self.arities.insert(name.to_string(), function.arity());
+
+        Ok(())
+    }
+
+    fn eval(&mut self, function: &Function) -> Result<Option<f64>, BackendError> {
+        backend::check_extern(function, self.arities.get(&function.prototype.name).copied()).map_err(BackendError::Compilation)?;
+
+        // the functions calling the previous definition were compiled with its signature
+        if let Some(definition) = self.definitions.get(&function.prototype.name) {
+            if !function.is_anon && definition.arg_count != function.prototype.args.len() {
+                return Err(BackendError::Compilation("Cannot redefine a function with a different number of parameters."));
+            }
+        }
+
+        if function.body.is_none() && !self.resolves(&function.prototype.name) {
+            return Err(BackendError::Compilation("Unknown external function."));
+        }
+
+        let (module, compiled) = self.compile(function).map_err(BackendError::Compilation)?;
+
+        self.ir = Some(compiled.print_to_string().to_string());
+
+        if function.is_anon {
+            let name = compiled.get_name().to_str().unwrap().to_string();
+
+            return self.run(module, &name).map(Some).map_err(BackendError::Execution);
+        }
+
+        // only define it now to ensure it is correct
+        match function.body {
+            Some(_) => self.define_body(function, module, compiled),
+            None => self.define_extern(function)
+        }.map_err(BackendError::Execution)?;
+
+        Ok(None)
+    }
+
+    fn ir(&self) -> Option<&str> {
+        self.ir.as_deref()
+    }
+
+    fn module_ir(&self) -> Option<String> {
+        let ir = self.functions.iter()
+            .map(|function| {
+                let (_module, compiled) = self.compile(function).expect("Cannot compile previously compiled function.");
+
+                compiled.print_to_string().to_string()
+            })
+            .collect::<Vec<String>>();
+
+        Some(ir.join("\n"))
+    }
+
+    fn asm(&self, name: &str) -> Result<String, &'static str> {
+        let function = match self.functions.iter().find(|function| function.prototype.name == name) {
+            Some(function) if function.body.is_some() => function,
+            Some(_) => return Err("External functions have no assembly."),
+            None => return Err("Unknown function.")
+        };
+
+        let (module, _) = self.compile(function)?;
+        let machine = host_target_machine(self.optimization)?;
+        let buffer = machine.write_to_memory_buffer(&module, FileType::Assembly).map_err(|_| "Could not emit assembly.")?;
+
+        Ok(String::from_utf8_lossy(buffer.as_slice()).into_owned())
+    }
+}
+
+/// Creates the pass manager which runs the function passes of the JIT at the given optimization
+/// level (none at `OptimizationLevel::None`) on the functions of the given module.
+pub fn function_passes<'ctx>(module: &Module<'ctx>, optimization: OptimizationLevel) -> PassManager<FunctionValue<'ctx>> {
+    let fpm = PassManager::create(module);
+
+    if optimization != OptimizationLevel::None {
+        fpm.add_instruction_combining_pass();
+        fpm.add_reassociate_pass();
+        fpm.add_gvn_pass();
+        fpm.add_cfg_simplification_pass();
+        fpm.add_basic_alias_analysis_pass();
+        fpm.add_promote_memory_to_register_pass();
+        fpm.add_instruction_combining_pass();
+        fpm.add_reassociate_pass();
+    }
+
+    fpm.initialize();
+    fpm
+}
+
+/// Creates a target machine for the host, which generates the same machine code as the JIT
+/// at the given optimization level.
+pub fn host_target_machine(optimization: OptimizationLevel) -> Result<TargetMachine, &'static str> {
+    Target::initialize_native(&InitializationConfig::default()).map_err(|_| "Could not initialize the native target.")?;
+
+    let triple = TargetMachine::get_default_triple();
+
+    Target::from_triple(&triple).ok()
+        .and_then(|target| target.create_target_machine(
+            &triple,
+            TargetMachine::get_host_cpu_name().to_str().unwrap_or(""),
+            TargetMachine::get_host_cpu_features().to_str().unwrap_or(""),
+            optimization,
+            RelocMode::Default,
+            CodeModel::JITDefault
+        ))
+        .ok_or("Could not create the native target machine.")
+}
+
+/// Creates the LLJIT of the host machine, which registers the compiled code with debuggers.
+fn create_jit(optimization: OptimizationLevel) -> LLVMOrcLLJITRef {
+    Target::initialize_native(&InitializationConfig::default()).expect("Cannot initialize the native target.");
+
+    let level = match optimization {
+        OptimizationLevel::None => LLVMCodeGenOptLevel::LLVMCodeGenLevelNone,
+        OptimizationLevel::Less => LLVMCodeGenOptLevel::LLVMCodeGenLevelLess,
+        OptimizationLevel::Default => LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault,
+        OptimizationLevel::Aggressive => LLVMCodeGenOptLevel::LLVMCodeGenLevelAggressive
+    };
+
+    unsafe {
+        let triple = LLVMGetDefaultTargetTriple();
+        let mut target = ptr::null_mut();
+        let mut error = ptr::null_mut();
+
+        if LLVMGetTargetFromTriple(triple, &mut target, &mut error) != 0 {
+            LLVMDisposeMessage(error);
+            LLVMDisposeMessage(triple);
+            panic!("Cannot find the native target.");
+        }
+
+        let cpu = LLVMGetHostCPUName();
+        let features = LLVMGetHostCPUFeatures();
+        let machine = LLVMCreateTargetMachine(
+            target,
+            triple,
+            cpu,
+            features,
+            level,
+            LLVMRelocMode::LLVMRelocDefault,
+            LLVMCodeModel::LLVMCodeModelJITDefault
+        );
+
+        LLVMDisposeMessage(features);
+        LLVMDisposeMessage(cpu);
+        LLVMDisposeMessage(triple);
+
+        // the target machine and the builder are now owned by the JIT
+        let builder = LLVMOrcCreateLLJITBuilder();
+
+        LLVMOrcLLJITBuilderSetJITTargetMachineBuilder(builder, LLVMOrcJITTargetMachineBuilderCreateFromTargetMachine(machine));
+        LLVMOrcLLJITBuilderSetObjectLinkingLayerCreator(builder, create_object_layer, ptr::null_mut());
+
+        let mut lljit = ptr::null_mut();
+
+        check(LLVMOrcCreateLLJIT(&mut lljit, builder), "Cannot create the JIT.").unwrap();
+
+        // makes the symbols of the process available to the JITDylib of the builtins
+        LLVMLoadLibraryPermanently(ptr::null());
+
+        lljit
+    }
+}
+
+/// Creates the object linking layer of the JIT, which registers every object it loads with
+/// debuggers, until it is removed.
+extern "C" fn create_object_layer(_: *mut c_void, session: LLVMOrcExecutionSessionRef, _: *const c_char) -> LLVMOrcObjectLayerRef {
+    unsafe {
+        let layer = LLVMOrcCreateRTDyldObjectLinkingLayerWithSectionMemoryManager(session);
+
+        LLVMOrcRTDyldObjectLinkingLayerRegisterJITEventListener(layer, LLVMCreateGDBRegistrationListener());
+
+        layer
+    }
+}
+
+/// Called instead of a function whose lazy compilation failed, which cannot be recovered from.
+extern "C" fn lazy_compilation_failed() {
+    eprintln!("Could not compile function.");
+    std::process::abort();
+}
+
+/// Returns the flags of the symbol of a function.
+fn function_flags() -> LLVMJITSymbolFlags {
+    LLVMJITSymbolFlags {
+        GenericFlags: LLVMJITSymbolGenericFlags::LLVMJITSymbolGenericFlagsExported as u8 | LLVMJITSymbolGenericFlags::LLVMJITSymbolGenericFlagsCallable as u8,
+        TargetFlags: 0
+    }
+}
+
+/// Adds the given materialization unit to the given JITDylib, which then owns it.
+unsafe fn define_symbols(dylib: LLVMOrcJITDylibRef, unit: LLVMOrcMaterializationUnitRef) -> Result<(), &'static str> {
+    let error = LLVMOrcJITDylibDefine(dylib, unit);
+
+    if !error.is_null() {
+        LLVMOrcDisposeMaterializationUnit(unit);
+    }
+
+    check(error, "Could not define symbol.")
+}
+
+/// Removes the code owned by the given resource tracker from the JIT, and releases it.
+unsafe fn remove(tracker: LLVMOrcResourceTrackerRef) -> Result<(), &'static str> {
+    let error = LLVMOrcResourceTrackerRemove(tracker);
+
+    LLVMOrcReleaseResourceTracker(tracker);
+    check(error, "Could not remove module from the JIT.")
+}
+
+/// Consumes the given error, returning the given message if it is an error.
+unsafe fn check(error: LLVMErrorRef, message: &'static str) -> Result<(), &'static str> {
+    if error.is_null() {
+        Ok(())
+    } else {
+        LLVMConsumeError(error);
+        Err(message)
+    }
+}
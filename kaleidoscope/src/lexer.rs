@@ -3,26 +3,69 @@ use std::str::Chars;
 use std::ops::DerefMut;
 
 /// Represents a primitive syntax token.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Binary,
     Comma,
-    Comment,
+    Comment(String),
     Def,
+    DocComment(String),
     Else,
     EOF,
+    Export,
     Extern,
     For,
     Ident(String),
     If,
+    Import,
     In,
     LParen,
     Number(f64),
     Op(char),
     RParen,
+    Str(String),
     Then,
     Unary,
-    Var
+    Var,
+    Whitespace(String)
+}
+
+/// Keywords of the language, along with their `Token`.
+pub const KEYWORDS: &[(&str, Token)] = &[
+    ("binary", Token::Binary),
+    ("def", Token::Def),
+    ("else", Token::Else),
+    ("export", Token::Export),
+    ("extern", Token::Extern),
+    ("for", Token::For),
+    ("if", Token::If),
+    ("import", Token::Import),
+    ("in", Token::In),
+    ("then", Token::Then),
+    ("unary", Token::Unary),
+    ("var", Token::Var)
+];
+
+/// Defines the location of a `Token` in the source code, as a range of byte offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// Returns the (1-based) line and column at which the span starts in the given source code.
+    pub fn location(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.start.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|&ch| ch != '\n').count() + 1;
+
+        (line, column)
+    }
 }
 
 /// Defines an error encountered by the `Lexer`.
@@ -57,7 +100,10 @@ pub type LexerResult = Result<Token, LexerError>;
 pub struct Lexer<'a> {
     input: &'a str,
     chars: Box<Peekable<Chars<'a>>>,
-    pos: usize
+    pos: usize,
+    start: usize,
+    preserve_comments: bool,
+    preserve_whitespace: bool
 }
 
 impl<'a> Lexer<'a> {
@@ -66,104 +112,228 @@ impl<'a> Lexer<'a> {
         Lexer {
             input,
             chars: Box::new(input.chars().peekable()),
-            pos: 0
+            pos: 0,
+            start: 0,
+            preserve_comments: false,
+            preserve_whitespace: false
         }
     }
 
+    /// Creates a `Lexer` which emits `Token::Comment` tokens instead of skipping comments,
+    /// for tools which need to preserve them.
+    pub fn with_comments(input: &'a str) -> Lexer<'a> {
+        Lexer {
+            preserve_comments: true,
+            ..Lexer::new(input)
+        }
+    }
+
+    /// Creates a lossless `Lexer`, which also emits `Token::Whitespace` and `Token::Comment`
+    /// tokens, so that the source code can be rebuilt from the resulting tokens.
+    pub fn lossless(input: &'a str) -> Lexer<'a> {
+        Lexer {
+            preserve_comments: true,
+            preserve_whitespace: true,
+            ..Lexer::new(input)
+        }
+    }
+
+    /// Returns the location of the last lexed `Token`.
+    pub fn span(&self) -> Span {
+        Span::new(self.start, self.pos)
+    }
+
+    /// Lexes and returns the next `Token` from the source code, along with its location.
+    pub fn lex_spanned(&mut self) -> Result<(Token, Span), LexerError> {
+        let token = self.lexer()?;
+
+        Ok((token, self.span()))
+    }
+
     /// Lexes and returns the next `Token` from the source code.
+    ///
+    /// Comments are skipped unless the `Lexer` was created with `Lexer::with_comments`,
+    /// but doc comments (`## ...`) are always returned, since they are attached to
+    /// the following function by the `Parser`.
     pub fn lexer(&mut self) -> LexerResult {
+        loop {
+            match self.lex_token()? {
+                Token::Comment(_) if !self.preserve_comments => continue,
+                token => return Ok(token)
+            }
+        }
+    }
+
+    /// Lexes and returns the next `Token` (including comments) from the source code.
+    fn lex_token(&mut self) -> LexerResult {
         let chars = self.chars.deref_mut();
         let src = self.input;
         let mut pos = self.pos;
 
         // Skip whitespace
-        loop {
-            // Note: the following lines are in their own scope to
-            // limit how long 'chars' is borrowed, and in order to allow
-            // it to be borrowed again in the loop by 'chars.next()'.
-            {
-                let ch = chars.peek();
-                if ch.is_none() {
-                    self.pos = pos;
-                    return Ok(Token::EOF);
-                }
-                if !ch.unwrap().is_whitespace() {
-                    break;
-                }
+        let whitespace_start = pos;
+
+        while let Some(&ch) = chars.peek() {
+            if !ch.is_whitespace() {
+                break;
             }
 
             chars.next();
-            pos += 1;
+            pos += ch.len_utf8();
         }
 
-        let start = pos;
-        let next = chars.next();
+        self.start = whitespace_start;
+        self.pos = pos;
 
-        if next.is_none() {
-            return Ok(Token::EOF);
+        if self.preserve_whitespace && pos > whitespace_start {
+            return Ok(Token::Whitespace(src[whitespace_start..pos].to_string()));
         }
 
-        pos += 1;
+        let start = pos;
+        let next = match chars.next() {
+            Some(ch) => ch,
+            None => return Ok(Token::EOF)
+        };
+
+        self.start = start;
+        pos += next.len_utf8();
+
         // Actually get the next token
-        let result = match next.unwrap() {
+        let result = match next {
             '(' => Ok(Token::LParen),
             ')' => Ok(Token::RParen),
             ',' => Ok(Token::Comma),
-            '#' => {
-                // Comment
+            '"' => {
+                // String literal, used to import modules by path
+                let mut text = String::new();
+
                 loop {
-                    let ch = chars.next();
-                    pos += 1;
-                    if ch == Some('\n') {
-                        break;
+                    match chars.next() {
+                        Some('"') => {
+                            pos += 1;
+                            break;
+                        },
+                        Some(ch) if ch != '\n' => {
+                            pos += ch.len_utf8();
+                            text.push(ch);
+                        },
+                        _ => {
+                            self.pos = pos;
+                            return Err(LexerError::with_index("Unterminated string literal.", start));
+                        }
+                    }
+                }
+
+                Ok(Token::Str(text))
+            },
+            '#' => {
+                let mut text = String::from("#");
+
+                match chars.peek() {
+                    Some('[') => {
+                        // Block comment, which may contain nested block comments
+                        let mut depth = 0;
+
+                        loop {
+                            let ch = match chars.next() {
+                                Some(ch) => ch,
+                                None => {
+                                    self.pos = pos;
+                                    return Err(LexerError::with_index("Unterminated block comment.", start));
+                                }
+                            };
+
+                            pos += ch.len_utf8();
+                            text.push(ch);
+
+                            if ch == '[' && text.ends_with("#[") {
+                                depth += 1;
+                            } else if ch == '#' && text.ends_with("]#") {
+                                depth -= 1;
+
+                                if depth == 0 {
+                                    break;
+                                }
+                            }
+                        }
+
+                        Ok(Token::Comment(text))
+                    },
+
+                    Some('#') => {
+                        // Doc comment
+                        chars.next();
+                        pos += 1;
+
+                        let mut doc = String::new();
+
+                        while let Some(&ch) = chars.peek() {
+                            if ch == '\n' {
+                                break;
+                            }
+
+                            chars.next();
+                            pos += ch.len_utf8();
+                            doc.push(ch);
+                        }
+
+                        let doc = doc.strip_prefix(' ').unwrap_or(doc.as_str());
+
+                        Ok(Token::DocComment(doc.trim_end().to_string()))
+                    },
+
+                    _ => {
+                        // Line comment
+                        while let Some(&ch) = chars.peek() {
+                            if ch == '\n' {
+                                break;
+                            }
+
+                            chars.next();
+                            pos += ch.len_utf8();
+                            text.push(ch);
+                        }
+
+                        Ok(Token::Comment(text))
                     }
                 }
-                Ok(Token::Comment)
             },
             '.' | '0' ..= '9' => {
                 // Parse number literal
-                loop {
-                    let ch = match chars.peek() {
-                        Some(ch) => *ch,
-                        None => return Ok(Token::EOF)
-                    };
-
+                while let Some(&ch) = chars.peek() {
                     // Parse float
-                    if ch != '.' && !ch.is_digit(16) {
+                    if ch != '.' && !ch.is_ascii_digit() {
                         break;
                     }
 
                     chars.next();
                     pos += 1;
                 }
-                Ok(Token::Number(src[start..pos].parse().unwrap()))
+
+                match src[start..pos].parse() {
+                    Ok(nb) => Ok(Token::Number(nb)),
+                    Err(_) => {
+                        self.pos = pos;
+                        return Err(LexerError::with_index("Invalid number literal.", start));
+                    }
+                }
             },
             'a'..='z' | 'A'..='Z' | '_' => {
-                loop {
-                    let ch = match chars.peek() {
-                        Some(ch) => *ch,
-                        None => return Ok(Token::EOF)
-                    };
+                while let Some(&ch) = chars.peek() {
                     // A word-like identifier only contains underscores and alphanumeric characters.
                     if ch != '_' && !ch.is_alphanumeric() {
                         break;
                     }
 
                     chars.next();
-                    pos += 1;
+                    pos += ch.len_utf8();
                 }
 
-                match &src[start..pos] {
-                    "def" => Ok(Token::Def),
-                    "extern" => Ok(Token::Extern),
-                    "if" => Ok(Token::If),
-                    "else" => Ok(Token::Else),
-                    "for" => Ok(Token::For),
-                    "in" => Ok(Token::In),
-                    "unary" => Ok(Token::Unary),
-                    "binary" => Ok(Token::Binary),
-                    "var" => Ok(Token::Var),
-                    ident=> Ok(Token::Ident(ident.to_string()))
+                let ident = &src[start..pos];
+
+                match KEYWORDS.iter().find(|(keyword, _)| *keyword == ident) {
+                    Some((_, token)) => Ok(token.clone()),
+                    None => Ok(Token::Ident(ident.to_string()))
                 }
             },
             op => Ok(Token::Op(op))
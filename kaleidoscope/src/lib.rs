@@ -1,5 +1,25 @@
 pub mod lexer;
 pub mod parser;
+#[cfg(feature = "llvm")]
 pub mod compiler;
+#[cfg(feature = "llvm")]
+pub mod debug;
+#[cfg(feature = "llvm")]
+pub mod gdb;
+pub mod operator;
+pub mod session;
+pub mod completion;
+pub mod module;
+pub mod syntax;
+pub mod format;
+pub mod printer;
+pub mod visit;
+pub mod optimizer;
+pub mod interp;
+pub mod backend;
+pub mod runtime;
+#[cfg(feature = "llvm")]
+pub mod jit;
 
-const ANONYMOUS_FUNCTION_NAME: &str = "anonymous";
\ No newline at end of file
+/// Name of the function wrapping a top-level expression.
+pub const ANONYMOUS_FUNCTION_NAME: &str = "anonymous";
\ No newline at end of file
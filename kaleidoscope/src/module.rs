@@ -0,0 +1,285 @@
+//! Loading of source files as modules, which may import other modules.
+//!
+//! A module imports another one with `import "path/to/file.ks"`, or `import name` for the file
+//! `name.ks`, which is searched in the directory of the importing module, then in each directory
+//! of the search path. Imports are hoisted: imported modules are loaded before the rest of the
+//! module is parsed, so that the operators they define can be used anywhere in it. Each module
+//! is only loaded once by a `Loader`, and cyclic imports are errors.
+//!
+//! The definitions of an imported module are private unless declared with `export def`: they
+//! are renamed to `<module>.<name>`, which cannot be written in source code, so that they can
+//! neither collide with nor be called by other modules. Extern declarations and operators are
+//! always visible, since they refer to host functions and to the operator table of the session.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::backend::{Backend, BackendError};
+use crate::parser::{Expr, Function, Import, Item};
+use crate::session::Session;
+use crate::syntax::{self, SyntaxTree};
+use crate::visit::{self, VisitorMut};
+
+/// Defines an error which occurred while loading a module.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModuleError {
+    /// The imported file was found neither next to the importing module nor in the search path.
+    NotFound(String),
+    /// A module imports itself, through the given chain of modules.
+    Cycle(Vec<PathBuf>),
+    /// The module could not be read.
+    Read(PathBuf, String),
+    /// The module contains a syntax error, at the given (1-based) line and column.
+    Syntax {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        error: &'static str
+    },
+    /// A function of the module could not be compiled or executed.
+    Backend(PathBuf, BackendError)
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleError::NotFound(path) => write!(f, "Could not find module '{}'.", path),
+            ModuleError::Cycle(modules) => {
+                let modules = modules.iter().map(|path| path.display().to_string()).collect::<Vec<String>>();
+
+                write!(f, "Cyclic import: {}.", modules.join(" -> "))
+            },
+            ModuleError::Read(path, err) => write!(f, "Could not read {}: {}", path.display(), err),
+            ModuleError::Syntax { path, line, column, error } => write!(f, "Error parsing {}:{}:{}: {}", path.display(), line, column, error),
+            ModuleError::Backend(path, err) => write!(f, "{} (in {})", err, path.display())
+        }
+    }
+}
+
+/// Defines a module imported by a `Loader`, along with the functions it defined.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedModule {
+    /// Canonical path of the module.
+    pub path: PathBuf,
+    /// Functions defined by the module, once renamed.
+    pub functions: Vec<Function>
+}
+
+/// Defines a loader of modules, which remembers the modules it loaded in a session.
+pub struct Loader {
+    search_path: Vec<PathBuf>,
+    /// Canonical paths of the loaded modules.
+    loaded: HashSet<PathBuf>,
+    /// Canonical paths of the modules being loaded, from the outermost to the innermost one.
+    loading: Vec<PathBuf>,
+    /// Module of each prefix given to private functions.
+    prefixes: HashMap<String, PathBuf>,
+    /// Imported modules, in the order they were loaded.
+    imported: Vec<ImportedModule>
+}
+
+impl Loader {
+
+    /// Creates a new loader, which searches imported modules in the given directories after the
+    /// directory of the importing module.
+    pub fn new(search_path: Vec<PathBuf>) -> Loader {
+        Loader {
+            search_path,
+            loaded: HashSet::new(),
+            loading: Vec::new(),
+            prefixes: HashMap::new(),
+            imported: Vec::new()
+        }
+    }
+
+    /// Returns the directories in which imported modules are searched.
+    pub fn search_path(&self) -> &[PathBuf] {
+        self.search_path.as_slice()
+    }
+
+    /// Returns the modules imported in the session, in the order they were loaded (and thus
+    /// after the modules they import).
+    pub fn imported(&self) -> &[ImportedModule] {
+        self.imported.as_slice()
+    }
+
+    /// Loads the file at the given path as the main module, whose functions are all visible,
+    /// and returns the results of its top-level expressions.
+    ///
+    /// Unlike imported modules, the main module is loaded again on each call.
+    pub fn load(&mut self, path: &Path, session: &mut Session, backend: &mut dyn Backend) -> Result<Vec<f64>, ModuleError> {
+        let path = canonicalize(path)?;
+
+        self.load_module(path, true, session, backend)
+    }
+
+    /// Imports the given module, unless it has already been loaded; it is searched relative to
+    /// the given directory (the current directory if `None`), then in the search path.
+    pub fn import(&mut self, import: &Import, dir: Option<&Path>, session: &mut Session, backend: &mut dyn Backend) -> Result<(), ModuleError> {
+        let path = self.resolve(import, dir)?;
+
+        if self.loaded.contains(&path) {
+            return Ok(());
+        }
+
+        self.load_module(path, false, session, backend).map(|_| ())
+    }
+
+    /// Returns the canonical path of the given imported module.
+    fn resolve(&self, import: &Import, dir: Option<&Path>) -> Result<PathBuf, ModuleError> {
+        let file = import.file_path();
+        let dir = dir.unwrap_or_else(|| Path::new("."));
+
+        std::iter::once(dir)
+            .chain(self.search_path.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(file.as_str()))
+            .find(|path| path.is_file())
+            .ok_or(ModuleError::NotFound(file.clone()))
+            .and_then(|path| canonicalize(&path))
+    }
+
+    fn load_module(&mut self, path: PathBuf, main: bool, session: &mut Session, backend: &mut dyn Backend) -> Result<Vec<f64>, ModuleError> {
+        if let Some(index) = self.loading.iter().position(|module| *module == path) {
+            let mut cycle = self.loading[index..].to_vec();
+
+            cycle.push(path);
+
+            return Err(ModuleError::Cycle(cycle));
+        }
+
+        let source = fs::read_to_string(&path).map_err(|err| ModuleError::Read(path.clone(), err.to_string()))?;
+
+        self.loading.push(path.clone());
+
+        let result = self.eval_module(&path, source.as_str(), main, session, backend);
+
+        self.loading.pop();
+
+        if result.is_ok() && !main {
+            self.loaded.insert(path);
+        }
+
+        result
+    }
+
+    fn eval_module(&mut self, path: &Path, source: &str, main: bool, session: &mut Session, backend: &mut dyn Backend) -> Result<Vec<f64>, ModuleError> {
+        let items = parse_items(path, source, &syntax::parse(source, session.operators()))?;
+
+        for item in &items {
+            if let Item::Import(import) = item {
+                self.import(import, path.parent(), session, backend)?;
+            }
+        }
+
+        // parse again, now that the operators of the imported modules are known
+        let items = parse_items(path, source, &syntax::parse(source, session.operators()))?;
+
+        let private = items.iter()
+            .filter_map(|item| match item {
+                Item::Function { function, exported: false } if !main && function.body.is_some() && !function.is_anon && !function.prototype.is_op => {
+                    Some(function.prototype.name.clone())
+                },
+                _ => None
+            })
+            .collect::<HashSet<String>>();
+
+        let mut renamer = Renamer {
+            prefix: if private.is_empty() { String::new() } else { self.prefix(path) },
+            private
+        };
+
+        let mut results = Vec::new();
+        let mut functions = Vec::new();
+
+        for item in items {
+            let mut function = match item {
+                Item::Function { function, .. } => function,
+                Item::Import(_) => continue
+            };
+
+            renamer.visit_function_mut(&mut function);
+
+            match backend.eval(&function) {
+                Ok(result) => {
+                    results.extend(result);
+
+                    if !main && !function.is_anon {
+                        functions.push(function.clone());
+                    }
+
+                    session.define(function).map_err(|err| ModuleError::Backend(path.to_path_buf(), BackendError::Compilation(err)))?;
+                },
+                Err(err) => return Err(ModuleError::Backend(path.to_path_buf(), err))
+            }
+        }
+
+        if !main {
+            self.imported.push(ImportedModule { path: path.to_path_buf(), functions });
+        }
+
+        Ok(results)
+    }
+
+    /// Returns the prefix of the private functions of the given module, which is its file name
+    /// unless another module with the same name has been loaded.
+    fn prefix(&mut self, path: &Path) -> String {
+        let name = path.file_stem().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let mut prefix = name.clone();
+        let mut index = 1;
+
+        while let Some(module) = self.prefixes.get(&prefix) {
+            if module == path {
+                return prefix;
+            }
+
+            index += 1;
+            prefix = format!("{}{}", name, index);
+        }
+
+        self.prefixes.insert(prefix.clone(), path.to_path_buf());
+        prefix
+    }
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf, ModuleError> {
+    path.canonicalize().map_err(|err| ModuleError::Read(path.to_path_buf(), err.to_string()))
+}
+
+/// Derives the items of the given module from its syntax tree, failing on syntax errors.
+fn parse_items(path: &Path, source: &str, tree: &SyntaxTree) -> Result<Vec<Item>, ModuleError> {
+    tree.items().map_err(|error| {
+        let (line, column) = tree.errors().first().map(|err| err.span.location(source)).unwrap_or((1, 1));
+
+        ModuleError::Syntax { path: path.to_path_buf(), line, column, error }
+    })
+}
+
+/// Renames the private functions of a module, and the calls to them.
+struct Renamer {
+    prefix: String,
+    private: HashSet<String>
+}
+
+impl Renamer {
+    fn rename(&self, name: &mut String) {
+        if self.private.contains(name) {
+            *name = format!("{}.{}", self.prefix, name);
+        }
+    }
+}
+
+impl VisitorMut for Renamer {
+    fn visit_function_mut(&mut self, fun: &mut Function) {
+        self.rename(&mut fun.prototype.name);
+        visit::walk_function_mut(self, fun)
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Expr::Call { func_name, .. } = expr {
+            self.rename(func_name);
+        }
+
+        visit::walk_expr_mut(self, expr)
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+/// Precedence given by the `Parser` to operators which are not in the table.
+pub const DEFAULT_PRECEDENCE: i32 = 100;
+
+/// Defines the associativity of a binary operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Associativity {
+    Left,
+    Right
+}
+
+/// Defines a binary operator known by the `Parser`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Operator {
+    pub op: char,
+    pub precedence: i32,
+    pub associativity: Associativity,
+    pub builtin: bool
+}
+
+/// Defines the table of binary operators (and their precedence) used by the `Parser`.
+///
+/// The table is meant to live as long as the session it belongs to, so that operators
+/// defined by the user with `def binary` remain usable by later inputs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperatorTable {
+    operators: BTreeMap<char, Operator>
+}
+
+impl OperatorTable {
+
+    /// Creates an empty table, without any builtin operator.
+    pub fn empty() -> OperatorTable {
+        OperatorTable {
+            operators: BTreeMap::new()
+        }
+    }
+
+    /// Creates a table containing the builtin operators of the language.
+    pub fn new() -> OperatorTable {
+        let mut table = OperatorTable::empty();
+
+        table.insert_builtin('=', 2, Associativity::Right);
+        table.insert_builtin('<', 10, Associativity::Left);
+        table.insert_builtin('>', 10, Associativity::Left);
+        table.insert_builtin('+', 20, Associativity::Left);
+        table.insert_builtin('-', 20, Associativity::Left);
+        table.insert_builtin('*', 40, Associativity::Left);
+        table.insert_builtin('/', 40, Associativity::Left);
+
+        table
+    }
+
+    fn insert_builtin(&mut self, op: char, precedence: i32, associativity: Associativity) {
+        self.operators.insert(op, Operator {
+            op,
+            precedence,
+            associativity,
+            builtin: true
+        });
+    }
+
+    /// Registers a user-defined operator, replacing any previous definition of it.
+    ///
+    /// Builtin operators are compiled by the backends rather than called, so the `Parser` and
+    /// the `Session` refuse to redefine them (see `is_builtin`) before registering operators.
+    pub fn insert(&mut self, op: char, precedence: i32, associativity: Associativity) {
+        self.operators.insert(op, Operator {
+            op,
+            precedence,
+            associativity,
+            builtin: false
+        });
+    }
+
+    /// Removes a user-defined operator from the table. Builtin operators cannot be removed.
+    pub fn remove(&mut self, op: char) -> Option<Operator> {
+        match self.operators.get(&op) {
+            Some(operator) if !operator.builtin => self.operators.remove(&op),
+            _ => None
+        }
+    }
+
+    /// Returns a value indicating whether the given operator is a builtin operator.
+    pub fn is_builtin(&self, op: char) -> bool {
+        self.operators.get(&op).is_some_and(|operator| operator.builtin)
+    }
+
+    /// Returns the definition of the given operator, if any.
+    pub fn get(&self, op: char) -> Option<&Operator> {
+        self.operators.get(&op)
+    }
+
+    /// Returns the precedence of the given operator, if it is defined.
+    pub fn precedence(&self, op: char) -> Option<i32> {
+        self.operators.get(&op).map(|operator| operator.precedence)
+    }
+
+    /// Returns the associativity of the given operator, defaulting to left-associativity.
+    pub fn associativity(&self, op: char) -> Associativity {
+        self.operators.get(&op).map_or(Associativity::Left, |operator| operator.associativity)
+    }
+
+    /// Returns an iterator over all the defined operators, ordered by character.
+    pub fn iter(&self) -> impl Iterator<Item = &Operator> {
+        self.operators.values()
+    }
+
+    /// Returns an iterator over the operators defined by the user.
+    pub fn user_defined(&self) -> impl Iterator<Item = &Operator> {
+        self.iter().filter(|operator| !operator.builtin)
+    }
+}
+
+impl Default for OperatorTable {
+    fn default() -> Self {
+        OperatorTable::new()
+    }
+}
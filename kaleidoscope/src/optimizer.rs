@@ -0,0 +1,257 @@
+//! AST-level optimizations, which do not depend on LLVM and can thus be used by any backend.
+//!
+//! The optimizer folds constant expressions, removes `if` expressions whose condition is
+//! constant, inlines trivial user-defined operators and (optionally) unrolls small `for`
+//! loops with constant bounds. Constants are folded with the exact semantics of the code
+//! generated by the `Compiler` (as implemented by the interpreter), including for
+//! comparisons involving NaN.
+
+use std::collections::HashMap;
+use crate::interp::{builtin_binary, is_true};
+use crate::parser::{Expr, Function};
+use crate::visit::{self, Fold, Visitor};
+
+/// Defines the options of the optimizer.
+#[derive(Debug, Clone)]
+pub struct OptimizeOptions {
+    /// Maximum number of iterations of a `for` loop with constant bounds for it to be
+    /// unrolled, or 0 to never unroll loops.
+    pub unroll_limit: usize,
+    /// Whether calls to trivial user-defined operators are replaced by their body.
+    pub inline_operators: bool
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        OptimizeOptions {
+            unroll_limit: 0,
+            inline_operators: true
+        }
+    }
+}
+
+/// Optimizes the given function, given the previously defined functions (whose operators
+/// may be inlined).
+pub fn optimize(function: Function, functions: &[Function], options: &OptimizeOptions) -> Function {
+    let operators = functions.iter()
+        // a function redefining an operator must not inline its previous definition
+        .filter(|fun| fun.prototype.name != function.prototype.name)
+        .filter(|fun| fun.prototype.binary_operator().is_some() || fun.prototype.unary_operator().is_some())
+        .filter_map(|fun| match &fun.body {
+            Some(body) if is_trivial(body, &fun.prototype.args) => Some((fun.prototype.name.as_str(), (fun.prototype.args.as_slice(), body))),
+            _ => None
+        })
+        .collect();
+
+    let optimized = Optimizer { operators, options }.fold_function(function);
+
+    // the locations of the expressions do not match the optimized body anymore
+    Function { locations: None, ..optimized }
+}
+
+/// Returns a value indicating whether the given expression only uses numbers, the given
+/// variables, conditionals and builtin operators (assignment excepted).
+///
+/// Such an expression has no side effect, and can be evaluated as soon as the values of
+/// its variables are known.
+fn is_trivial(expr: &Expr, variables: &[String]) -> bool {
+    match expr {
+        Expr::Number(_) => true,
+        Expr::Variable(name) => variables.contains(name),
+        Expr::Binary { op, left, right } => {
+            "+-*/<>".contains(*op) && is_trivial(left, variables) && is_trivial(right, variables)
+        },
+        Expr::Conditional { cond, consequence, alternative } => {
+            is_trivial(cond, variables) && is_trivial(consequence, variables) && is_trivial(alternative, variables)
+        },
+        _ => false
+    }
+}
+
+/// Replaces variables by expressions. Only used on trivial expressions, which do not bind
+/// any variable.
+struct Substitute<'a> {
+    bindings: HashMap<&'a str, &'a Expr>
+}
+
+impl<'a> Fold for Substitute<'a> {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        match expr {
+            Expr::Variable(name) => match self.bindings.get(name.as_str()) {
+                Some(value) => (*value).clone(),
+                None => Expr::Variable(name)
+            },
+            expr => visit::fold_expr(self, expr)
+        }
+    }
+}
+
+/// Finds whether a variable is assigned, including when it is shadowed.
+struct FindAssignment<'a> {
+    name: &'a str,
+    found: bool
+}
+
+impl<'a> Visitor for FindAssignment<'a> {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let Expr::Binary { op: '=', left, .. } = expr {
+            if let Expr::Variable(name) = left.as_ref() {
+                self.found |= name == self.name;
+            }
+        }
+
+        visit::walk_expr(self, expr);
+    }
+}
+
+struct Optimizer<'a> {
+    /// Trivial user-defined operators, which can be inlined, along with their parameters.
+    operators: HashMap<&'a str, (&'a [String], &'a Expr)>,
+    options: &'a OptimizeOptions
+}
+
+impl<'a> Optimizer<'a> {
+
+    /// Returns the inlined body of the given operator, if it is trivial and if its arguments
+    /// are simple enough to be duplicated (or removed) without changing the semantics.
+    fn inline(&mut self, name: &str, args: &[Expr]) -> Option<Expr> {
+        if !self.options.inline_operators || !args.iter().all(|arg| matches!(arg, Expr::Number(_) | Expr::Variable(_))) {
+            return None;
+        }
+
+        let (params, body) = *self.operators.get(name)?;
+
+        if params.len() != args.len() {
+            return None;
+        }
+
+        let bindings = params.iter().map(String::as_str).zip(args.iter()).collect();
+        let inlined = Substitute { bindings }.fold_expr(body.clone());
+
+        Some(self.fold_expr(inlined))
+    }
+
+    /// Returns the value of a trivial expression of the given variable, if it is constant.
+    fn evaluate(&mut self, expr: &Expr, var_name: &str, value: f64) -> Option<f64> {
+        let value = Expr::Number(value);
+        let bindings = std::iter::once((var_name, &value)).collect();
+
+        match self.fold_expr(Substitute { bindings }.fold_expr(expr.clone())) {
+            Expr::Number(nb) => Some(nb),
+            _ => None
+        }
+    }
+
+    /// Unrolls the given `for` loop into a `var` expression which successively binds the loop
+    /// variable to its values and to the results of the body, e.g.
+    /// `for i = 0, i < 1 in f(i)` becomes `var i = 0, i = f(i), i = 1, i = f(i) in 0`.
+    fn unroll(&mut self, var_name: &str, start: &Expr, end: &Expr, step: Option<&Expr>, body: &Expr) -> Option<Expr> {
+        let mut value = match start {
+            Expr::Number(nb) => *nb,
+            _ => return None
+        };
+
+        let variables = [ var_name.to_string() ];
+
+        let trivial_step = match step {
+            Some(step) => is_trivial(step, &variables),
+            None => true
+        };
+
+        if !trivial_step || !is_trivial(end, &variables) {
+            return None;
+        }
+
+        // the body must not change the value of the loop variable, since the unrolled
+        // loop binds the variable to constants
+        let mut assignment = FindAssignment { name: var_name, found: false };
+
+        assignment.visit_expr(body);
+
+        if assignment.found {
+            return None;
+        }
+
+        let mut bindings = Vec::new();
+
+        // like the compiled loop, evaluate the body before checking the end condition,
+        // which is given the value of the variable before it is incremented
+        loop {
+            if bindings.len() / 2 >= self.options.unroll_limit {
+                return None;
+            }
+
+            bindings.push((var_name.to_string(), Some(Expr::Number(value))));
+            bindings.push((var_name.to_string(), Some(body.clone())));
+
+            let step = match step {
+                Some(step) => self.evaluate(step, var_name, value)?,
+                None => 1.
+            };
+
+            if !is_true(self.evaluate(end, var_name, value)?) {
+                break;
+            }
+
+            value += step;
+        }
+
+        Some(Expr::VarIn {
+            variables: bindings,
+            body: Box::new(Expr::Number(0.))
+        })
+    }
+}
+
+impl<'a> Fold for Optimizer<'a> {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        match visit::fold_expr(self, expr) {
+            Expr::Binary { op, left, right } => match (op, *left, *right) {
+                (op, Expr::Number(left), Expr::Number(right)) if builtin_binary(op, left, right).is_some() => {
+                    Expr::Number(builtin_binary(op, left, right).unwrap())
+                },
+
+                // algebraic identities which hold for every value (including NaN and -0)
+                ('*', expr, Expr::Number(nb)) | ('*', Expr::Number(nb), expr) | ('/', expr, Expr::Number(nb)) if nb == 1. => expr,
+                ('-', expr, Expr::Number(nb)) if nb == 0. && nb.is_sign_positive() => expr,
+
+                (op, left, right) => {
+                    let mut name = String::from("binary");
+
+                    name.push(op);
+
+                    let args = [ left, right ];
+
+                    match self.inline(&name, &args) {
+                        Some(inlined) => inlined,
+                        None => {
+                            let [ left, right ] = args;
+
+                            Expr::Binary { op, left: Box::new(left), right: Box::new(right) }
+                        }
+                    }
+                }
+            },
+
+            Expr::Call { func_name, args } => match self.inline(&func_name, &args) {
+                Some(inlined) => inlined,
+                None => Expr::Call { func_name, args }
+            },
+
+            Expr::Conditional { cond, consequence, alternative } => match *cond {
+                Expr::Number(nb) if is_true(nb) => *consequence,
+                Expr::Number(_) => *alternative,
+                cond => Expr::Conditional { cond: Box::new(cond), consequence, alternative }
+            },
+
+            Expr::For { var_name, start, end, step, body } if self.options.unroll_limit > 0 => {
+                match self.unroll(&var_name, &start, &end, step.as_deref(), &body) {
+                    Some(unrolled) => unrolled,
+                    None => Expr::For { var_name, start, end, step, body }
+                }
+            },
+
+            expr => expr
+        }
+    }
+}
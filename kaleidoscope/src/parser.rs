@@ -1,9 +1,9 @@
-use crate::lexer::{Token, Lexer};
-use std::collections::HashMap;
-use crate::ANONYMOUS_FUNCTION_NAME;
+use crate::lexer::Span;
+use crate::operator::{Associativity, OperatorTable};
+use crate::syntax::{self, SyntaxKind, SyntaxNode};
 
 /// Defines a primitive expression.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Binary {
         op: char,
@@ -35,7 +35,7 @@ pub enum Expr {
 }
 
 /// Defines the prototype (name and parameters) of a function.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Prototype {
     pub name: String,
     pub args: Vec<String>,
@@ -43,543 +43,199 @@ pub struct Prototype {
     pub prec: usize
 }
 
-/// Defines a user-defined or external function.
-#[derive(Debug)]
-pub struct Function {
-    pub prototype: Prototype,
-    pub body: Option<Expr>,
-    pub is_anon: bool
-}
-
-/// Represents the `Expr` parser.
-pub struct Parser<'a> {
-    tokens: Vec<Token>,
-    pos: usize,
-    prec: &'a mut HashMap<char, i32>
-}
-
-// I'm ignoring the 'must_use' lint in order to call 'self.advance' without checking
-// the result when an EOF is acceptable.
-impl<'a> Parser<'a> {
-
-    pub fn new(input: String, op_precedence: &'a mut HashMap<char, i32>) -> Self {
-        let mut lexer = Lexer::new(input.as_str());
-        let tokens = lexer.by_ref().collect();
-        Parser {
-            tokens,
-            prec: op_precedence,
-            pos: 0
-        }
-    }
-
-    /// Parses the content of the parser.
-    pub fn parse(&mut self) -> Result<Function, &'static str> {
-        let result = match self.current()? {
-            Token::Def => self.parse_def(),
-            Token::Extern => self.parse_extern(),
-            _ => self.parse_toplevel_expr()
-        };
-
-        match result {
-            Ok(result) => {
-                if !self.at_end() {
-                    Err("Unexpected token after parsed expression.")
-                } else {
-                    Ok(result)
-                }
-            },
-
-            err => err
-        }
-    }
-
-    /// Returns the current `Token`, without performing safety checks beforehand.
-    fn curr(&self) -> Token {
-        self.tokens[self.pos].clone()
-    }
-
-    /// Returns the current `Token`, or an error that
-    /// indicates that the end of the file has been unexpectedly reached if it is the case.
-    fn current(&self) -> Result<Token, &'static str> {
-        if self.pos >= self.tokens.len() {
-            Err("Unexpected end of file.")
-        } else {
-            Ok(self.tokens[self.pos].clone())
-        }
-    }
-
-    /// Advances the position, and returns an empty `Result` whose error
-    /// indicates that the end of the file has been unexpectedly reached.
-    /// This allows to use the `self.advance()?;` syntax.
-    fn advance(&mut self) -> Result<(), &'static str> {
-        let npos = self.pos + 1;
-        self.pos = npos;
-
-        if npos < self.tokens.len() {
-            Ok(())
+impl Prototype {
+    /// Returns the operator defined by this prototype, if it is a binary operator.
+    pub fn binary_operator(&self) -> Option<char> {
+        if self.is_op && self.args.len() == 2 {
+            self.name.strip_prefix("binary").and_then(|op| op.chars().next())
         } else {
-            Err("Unexpected end of file")
+            None
         }
     }
 
-    /// Returns a value indicating whether or not the `Parser`
-    /// has reached the end of the input.
-    fn at_end(&self) -> bool {
-        self.pos >= self.tokens.len()
-    }
-
-    /// Returns the precedence of the current `Token`, or 0 if it is not recognized as a binary operator.
-    fn get_token_precedence(&self) -> i32 {
-        if let Ok(Token::Op(op)) = self.current() {
-           *self.prec.get(&op).unwrap_or(&100)
+    /// Returns the operator defined by this prototype, if it is an unary operator.
+    pub fn unary_operator(&self) -> Option<char> {
+        if self.is_op && self.args.len() == 1 {
+            self.name.strip_prefix("unary").and_then(|op| op.chars().next())
         } else {
-            -1
+            None
         }
     }
+}
 
-    /// Parses the prototype of a function, whether external or user-defined.
-    fn parse_prototype(&mut self) -> Result<Prototype, &'static str> {
-        let (id, is_operator, precedence) = match self.curr() {
-            Token::Ident(id) => {
-                self.advance()?;
-
-                (id, false, 0)
-            },
-
-            Token::Binary => {
-                self.advance()?;
-
-                let op = match self.curr() {
-                    Token::Op(ch) => ch,
-                    _ => return Err("Expected operator in custom operator declaration.")
-                };
-
-                self.advance()?;
-
-                let mut name = String::from("binary");
-
-                name.push(op);
-
-                let prec = if let Token::Number(prec) = self.curr() {
-                    self.advance()?;
-
-                    prec as usize
-                } else {
-                    0
-                };
-
-                self.prec.insert(op, prec as i32);
-
-                (name, true, prec)
-            },
-
-            Token::Unary => {
-                self.advance()?;
-
-                let op = match self.curr() {
-                    Token::Op(ch) => ch,
-                    _ => return Err("Expected operator in custom operator declaration.")
-                };
-
-                let mut name = String::from("unary");
-
-                name.push(op);
-
-                self.advance()?;
-
-                (name, true, 0)
-            },
-
-            _ => return Err("Expected identifier in prototype declaration.")
-        };
-
-        match self.curr() {
-            Token::LParen => (),
-            _ => return Err("Expected '(' character in prototype declaration.")
-        }
-
-        self.advance()?;
-
-        if let Token::RParen = self.curr() {
-            self.advance();
-
-            return Ok(Prototype {
-                name: id,
-                args: vec![],
-                is_op: is_operator,
-                prec: precedence
-            });
-        }
-
-        let mut args = vec![];
-
-        loop {
-            match self.curr() {
-                Token::Ident(name) => args.push(name),
-                _ => return Err("Expected identifier in parameter declaration.")
-            }
-
-            self.advance()?;
-
-            match self.curr() {
-                Token::RParen => {
-                    self.advance();
-                    break;
-                },
-                Token::Comma => {
-                    self.advance();
-                },
-                _ => return Err("Expected ',' or ')' character in prototype declaration.")
-            }
-        }
-
-        Ok(Prototype {
-            name: id,
-            args,
-            is_op: is_operator,
-            prec: precedence
-        })
-    }
-
-    /// Parses a user-defined function.
-    fn parse_def(&mut self) -> Result<Function, &'static str> {
-        // Eat 'def' keyword
-        self.pos += 1;
-
-        // Parse signature of function
-        let proto = self.parse_prototype()?;
-
-        // Parse body of function
-        let body = self.parse_expr()?;
-
-        // Return new function
-        Ok(Function {
-            prototype: proto,
-            body: Some(body),
-            is_anon: false
-        })
-    }
+/// Defines a position in the source code, as a 1-based line and column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize
+}
 
-    /// Parses an external function declaration.
-    fn parse_extern(&mut self) -> Result<Function, &'static str> {
-        // Eat 'extern' keyword
-        self.pos += 1;
+/// Defines the locations of a function in the source code it was parsed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locations {
+    /// Location of the definition (or of the top-level expression).
+    pub definition: Location,
+    /// Locations of the expressions of the body, in the order in which they are visited by
+    /// `visit::walk_expr` (parents before their children).
+    pub expressions: Vec<Location>
+}
 
-        // Parse signature of extern function
-        let proto = self.parse_prototype()?;
+/// Defines a user-defined or external function.
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub prototype: Prototype,
+    pub body: Option<Expr>,
+    pub is_anon: bool,
+    pub doc: Option<String>,
+    /// Locations of the function in the source code, used to emit debug information. They are
+    /// only known for functions read by the `Parser`, and are dropped by the optimizer.
+    pub locations: Option<Locations>
+}
 
-        Ok(Function {
-            prototype: proto,
-            body: None,
-            is_anon: false
-        })
+// functions are compared regardless of where they were parsed from
+impl PartialEq for Function {
+    fn eq(&self, other: &Function) -> bool {
+        self.prototype == other.prototype && self.body == other.body && self.is_anon == other.is_anon && self.doc == other.doc
     }
+}
 
-    /// Parses any expression.
-    fn parse_expr(&mut self) -> Result<Expr, &'static str> {
-        match self.parse_unary_expr() {
-            Ok(left) => self.parse_binary_expr(0, left),
-            err => err
-        }
-    }
+/// Defines a module imported by a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Import {
+    /// Import of a file by path (`import "lib/util.ks"`).
+    Path(String),
+    /// Import of a module by name (`import math`), which is the file `math.ks`.
+    Module(String)
+}
 
-    /// Parses a literal number.
-    fn parse_nb_expr(&mut self) -> Result<Expr, &'static str> {
-        // Simply convert Token::Number to Expr::Number
-        match self.curr() {
-            Token::Number(nb) => {
-                self.advance();
-                Ok(Expr::Number(nb))
-            },
-            _ => Err("Expected number literal.")
+impl Import {
+    /// Returns the path of the imported file, relative to the importing file or to a directory
+    /// of the search path.
+    pub fn file_path(&self) -> String {
+        match self {
+            Import::Path(path) => path.clone(),
+            Import::Module(name) => format!("{}.ks", name)
         }
     }
+}
 
-    /// Parses an expression enclosed in parenthesis.
-    fn parse_paren_expr(&mut self) -> Result<Expr, &'static str> {
-        match self.current()? {
-            Token::LParen => (),
-            _ => return Err("Expected '(' character at start of parenthesized expression.")
-        }
-
-        self.advance()?;
-
-        let expr = self.parse_expr()?;
-
-        match self.current()? {
-            Token::RParen => (),
-            _ => return Err("Expected ')' character at end of parenthesized expression.")
-        }
-
-        self.advance();
-
-        Ok(expr)
+/// Defines a top-level item of a source file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Import(Import),
+    /// Function, extern declaration or top-level expression; only exported functions are
+    /// visible outside of the module defining them.
+    Function {
+        function: Function,
+        exported: bool
     }
+}
 
-    /// Parses an expression that starts with an identifier (either a variable or a function call).
-    fn parse_id_expr(&mut self) -> Result<Expr, &'static str> {
-        let id = match self.curr() {
-            Token::Ident(id) => id,
-            _ => return Err("Expected identifier.")
-        };
-
-        if self.advance().is_err() {
-            return Ok(Expr::Variable(id));
-        }
-
-        match self.curr() {
-            Token::LParen => {
-                self.advance()?;
-
-                if let Token::RParen = self.curr() {
-                    return Ok(Expr::Call { func_name: id, args: vec![] });
-                }
-
-                let mut args = vec![];
-
-                loop {
-                    args.push(self.parse_expr()?);
-
-                    match self.current()? {
-                        Token::Comma => (),
-                        Token::RParen => break,
-                        _ => return Err("Expected ',' character in function call.")
-                    }
-
-                    self.advance()?;
-                }
-
-                self.advance();
+/// Represents the `Expr` parser, which derives the AST from the syntax tree built by
+/// `syntax::parse`, so that both share a single grammar.
+pub struct Parser<'a> {
+    input: String,
+    prec: &'a mut OperatorTable
+}
 
-                Ok(Expr::Call { func_name: id, args: args })
-            },
+impl<'a> Parser<'a> {
 
-            _ => Ok(Expr::Variable(id))
+    pub fn new(input: String, op_precedence: &'a mut OperatorTable) -> Self {
+        Parser {
+            input,
+            prec: op_precedence
         }
     }
 
-    /// Parses an unary expression.
-    fn parse_unary_expr(&mut self) -> Result<Expr, &'static str> {
-        let op = match self.current()? {
-            Token::Op(ch) => {
-                self.advance()?;
-                ch
-            },
-            _ => return self.parse_primary()
-        };
-
-        let mut name = String::from("unary");
-
-        name.push(op);
-
-        Ok(Expr::Call {
-            func_name: name,
-            args: vec![ self.parse_unary_expr()? ]
-        })
-    }
-
-    /// Parses a binary expression, given its left-hand expression.
-    fn parse_binary_expr(&mut self, prec: i32, mut left: Expr) -> Result<Expr, &'static str> {
-        loop {
-            let curr_prec = self.get_token_precedence();
-
-            if curr_prec < prec || self.at_end() {
-                return Ok(left);
-            }
-
-            let op = match self.curr() {
-                Token::Op(op) => op,
-                _ => return Err("Invalid operator.")
-            };
-
-            self.advance()?;
-
-            let mut right = self.parse_unary_expr()?;
-
-            let next_prec = self.get_token_precedence();
-
-            if curr_prec < next_prec {
-                right = self.parse_binary_expr(curr_prec + 1, right)?;
-            }
-
-            left = Expr::Binary {
-                op,
-                left: Box::new(left),
-                right: Box::new(right)
+    /// Parses the content of the parser, which must be a single function, extern declaration
+    /// or top-level expression (which may be exported).
+    pub fn parse(&mut self) -> Result<Function, &'static str> {
+        match self.parse_item()? {
+            Item::Function { function, .. } => Ok(function),
+            Item::Import(_) => Err("Imports are only supported when loading modules.")
+        }
+    }
+
+    /// Parses the content of the parser, which may also be an import or an exported function.
+    ///
+    /// The binary operator defined by the parsed function, if any, is added to the operator table.
+    pub fn parse_item(&mut self) -> Result<Item, &'static str> {
+        let input = self.input.as_str();
+        let tree = syntax::parse(input, self.prec);
+        let root = tree.root();
+
+        // nodes recovered from an error belong to the item that precedes them
+        let mut items = root.children().filter(|node| node.kind() != SyntaxKind::Error);
+        let item = items.next();
+        let next = items.next().map(|node| node.span().start);
+
+        if let Some(err) = tree.errors().iter().find(|err| next.is_none_or(|next| err.span.start < next)) {
+            return if err.span == Span::new(input.len(), input.len()) {
+                Err("Unexpected end of file.")
+            } else {
+                Err(err.error)
             };
         }
-    }
-
-    /// Parses a conditional if..then..else expression.
-    fn parse_conditional_expr(&mut self) -> Result<Expr, &'static str> {
-        // eat 'if' token
-        self.advance()?;
-
-        let cond = self.parse_expr()?;
-
-        // eat 'then' token
-        match self.current() {
-            Ok(Token::Then) => self.advance()?,
-            _ => return Err("Expected 'then' keyword.")
-        }
-
-        let then = self.parse_expr()?;
-
-        // eat 'else' token
-        match self.current() {
-            Ok(Token::Else) => self.advance()?,
-            _ => return Err("Expected 'else' keyword.")
-        }
-
-        let otherwise = self.parse_expr()?;
-
-        Ok(Expr::Conditional {
-            cond: Box::new(cond),
-            consequence: Box::new(then),
-            alternative: Box::new(otherwise)
-        })
-    }
-
-    /// Parses a loop for..in.. expression.
-    fn parse_for_expr(&mut self) -> Result<Expr, &'static str> {
-        // eat 'for' token
-        self.advance()?;
-
-        let name = match self.curr() {
-            Token::Ident(n) => n,
-            _ => return Err("Expected identifier in for loop.")
-        };
 
-        // eat identifier
-        self.advance()?;
+        let node = item.ok_or("Unexpected end of file.")?;
 
-        // eat '=' token
-        match self.curr() {
-            Token::Op('=') => self.advance()?,
-            _ => return Err("Expected '=' character in for loop.")
+        if next.is_some() {
+            return match node.kind() {
+                SyntaxKind::ImportDecl => Err("Unexpected token after import declaration."),
+                _ => Err("Unexpected token after parsed expression.")
+            };
         }
 
-        let start = self.parse_expr()?;
+        match syntax::lower_item(node)? {
+            Item::Function { mut function, exported } => {
+                let definition = first_token(node).map_or(Location { line: 1, column: 1 }, |span| location(span, input));
+                let mut expressions = Vec::new();
 
-        // eat ',' token
-        match self.current()? {
-            Token::Comma => self.advance()?,
-            _ => return Err("Expected ',' character in for loop.")
-        }
+                expression_locations(node, input, &mut expressions);
 
-        let end = self.parse_expr()?;
+                if let Some(op) = function.prototype.binary_operator() {
+                    self.prec.insert(op, function.prototype.prec as i32, Associativity::Left);
+                }
 
-        // parse (optional) step expression
-        let step = match self.current()? {
-            Token::Comma => {
-                self.advance()?;
+                function.locations = Some(Locations { definition, expressions });
 
-                Some(self.parse_expr()?)
+                Ok(Item::Function { function, exported })
             },
 
-            _ => None
-        };
-
-        // eat 'in' token
-        match self.current()? {
-            Token::In => self.advance()?,
-            _ => return Err("Expected 'in' keyword in for loop.")
+            import => Ok(import)
         }
-
-        let body = self.parse_expr()?;
-
-        Ok(Expr::For {
-            var_name: name,
-            start: Box::new(start),
-            end: Box::new(end),
-            step: step.map(Box::new),
-            body: Box::new(body)
-        })
     }
+}
 
-    /// Parses a var..in expression.
-    fn parse_var_expr(&mut self) -> Result<Expr, &'static str> {
-        // eat 'var' token
-        self.advance()?;
-
-        let mut variables = Vec::new();
-
-        // parse variables
-        loop {
-            let name = match self.curr() {
-                Token::Ident(name) => name,
-                _ => return Err("Expected identifier in 'var..in' declaration.")
-            };
-
-            self.advance()?;
-
-            // read (optional) initializer
-            let initializer = match self.curr() {
-                Token::Op('=') => Some({
-                    self.advance()?;
-                    self.parse_expr()?
-                }),
-
-                _ => None
-            };
-
-            variables.push((name, initializer));
+/// Returns the location of the given span in the given source code.
+fn location(span: Span, input: &str) -> Location {
+    let (line, column) = span.location(input);
 
-            match self.curr() {
-                Token::Comma => {
-                    self.advance()?;
-                },
-                Token::In => {
-                    self.advance()?;
-                    break;
-                }
-                _ => {
-                    return Err("Expected comma or 'in' keyword in variable declaration.")
-                }
-            }
-        }
+    Location { line, column }
+}
 
-        // parse body
-        let body = self.parse_expr()?;
+/// Returns the span of the first token of the given node which is not trivia.
+fn first_token(node: SyntaxNode) -> Option<Span> {
+    node.descendant_tokens().into_iter().find(|token| !token.kind().is_trivia()).map(|token| token.span())
+}
 
-        Ok(Expr::VarIn {
-            variables,
-            body: Box::new(body)
-        })
-    }
+/// Adds the locations of the expressions contained in the given node to `locations`, in the
+/// order in which `visit::walk_expr` visits them (parents before their children).
+fn expression_locations(node: SyntaxNode, input: &str, locations: &mut Vec<Location>) {
+    // parenthesized expressions do not appear in the AST, and binary expressions are located
+    // at their operator
+    let span = match node.kind() {
+        SyntaxKind::ParenExpr => None,
+        SyntaxKind::BinaryExpr => node.child_token(SyntaxKind::Op).map(|token| token.span()),
+        kind if kind.is_expr() => first_token(node),
+        _ => None
+    };
 
-    /// Parses a primary expression (an identifier, a number or a parenthesized expression).
-    fn parse_primary(&mut self) -> Result<Expr, &'static str> {
-        match self.curr() {
-            Token::Ident(_) => self.parse_id_expr(),
-            Token::Number(_) => self.parse_nb_expr(),
-            Token::LParen => self.parse_paren_expr(),
-            Token::If => self.parse_conditional_expr(),
-            Token::For => self.parse_for_expr(),
-            Token::Var => self.parse_var_expr(),
-            _ => Err("Unknown expression.")
-        }
+    if let Some(span) = span {
+        locations.push(location(span, input));
     }
 
-    /// Parses a top-level expression and makes an anonymous function out of it,
-    /// for easier compilation.
-    fn parse_toplevel_expr(&mut self) -> Result<Function, &'static str> {
-        match self.parse_expr() {
-            Ok(expr) => {
-                Ok(Function {
-                    prototype: Prototype {
-                        name: ANONYMOUS_FUNCTION_NAME.to_string(),
-                        args: vec![],
-                        is_op: false,
-                        prec: 0
-
-                    },
-                    body: Some(expr),
-                    is_anon: true
-                })
-            },
-
-            Err(err) => Err(err)
-        }
+    for child in node.children() {
+        expression_locations(child, input, locations);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,270 @@
+//! Printing of the AST back to Kaleidoscope source code.
+//!
+//! Parentheses are only emitted where they are needed for the printed code to be parsed back
+//! into the same AST, which depends on the precedence of the operators. The `Display`
+//! implementations use the builtin operators, while `pretty` can be used to print code
+//! which uses operators defined in a session.
+
+use std::fmt;
+use crate::operator::{Associativity, OperatorTable, DEFAULT_PRECEDENCE};
+use crate::parser::{Expr, Function, Prototype};
+
+/// Wraps an AST item so that it is displayed using the given operator table.
+pub struct Pretty<'a, T> {
+    item: &'a T,
+    operators: &'a OperatorTable
+}
+
+impl Expr {
+    /// Returns a value which displays the expression using the given operators.
+    pub fn pretty<'a>(&'a self, operators: &'a OperatorTable) -> Pretty<'a, Expr> {
+        Pretty { item: self, operators }
+    }
+}
+
+impl Prototype {
+    /// Returns a value which displays the prototype using the given operators.
+    pub fn pretty<'a>(&'a self, operators: &'a OperatorTable) -> Pretty<'a, Prototype> {
+        Pretty { item: self, operators }
+    }
+}
+
+impl Function {
+    /// Returns a value which displays the function using the given operators.
+    pub fn pretty<'a>(&'a self, operators: &'a OperatorTable) -> Pretty<'a, Function> {
+        Pretty { item: self, operators }
+    }
+}
+
+/// Returns the operator of a call to an unary operator, e.g. '!' for `unary!`.
+fn unary_operator(func_name: &str, args: &[Expr]) -> Option<char> {
+    let mut chars = func_name.strip_prefix("unary")?.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(op), None) if args.len() == 1 && is_operator_char(op) => Some(op),
+        _ => None
+    }
+}
+
+/// Returns a value indicating whether the given character is lexed as an operator.
+fn is_operator_char(ch: char) -> bool {
+    !ch.is_alphanumeric() && !ch.is_whitespace() && !"_.#(),".contains(ch)
+}
+
+/// Defines the context in which an expression is printed.
+#[derive(Clone, Copy)]
+struct Context {
+    /// Minimum precedence a binary expression must have to be printed without parentheses.
+    min_prec: i32,
+    /// Whether the expression extends to the end of the enclosing expression, in which
+    /// case 'if', 'for' and 'var' expressions (whose body extends as far as possible)
+    /// can be printed without parentheses.
+    trailing: bool
+}
+
+impl Context {
+    /// Context of an expression which is delimited by a token, e.g. a function argument.
+    const DELIMITED: Context = Context { min_prec: i32::MIN, trailing: true };
+}
+
+struct Printer<'a> {
+    operators: &'a OperatorTable
+}
+
+impl<'a> Printer<'a> {
+    fn precedence(&self, op: char) -> i32 {
+        self.operators.precedence(op).unwrap_or(DEFAULT_PRECEDENCE)
+    }
+
+    fn needs_parens(&self, expr: &Expr, ctx: Context) -> bool {
+        match expr {
+            Expr::Binary { op, .. } => self.precedence(*op) < ctx.min_prec,
+            Expr::Conditional { .. } | Expr::For { .. } | Expr::VarIn { .. } => !ctx.trailing,
+            Expr::Number(nb) if !nb.is_finite() => self.precedence('/') < ctx.min_prec,
+            Expr::Number(nb) if nb.is_sign_negative() => self.precedence('-') < ctx.min_prec,
+            _ => false
+        }
+    }
+
+    fn write_expr(&self, f: &mut fmt::Formatter, expr: &Expr, ctx: Context) -> fmt::Result {
+        if self.needs_parens(expr, ctx) {
+            f.write_str("(")?;
+            self.write_expr(f, expr, Context::DELIMITED)?;
+            return f.write_str(")");
+        }
+
+        match expr {
+            // the parser never produces negative or infinite numbers, but optimizations may
+            Expr::Number(nb) if nb.is_nan() => f.write_str("0 / 0"),
+            Expr::Number(nb) if nb.is_infinite() && *nb > 0. => f.write_str("1 / 0"),
+            Expr::Number(nb) if nb.is_infinite() => f.write_str("(0 - 1) / 0"),
+            Expr::Number(nb) if nb.is_sign_negative() => write!(f, "0 - {}", -nb),
+            Expr::Number(nb) => write!(f, "{}", nb),
+
+            Expr::Variable(name) => f.write_str(name),
+
+            Expr::Binary { op, left, right } => {
+                let prec = self.precedence(*op);
+                let (mut left_prec, right_prec) = match self.operators.associativity(*op) {
+                    Associativity::Left => (prec, prec + 1),
+                    Associativity::Right => (prec + 1, prec)
+                };
+
+                // a right-associative operator with the same precedence would take the
+                // rest of the expression as its right operand, e.g. `(a = b) | c`
+                if let Expr::Binary { op: left_op, .. } = left.as_ref() {
+                    if self.operators.associativity(*left_op) == Associativity::Right && self.precedence(*left_op) == prec {
+                        left_prec = prec + 1;
+                    }
+                }
+
+                self.write_expr(f, left, Context { min_prec: left_prec, trailing: false })?;
+                write!(f, " {} ", op)?;
+                self.write_expr(f, right, Context { min_prec: right_prec, trailing: ctx.trailing })
+            },
+
+            Expr::Call { func_name, args } => match unary_operator(func_name, args) {
+                Some(op) => {
+                    write!(f, "{}", op)?;
+                    self.write_expr(f, &args[0], Context { min_prec: i32::MAX, trailing: ctx.trailing })
+                },
+
+                None => {
+                    write!(f, "{}(", func_name)?;
+
+                    for (i, arg) in args.iter().enumerate() {
+                        if i > 0 {
+                            f.write_str(", ")?;
+                        }
+
+                        self.write_expr(f, arg, Context::DELIMITED)?;
+                    }
+
+                    f.write_str(")")
+                }
+            },
+
+            Expr::Conditional { cond, consequence, alternative } => {
+                f.write_str("if ")?;
+                self.write_expr(f, cond, Context::DELIMITED)?;
+                f.write_str(" then ")?;
+                self.write_expr(f, consequence, Context::DELIMITED)?;
+                f.write_str(" else ")?;
+                self.write_expr(f, alternative, Context { min_prec: i32::MIN, trailing: ctx.trailing })
+            },
+
+            Expr::For { var_name, start, end, step, body } => {
+                write!(f, "for {} = ", var_name)?;
+                self.write_expr(f, start, Context::DELIMITED)?;
+                f.write_str(", ")?;
+                self.write_expr(f, end, Context::DELIMITED)?;
+
+                if let Some(step) = step {
+                    f.write_str(", ")?;
+                    self.write_expr(f, step, Context::DELIMITED)?;
+                }
+
+                f.write_str(" in ")?;
+                self.write_expr(f, body, Context { min_prec: i32::MIN, trailing: ctx.trailing })
+            },
+
+            Expr::VarIn { variables, body } => {
+                f.write_str("var ")?;
+
+                for (i, (name, initializer)) in variables.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+
+                    f.write_str(name)?;
+
+                    if let Some(init) = initializer {
+                        f.write_str(" = ")?;
+                        self.write_expr(f, init, Context::DELIMITED)?;
+                    }
+                }
+
+                f.write_str(" in ")?;
+                self.write_expr(f, body, Context { min_prec: i32::MIN, trailing: ctx.trailing })
+            }
+        }
+    }
+
+    fn write_prototype(&self, f: &mut fmt::Formatter, proto: &Prototype) -> fmt::Result {
+        f.write_str(&proto.name)?;
+
+        if proto.binary_operator().is_some() && proto.prec != 0 {
+            write!(f, " {} ", proto.prec)?;
+        }
+
+        write!(f, "({})", proto.args.join(", "))
+    }
+
+    fn write_function(&self, f: &mut fmt::Formatter, fun: &Function) -> fmt::Result {
+        if let Some(doc) = &fun.doc {
+            for line in doc.split('\n') {
+                writeln!(f, "## {}", line)?;
+            }
+        }
+
+        match &fun.body {
+            Some(body) if fun.is_anon => self.write_expr(f, body, Context::DELIMITED),
+            Some(body) => {
+                f.write_str("def ")?;
+                self.write_prototype(f, &fun.prototype)?;
+                f.write_str(" ")?;
+
+                // the operator defined by the function is already usable in its body
+                match fun.prototype.binary_operator() {
+                    Some(op) => {
+                        let mut operators = self.operators.clone();
+
+                        operators.insert(op, fun.prototype.prec as i32, Associativity::Left);
+                        Printer { operators: &operators }.write_expr(f, body, Context::DELIMITED)
+                    },
+                    None => self.write_expr(f, body, Context::DELIMITED)
+                }
+            },
+            None => {
+                f.write_str("extern ")?;
+                self.write_prototype(f, &fun.prototype)
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Display for Pretty<'a, Expr> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Printer { operators: self.operators }.write_expr(f, self.item, Context::DELIMITED)
+    }
+}
+
+impl<'a> fmt::Display for Pretty<'a, Prototype> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Printer { operators: self.operators }.write_prototype(f, self.item)
+    }
+}
+
+impl<'a> fmt::Display for Pretty<'a, Function> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Printer { operators: self.operators }.write_function(f, self.item)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.pretty(&OperatorTable::new()).fmt(f)
+    }
+}
+
+impl fmt::Display for Prototype {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.pretty(&OperatorTable::new()).fmt(f)
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.pretty(&OperatorTable::new()).fmt(f)
+    }
+}
@@ -0,0 +1,148 @@
+//! Standard library of host functions (math, random numbers, time and I/O), declared as
+//! `extern`s at the start of each session so that programs can call them directly, and
+//! prelude of common user-defined operators.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::backend::{Backend, BackendError, HostFunction};
+use crate::session::Session;
+use crate::syntax;
+
+/// Defines a builtin function, implemented by the host.
+pub struct Builtin {
+    pub name: &'static str,
+    /// Names of the parameters of its `extern` declaration.
+    pub params: &'static [&'static str],
+    pub function: HostFunction
+}
+
+/// Builtin functions of the runtime.
+pub const BUILTINS: &[Builtin] = &[
+    Builtin { name: "sin", params: &["x"], function: HostFunction::Args1(sin) },
+    Builtin { name: "cos", params: &["x"], function: HostFunction::Args1(cos) },
+    Builtin { name: "sqrt", params: &["x"], function: HostFunction::Args1(sqrt) },
+    Builtin { name: "pow", params: &["x", "y"], function: HostFunction::Args2(pow) },
+    Builtin { name: "floor", params: &["x"], function: HostFunction::Args1(floor) },
+    Builtin { name: "abs", params: &["x"], function: HostFunction::Args1(abs) },
+    Builtin { name: "random", params: &[], function: HostFunction::Args0(random) },
+    Builtin { name: "clock", params: &[], function: HostFunction::Args0(clock) },
+    Builtin { name: "readd", params: &[], function: HostFunction::Args0(readd) },
+    Builtin { name: "putchard", params: &["c"], function: HostFunction::Args1(putchard) },
+    Builtin { name: "printd", params: &["x"], function: HostFunction::Args1(printd) },
+    Builtin { name: "printfd", params: &["x", "width", "precision"], function: HostFunction::Args3(printfd) }
+];
+
+/// Registers the builtin functions with the given backend, without declaring them.
+pub fn register(backend: &mut dyn Backend) -> Result<(), &'static str> {
+    for builtin in BUILTINS {
+        backend.register_extern(builtin.name, builtin.function)?;
+    }
+
+    Ok(())
+}
+
+/// Registers the builtin functions with the given backend, and declares them as `extern`s
+/// in both the backend and the session.
+pub fn declare(session: &mut Session, backend: &mut dyn Backend) -> Result<(), BackendError> {
+    for builtin in BUILTINS {
+        session.declare_extern(backend, builtin.name, builtin.params, builtin.function)?;
+    }
+
+    Ok(())
+}
+
+/// Source code of the prelude, which defines the operators of chapter 6 of the tutorial.
+pub const PRELUDE: &str = include_str!("prelude.ks");
+
+/// Evaluates the prelude with the given backend, and defines its operators in the session.
+pub fn load_prelude(session: &mut Session, backend: &mut dyn Backend) -> Result<(), BackendError> {
+    let functions = syntax::parse(PRELUDE, session.operators()).functions().map_err(BackendError::Compilation)?;
+
+    for function in functions {
+        backend.eval(&function)?;
+        session.define(function).map_err(BackendError::Compilation)?;
+    }
+
+    Ok(())
+}
+
+extern "C" fn sin(x: f64) -> f64 {
+    x.sin()
+}
+
+extern "C" fn cos(x: f64) -> f64 {
+    x.cos()
+}
+
+extern "C" fn sqrt(x: f64) -> f64 {
+    x.sqrt()
+}
+
+extern "C" fn pow(x: f64, y: f64) -> f64 {
+    x.powf(y)
+}
+
+extern "C" fn floor(x: f64) -> f64 {
+    x.floor()
+}
+
+extern "C" fn abs(x: f64) -> f64 {
+    x.abs()
+}
+
+/// State of the xorshift generator used by `random`, seeded on first use (0 if unseeded).
+static RANDOM_STATE: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a pseudo-random number in `[0, 1)`.
+extern "C" fn random() -> f64 {
+    let mut state = RANDOM_STATE.load(Ordering::Relaxed);
+
+    if state == 0 {
+        state = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or(0) | 1;
+    }
+
+    state ^= state << 13;
+    state ^= state >> 7;
+    state ^= state << 17;
+
+    RANDOM_STATE.store(state, Ordering::Relaxed);
+
+    // the 53 high bits give a uniformly distributed double
+    (state >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Returns the number of seconds elapsed since the Unix epoch.
+extern "C" fn clock() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs_f64()).unwrap_or(0.)
+}
+
+/// Reads a number on a line of the standard input, returning NaN if it cannot be parsed
+/// or the end of the input is reached.
+extern "C" fn readd() -> f64 {
+    let mut line = String::new();
+
+    match io::stdin().read_line(&mut line) {
+        Ok(0) | Err(_) => f64::NAN,
+        Ok(_) => line.trim().parse().unwrap_or(f64::NAN)
+    }
+}
+
+extern "C" fn putchard(c: f64) -> f64 {
+    print!("{}", c as u8 as char);
+    io::stdout().flush().expect("Could not flush to standard output.");
+    c
+}
+
+extern "C" fn printd(x: f64) -> f64 {
+    println!("{}", x);
+    x
+}
+
+/// Prints the given number right-aligned on `width` characters with `precision` decimals,
+/// without a new line.
+extern "C" fn printfd(x: f64, width: f64, precision: f64) -> f64 {
+    print!("{:>width$.precision$}", x, width = width.max(0.) as usize, precision = precision.max(0.) as usize);
+    io::stdout().flush().expect("Could not flush to standard output.");
+    x
+}
@@ -0,0 +1,210 @@
+use crate::backend::{self, Backend, BackendError, HostFunction};
+use crate::lexer::{Lexer, Span, Token};
+use crate::operator::{Associativity, OperatorTable};
+use crate::parser::{Expr, Function, Item, Parser};
+use crate::syntax::{self, SyntaxError, SyntaxTree};
+use crate::visit::{self, Visitor};
+
+/// Defines a session, which holds the state shared between successive inputs
+/// (of a REPL, for instance): the operator table and the defined functions.
+pub struct Session {
+    operators: OperatorTable,
+    functions: Vec<Function>
+}
+
+impl Session {
+
+    /// Creates a new session, only knowing the builtin operators.
+    pub fn new() -> Session {
+        Session::with_operators(OperatorTable::new())
+    }
+
+    /// Creates a new session using the given operator table, which may have been
+    /// restored from a previous session.
+    pub fn with_operators(operators: OperatorTable) -> Session {
+        Session {
+            operators,
+            functions: Vec::new()
+        }
+    }
+
+    /// Returns the operator table of the session.
+    pub fn operators(&self) -> &OperatorTable {
+        &self.operators
+    }
+
+    /// Returns the functions defined in the session, in order of definition.
+    pub fn functions(&self) -> &[Function] {
+        self.functions.as_slice()
+    }
+
+    /// Returns the functions defined in the session, ordered so that each function follows the
+    /// functions and operators it uses (unless they use each other), which is an order in which
+    /// they can be defined again. A redefined function keeps its original place in `functions`,
+    /// where it may precede the functions it now uses.
+    pub fn functions_in_dependency_order(&self) -> Vec<&Function> {
+        let mut ordered = Vec::new();
+        let mut visiting = Vec::new();
+
+        for function in &self.functions {
+            self.order_dependencies(function, &mut visiting, &mut ordered);
+        }
+
+        ordered
+    }
+
+    fn order_dependencies<'a>(&'a self, function: &'a Function, visiting: &mut Vec<&'a str>, ordered: &mut Vec<&'a Function>) {
+        let name = function.prototype.name.as_str();
+
+        if visiting.contains(&name) || ordered.iter().any(|fun| fun.prototype.name == name) {
+            return;
+        }
+
+        let mut uses = Uses { names: Vec::new() };
+
+        uses.visit_function(function);
+        visiting.push(name);
+
+        for dependency in uses.names.iter().filter_map(|name| self.get_function(name)) {
+            self.order_dependencies(dependency, visiting, ordered);
+        }
+
+        visiting.pop();
+        ordered.push(function);
+    }
+
+    /// Returns the function with the given name, if it has been defined.
+    pub fn get_function(&self, name: &str) -> Option<&Function> {
+        self.functions.iter().find(|fun| fun.prototype.name == name)
+    }
+
+    /// Parses the given input using the operators of the session.
+    ///
+    /// Operators declared by the input are only usable by later inputs once the
+    /// resulting function has been added to the session with `define`.
+    pub fn parse(&self, input: &str) -> Result<Function, &'static str> {
+        let mut operators = self.operators.clone();
+
+        Parser::new(input.to_string(), &mut operators).parse()
+    }
+
+    /// Parses the given input using the operators of the session, like `parse`, but also
+    /// accepts imports and exported functions.
+    pub fn parse_item(&self, input: &str) -> Result<Item, &'static str> {
+        let mut operators = self.operators.clone();
+
+        Parser::new(input.to_string(), &mut operators).parse_item()
+    }
+
+    /// Returns a value indicating whether the given input is incomplete, and should thus be
+    /// continued on the next line: it has unclosed parentheses or block comments, ends in the
+    /// middle of an item, or ends with a doc comment, which documents the next definition.
+    pub fn is_incomplete(&self, input: &str) -> bool {
+        let mut depth = 0;
+        let mut documented = false;
+
+        for token in Lexer::new(input) {
+            match token {
+                Token::LParen => depth += 1,
+                Token::RParen => depth -= 1,
+                _ => ()
+            }
+
+            documented = matches!(token, Token::DocComment(_));
+        }
+
+        if depth > 0 || documented {
+            return true;
+        }
+
+        // the first error tells whether the input is invalid, or only stops too early
+        match self.parse_syntax(input).errors().first() {
+            Some(SyntaxError { error: "Unterminated block comment.", .. }) => true,
+            Some(SyntaxError { span, .. }) => *span == Span::new(input.len(), input.len()),
+            None => false
+        }
+    }
+
+    /// Registers a host function with the given backend, and declares it as an `extern` in
+    /// both the backend and the session, so that the following inputs can call it. Its
+    /// parameters keep the names of its previous declaration, if any.
+    ///
+    /// Fails if a function with the same name is declared with a different number of
+    /// parameters, in which case nothing is registered.
+    pub fn register_extern(&mut self, backend: &mut dyn Backend, name: &str, function: HostFunction) -> Result<(), BackendError> {
+        let params = match self.get_function(name) {
+            Some(declaration) if declaration.prototype.args.len() == function.arity() => declaration.prototype.args.clone(),
+            _ => [ "x", "y", "z" ].iter().take(function.arity()).map(|param| param.to_string()).collect()
+        };
+        let params = params.iter().map(String::as_str).collect::<Vec<&str>>();
+
+        self.declare_extern(backend, name, params.as_slice(), function)
+    }
+
+    /// Registers a host function like `register_extern`, and declares it with the given
+    /// names of parameters.
+    pub fn declare_extern(&mut self, backend: &mut dyn Backend, name: &str, params: &[&str], function: HostFunction) -> Result<(), BackendError> {
+        let declared = self.get_function(name).filter(|fun| fun.body.is_none()).map(|fun| fun.prototype.args.len());
+
+        backend::check_registration(declared, function.arity()).map_err(BackendError::Compilation)?;
+        backend.register_extern(name, function).map_err(BackendError::Compilation)?;
+
+        let declaration = self.parse(format!("extern {}({})", name, params.join(", ")).as_str()).map_err(BackendError::Compilation)?;
+
+        backend.eval(&declaration)?;
+        self.define(declaration).map_err(BackendError::Compilation)
+    }
+
+    /// Parses the given input into a lossless syntax tree, using the operators of the session.
+    pub fn parse_syntax(&self, input: &str) -> SyntaxTree {
+        syntax::parse(input, &self.operators)
+    }
+
+    /// Adds the given (successfully compiled) function to the session, replacing any
+    /// previous function with the same name, and registers the operator it defines.
+    ///
+    /// Fails if the function redefines a builtin operator, which the `Parser` already rejects.
+    pub fn define(&mut self, function: Function) -> Result<(), &'static str> {
+        if function.is_anon {
+            return Ok(());
+        }
+
+        if let Some(op) = function.prototype.binary_operator() {
+            if self.operators.is_builtin(op) {
+                return Err("Builtin operators cannot be redefined.");
+            }
+
+            self.operators.insert(op, function.prototype.prec as i32, Associativity::Left);
+        }
+
+        match self.functions.iter().position(|fun| fun.prototype.name == function.prototype.name) {
+            Some(index) => self.functions[index] = function,
+            None => self.functions.push(function)
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new()
+    }
+}
+
+/// Collects the names of the functions called by a function, including its binary operators.
+struct Uses {
+    names: Vec<String>
+}
+
+impl Visitor for Uses {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Call { func_name, .. } => self.names.push(func_name.clone()),
+            Expr::Binary { op, .. } => self.names.push(format!("binary{}", op)),
+            _ => ()
+        }
+
+        visit::walk_expr(self, expr);
+    }
+}
@@ -0,0 +1,1181 @@
+//! Lossless concrete syntax tree (CST) of the Kaleidoscope language.
+//!
+//! Unlike the `Expr` AST, which forgets about whitespace and comments, the functions of this
+//! module build a tree that keeps every byte of the input: the text of the source can always be
+//! rebuilt from the tree, even if it contains syntax errors. This makes it suitable for tools such
+//! as formatters, while the AST is derived from it by the `Parser` and `SyntaxTree::functions`.
+//!
+//! The tree is split in two layers, in the fashion of `rowan`: a "green" tree which owns the nodes
+//! and tokens, and a "red" layer (`SyntaxNode`, `SyntaxToken`) which is a cheap view over the green
+//! tree that also knows the absolute position of each element.
+
+use std::fmt;
+use crate::lexer::{Lexer, Span, Token};
+use crate::operator::{Associativity, OperatorTable, DEFAULT_PRECEDENCE};
+use crate::parser::{Expr, Function, Import, Item, Prototype};
+use crate::ANONYMOUS_FUNCTION_NAME;
+
+/// Defines the kind of a node or token of the syntax tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    // Tokens
+    Whitespace,
+    Comment,
+    DocComment,
+    BinaryKw,
+    DefKw,
+    ElseKw,
+    ExportKw,
+    ExternKw,
+    ForKw,
+    IfKw,
+    ImportKw,
+    InKw,
+    ThenKw,
+    UnaryKw,
+    VarKw,
+    Comma,
+    Ident,
+    LParen,
+    Number,
+    Op,
+    RParen,
+    String,
+
+    // Nodes
+    Source,
+    Definition,
+    ExternDecl,
+    ImportDecl,
+    TopLevelExpr,
+    Prototype,
+    ParamList,
+    NumberExpr,
+    VariableExpr,
+    CallExpr,
+    ArgList,
+    ParenExpr,
+    UnaryExpr,
+    BinaryExpr,
+    IfExpr,
+    ForExpr,
+    VarExpr,
+    VarBinding,
+
+    // Either a node or a token that could not be parsed
+    Error
+}
+
+impl SyntaxKind {
+    /// Returns a value indicating whether tokens of this kind are ignored by the parser.
+    pub fn is_trivia(self) -> bool {
+        matches!(self, SyntaxKind::Whitespace | SyntaxKind::Comment | SyntaxKind::DocComment)
+    }
+
+    /// Returns a value indicating whether nodes of this kind represent an expression.
+    pub fn is_expr(self) -> bool {
+        matches!(self,
+            SyntaxKind::NumberExpr | SyntaxKind::VariableExpr | SyntaxKind::CallExpr | SyntaxKind::ParenExpr |
+            SyntaxKind::UnaryExpr | SyntaxKind::BinaryExpr | SyntaxKind::IfExpr | SyntaxKind::ForExpr |
+            SyntaxKind::VarExpr)
+    }
+
+    /// Returns the kind of token corresponding to the given `Token`.
+    fn from_token(token: &Token) -> SyntaxKind {
+        match token {
+            Token::Binary => SyntaxKind::BinaryKw,
+            Token::Comma => SyntaxKind::Comma,
+            Token::Comment(_) => SyntaxKind::Comment,
+            Token::Def => SyntaxKind::DefKw,
+            Token::DocComment(_) => SyntaxKind::DocComment,
+            Token::Else => SyntaxKind::ElseKw,
+            Token::EOF => SyntaxKind::Error,
+            Token::Export => SyntaxKind::ExportKw,
+            Token::Extern => SyntaxKind::ExternKw,
+            Token::For => SyntaxKind::ForKw,
+            Token::Ident(_) => SyntaxKind::Ident,
+            Token::If => SyntaxKind::IfKw,
+            Token::Import => SyntaxKind::ImportKw,
+            Token::In => SyntaxKind::InKw,
+            Token::LParen => SyntaxKind::LParen,
+            Token::Number(_) => SyntaxKind::Number,
+            Token::Op(_) => SyntaxKind::Op,
+            Token::RParen => SyntaxKind::RParen,
+            Token::Str(_) => SyntaxKind::String,
+            Token::Then => SyntaxKind::ThenKw,
+            Token::Unary => SyntaxKind::UnaryKw,
+            Token::Var => SyntaxKind::VarKw,
+            Token::Whitespace(_) => SyntaxKind::Whitespace
+        }
+    }
+}
+
+// ======================================================================================
+// GREEN TREE ===========================================================================
+// ======================================================================================
+
+/// Defines a token of the green tree, which owns its text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GreenToken {
+    kind: SyntaxKind,
+    text: String
+}
+
+/// Defines a node of the green tree, which owns its children but does not know its position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    len: usize,
+    children: Vec<GreenElement>
+}
+
+/// Defines an element of the green tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GreenElement {
+    Node(GreenNode),
+    Token(GreenToken)
+}
+
+impl GreenNode {
+    fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> GreenNode {
+        let len = children.iter().map(GreenElement::len).sum();
+
+        GreenNode { kind, len, children }
+    }
+}
+
+impl GreenElement {
+    fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.len,
+            GreenElement::Token(token) => token.text.len()
+        }
+    }
+}
+
+/// Builds a green tree from a sequence of node starts, tokens and node ends.
+struct TreeBuilder {
+    parents: Vec<(SyntaxKind, Vec<GreenElement>)>
+}
+
+impl TreeBuilder {
+    fn new() -> TreeBuilder {
+        TreeBuilder { parents: Vec::new() }
+    }
+
+    /// Returns the number of nodes that are currently open.
+    fn depth(&self) -> usize {
+        self.parents.len()
+    }
+
+    /// Returns a checkpoint, which can be used to later wrap the elements added after it in a node.
+    fn checkpoint(&self) -> usize {
+        self.parents.last().map_or(0, |(_, children)| children.len())
+    }
+
+    fn start_node(&mut self, kind: SyntaxKind) {
+        self.parents.push((kind, Vec::new()));
+    }
+
+    /// Starts a node which contains all the elements added since the given checkpoint.
+    fn start_node_at(&mut self, checkpoint: usize, kind: SyntaxKind) {
+        let children = match self.parents.last_mut() {
+            Some((_, children)) => children.split_off(checkpoint),
+            None => Vec::new()
+        };
+
+        self.parents.push((kind, children));
+    }
+
+    fn token(&mut self, kind: SyntaxKind, text: &str) {
+        if let Some((_, children)) = self.parents.last_mut() {
+            children.push(GreenElement::Token(GreenToken { kind, text: text.to_string() }));
+        }
+    }
+
+    fn finish_node(&mut self) {
+        let (kind, children) = self.parents.pop().expect("No node to finish.");
+        let node = GreenNode::new(kind, children);
+
+        match self.parents.last_mut() {
+            Some((_, parent)) => parent.push(GreenElement::Node(node)),
+            None => panic!("Cannot finish the root node before building the tree.")
+        }
+    }
+
+    /// Finishes all the nodes opened after the given depth.
+    fn finish_to(&mut self, depth: usize) {
+        while self.parents.len() > depth {
+            self.finish_node();
+        }
+    }
+
+    fn finish(mut self) -> GreenNode {
+        let (kind, children) = self.parents.pop().expect("Empty syntax tree.");
+
+        GreenNode::new(kind, children)
+    }
+}
+
+// ======================================================================================
+// RED TREE =============================================================================
+// ======================================================================================
+
+/// Defines a node of the syntax tree, along with its position in the source code.
+#[derive(Debug, Clone, Copy)]
+pub struct SyntaxNode<'a> {
+    green: &'a GreenNode,
+    offset: usize
+}
+
+/// Defines a token of the syntax tree, along with its position in the source code.
+#[derive(Debug, Clone, Copy)]
+pub struct SyntaxToken<'a> {
+    green: &'a GreenToken,
+    offset: usize
+}
+
+/// Defines an element of the syntax tree.
+#[derive(Debug, Clone, Copy)]
+pub enum SyntaxElement<'a> {
+    Node(SyntaxNode<'a>),
+    Token(SyntaxToken<'a>)
+}
+
+impl<'a> SyntaxNode<'a> {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.offset, self.offset + self.green.len)
+    }
+
+    /// Returns the source code covered by this node, including trivia.
+    pub fn text(&self) -> String {
+        self.to_string()
+    }
+
+    /// Returns the direct children (both nodes and tokens) of this node.
+    pub fn children_with_tokens(&self) -> impl Iterator<Item = SyntaxElement<'a>> {
+        let mut offset = self.offset;
+
+        self.green.children.iter().map(move |child| {
+            let element = match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode { green, offset }),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken { green, offset })
+            };
+
+            offset += child.len();
+            element
+        })
+    }
+
+    /// Returns the direct children nodes of this node.
+    pub fn children(&self) -> impl Iterator<Item = SyntaxNode<'a>> {
+        self.children_with_tokens().filter_map(SyntaxElement::into_node)
+    }
+
+    /// Returns the direct children tokens of this node.
+    pub fn child_tokens(&self) -> impl Iterator<Item = SyntaxToken<'a>> {
+        self.children_with_tokens().filter_map(SyntaxElement::into_token)
+    }
+
+    /// Returns the direct children nodes of this node which represent expressions.
+    pub fn child_exprs(&self) -> impl Iterator<Item = SyntaxNode<'a>> {
+        self.children().filter(|child| child.kind().is_expr())
+    }
+
+    /// Returns the first direct child node of the given kind.
+    pub fn child_node(&self, kind: SyntaxKind) -> Option<SyntaxNode<'a>> {
+        self.children().find(|child| child.kind() == kind)
+    }
+
+    /// Returns the first direct child token of the given kind.
+    pub fn child_token(&self, kind: SyntaxKind) -> Option<SyntaxToken<'a>> {
+        self.child_tokens().find(|child| child.kind() == kind)
+    }
+
+    /// Returns all the tokens contained in this node, in source order.
+    pub fn descendant_tokens(&self) -> Vec<SyntaxToken<'a>> {
+        let mut tokens = Vec::new();
+
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.descendant_tokens()),
+                SyntaxElement::Token(token) => tokens.push(token)
+            }
+        }
+
+        tokens
+    }
+
+    /// Returns an indented representation of the tree, for debugging purposes.
+    pub fn debug_dump(&self) -> String {
+        fn dump(node: &SyntaxNode, indent: usize, out: &mut String) {
+            out.push_str(&format!("{:indent$}{:?}@{}..{}\n", "", node.kind(), node.span().start, node.span().end, indent = indent));
+
+            for child in node.children_with_tokens() {
+                match child {
+                    SyntaxElement::Node(node) => dump(&node, indent + 2, out),
+                    SyntaxElement::Token(token) => out.push_str(&format!(
+                        "{:indent$}{:?}@{}..{} {:?}\n", "", token.kind(), token.span().start, token.span().end, token.text(),
+                        indent = indent + 2))
+                }
+            }
+        }
+
+        let mut out = String::new();
+
+        dump(self, 0, &mut out);
+        out
+    }
+}
+
+impl<'a> fmt::Display for SyntaxNode<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) => write!(f, "{}", node)?,
+                SyntaxElement::Token(token) => f.write_str(token.text())?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> SyntaxToken<'a> {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.offset, self.offset + self.green.text.len())
+    }
+
+    pub fn text(&self) -> &'a str {
+        self.green.text.as_str()
+    }
+}
+
+impl<'a> SyntaxElement<'a> {
+    pub fn kind(&self) -> SyntaxKind {
+        match self {
+            SyntaxElement::Node(node) => node.kind(),
+            SyntaxElement::Token(token) => token.kind()
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            SyntaxElement::Node(node) => node.span(),
+            SyntaxElement::Token(token) => token.span()
+        }
+    }
+
+    pub fn into_node(self) -> Option<SyntaxNode<'a>> {
+        match self {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None
+        }
+    }
+
+    pub fn into_token(self) -> Option<SyntaxToken<'a>> {
+        match self {
+            SyntaxElement::Node(_) => None,
+            SyntaxElement::Token(token) => Some(token)
+        }
+    }
+}
+
+// ======================================================================================
+// PARSING ==============================================================================
+// ======================================================================================
+
+/// Defines an error encountered while building the syntax tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub error: &'static str,
+    pub span: Span
+}
+
+/// Defines a lossless syntax tree, along with the errors encountered while building it.
+#[derive(Debug, Clone)]
+pub struct SyntaxTree {
+    root: GreenNode,
+    errors: Vec<SyntaxError>
+}
+
+impl SyntaxTree {
+    /// Returns the root node of the tree, whose kind is `SyntaxKind::Source`.
+    pub fn root(&self) -> SyntaxNode<'_> {
+        SyntaxNode { green: &self.root, offset: 0 }
+    }
+
+    /// Returns the syntax errors encountered while parsing the source code.
+    pub fn errors(&self) -> &[SyntaxError] {
+        self.errors.as_slice()
+    }
+
+    /// Returns the source code represented by the tree.
+    pub fn text(&self) -> String {
+        self.root().text()
+    }
+
+    /// Derives the `Item`s of the source code from the tree, failing if the tree contains
+    /// any error.
+    pub fn items(&self) -> Result<Vec<Item>, &'static str> {
+        if let Some(err) = self.errors.first() {
+            return Err(err.error);
+        }
+
+        self.root().children().map(lower_item).collect()
+    }
+
+    /// Derives the `Function`s defined by the source code from the tree, failing if the tree
+    /// contains any error or import.
+    pub fn functions(&self) -> Result<Vec<Function>, &'static str> {
+        self.items()?
+            .into_iter()
+            .map(|item| match item {
+                Item::Function { function, .. } => Ok(function),
+                Item::Import(_) => Err("Imports are only supported when loading modules.")
+            })
+            .collect()
+    }
+}
+
+type ParseResult = Result<(), SyntaxError>;
+
+/// Parses the given source code into a lossless syntax tree, using the given operators.
+///
+/// Operators defined in the source code are taken into account for the rest of the source code,
+/// but are not added to the given table.
+pub fn parse(input: &str, operators: &OperatorTable) -> SyntaxTree {
+    let (tokens, lexer_error) = lex(input);
+    let mut parser = SyntaxParser {
+        source: input,
+        tokens,
+        pos: 0,
+        builder: TreeBuilder::new(),
+        operators: operators.clone(),
+        errors: Vec::new()
+    };
+
+    parser.parse_source();
+
+    if let Some(lexer_error) = lexer_error {
+        // the errors found at the invalid token are caused by the lexer error
+        parser.errors.retain(|err| err.span.start < lexer_error.span.start);
+        parser.errors.push(lexer_error);
+    }
+
+    SyntaxTree {
+        root: parser.builder.finish(),
+        errors: parser.errors
+    }
+}
+
+/// Lexes the given source code into tokens covering every byte of it, along with the lexer
+/// error which ended the input, if any.
+fn lex(input: &str) -> (Vec<(SyntaxKind, Span)>, Option<SyntaxError>) {
+    let mut lexer = Lexer::lossless(input);
+    let mut tokens = Vec::new();
+
+    loop {
+        match lexer.lex_spanned() {
+            Ok((Token::EOF, _)) => break,
+            Ok((token, span)) => tokens.push((SyntaxKind::from_token(&token), span)),
+            Err(err) => {
+                let span = Span::new(err.index, input.len());
+
+                // keep the rest of the input as an invalid token
+                tokens.push((SyntaxKind::Error, span));
+
+                return (tokens, Some(SyntaxError { error: err.error, span }));
+            }
+        }
+    }
+
+    (tokens, None)
+}
+
+/// Represents the parser building the syntax tree, which defines the grammar of the language.
+struct SyntaxParser<'a> {
+    source: &'a str,
+    tokens: Vec<(SyntaxKind, Span)>,
+    pos: usize,
+    builder: TreeBuilder,
+    operators: OperatorTable,
+    errors: Vec<SyntaxError>
+}
+
+impl<'a> SyntaxParser<'a> {
+
+    /// Returns the index of the next non-trivia token.
+    fn next_index(&self) -> usize {
+        let mut index = self.pos;
+
+        while index < self.tokens.len() && self.tokens[index].0.is_trivia() {
+            index += 1;
+        }
+
+        index
+    }
+
+    /// Returns the kind of the current (non-trivia) token, or `None` at the end of the input.
+    fn current(&self) -> Option<SyntaxKind> {
+        self.tokens.get(self.next_index()).map(|&(kind, _)| kind)
+    }
+
+    /// Returns the kind of the (non-trivia) token following the current one.
+    fn peek(&self) -> Option<SyntaxKind> {
+        let mut index = self.next_index() + 1;
+
+        while index < self.tokens.len() && self.tokens[index].0.is_trivia() {
+            index += 1;
+        }
+
+        self.tokens.get(index).map(|&(kind, _)| kind)
+    }
+
+    /// Returns the text of the current (non-trivia) token.
+    fn current_text(&self) -> &'a str {
+        match self.tokens.get(self.next_index()) {
+            Some(&(_, span)) => &self.source[span.start..span.end],
+            None => ""
+        }
+    }
+
+    /// Returns the operator character of the current token, if it is an operator.
+    fn current_op(&self) -> Option<char> {
+        match self.current() {
+            Some(SyntaxKind::Op) => self.current_text().chars().next(),
+            _ => None
+        }
+    }
+
+    /// Returns the precedence of the current token, or -1 if it is not an operator.
+    fn token_precedence(&self) -> i32 {
+        match self.current_op() {
+            Some(op) => self.operators.precedence(op).unwrap_or(DEFAULT_PRECEDENCE),
+            None => -1
+        }
+    }
+
+    /// Adds the trivia tokens preceding the next non-trivia token to the current node.
+    fn flush_trivia(&mut self) {
+        self.flush_trivia_to(self.next_index());
+    }
+
+    fn flush_trivia_to(&mut self, end: usize) {
+        while self.pos < end {
+            let (kind, span) = self.tokens[self.pos];
+
+            self.builder.token(kind, &self.source[span.start..span.end]);
+            self.pos += 1;
+        }
+    }
+
+    /// Adds the trivia preceding an item to the current node, except for the doc comments
+    /// which directly precede it, so that they end up in the node of the item.
+    fn flush_leading_trivia(&mut self) {
+        let end = self.next_index();
+        let mut start = end;
+
+        for index in (self.pos..end).rev() {
+            match self.tokens[index].0 {
+                SyntaxKind::DocComment => start = index,
+                SyntaxKind::Whitespace => (),
+                _ => break
+            }
+        }
+
+        self.flush_trivia_to(start);
+    }
+
+    /// Adds the current token (and the trivia preceding it) to the current node.
+    fn bump(&mut self) {
+        self.flush_trivia();
+
+        if self.pos < self.tokens.len() {
+            let (kind, span) = self.tokens[self.pos];
+
+            self.builder.token(kind, &self.source[span.start..span.end]);
+            self.pos += 1;
+        }
+    }
+
+    /// Bumps the current token if it has the given kind, and returns an error otherwise.
+    fn expect(&mut self, kind: SyntaxKind, error: &'static str) -> ParseResult {
+        if self.current() == Some(kind) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(self.error(error))
+        }
+    }
+
+    /// Returns an error located at the current token.
+    fn error(&self, error: &'static str) -> SyntaxError {
+        let span = match self.tokens.get(self.next_index()) {
+            Some(&(_, span)) => span,
+            None => Span::new(self.source.len(), self.source.len())
+        };
+
+        SyntaxError { error, span }
+    }
+
+    fn start_node(&mut self, kind: SyntaxKind) {
+        self.flush_trivia();
+        self.builder.start_node(kind);
+    }
+
+    fn checkpoint(&mut self) -> usize {
+        self.flush_trivia();
+        self.builder.checkpoint()
+    }
+
+    /// Parses the whole source code; the root node is finished by `TreeBuilder::finish`.
+    fn parse_source(&mut self) {
+        self.builder.start_node(SyntaxKind::Source);
+
+        while self.current().is_some() {
+            self.parse_item();
+        }
+
+        self.flush_trivia();
+    }
+
+    /// Parses an import, a definition, an extern declaration or a top-level expression,
+    /// recovering from errors by skipping tokens until the next item.
+    fn parse_item(&mut self) {
+        let depth = self.builder.depth();
+
+        // definitions and extern declarations may be preceded by 'export'
+        let keyword = match self.current() {
+            Some(SyntaxKind::ExportKw) => self.peek(),
+            keyword => keyword
+        };
+
+        let result = match keyword {
+            Some(SyntaxKind::ImportKw) => {
+                self.start_node(SyntaxKind::ImportDecl);
+                self.parse_import()
+            },
+            Some(SyntaxKind::DefKw) => {
+                self.flush_leading_trivia();
+                self.builder.start_node(SyntaxKind::Definition);
+                self.parse_definition()
+            },
+            Some(SyntaxKind::ExternKw) => {
+                self.flush_leading_trivia();
+                self.builder.start_node(SyntaxKind::ExternDecl);
+                self.parse_extern()
+            },
+            _ if self.current() == Some(SyntaxKind::ExportKw) => {
+                self.start_node(SyntaxKind::Definition);
+                self.bump();
+                Err(self.error("Expected 'def' or 'extern' after 'export'."))
+            },
+            _ => {
+                self.start_node(SyntaxKind::TopLevelExpr);
+                self.parse_expr()
+            }
+        };
+
+        if let Err(err) = result {
+            self.errors.push(err);
+            self.builder.finish_to(depth);
+            self.start_node(SyntaxKind::Error);
+
+            while let Some(kind) = self.current() {
+                if matches!(kind, SyntaxKind::DefKw | SyntaxKind::ExternKw | SyntaxKind::ExportKw | SyntaxKind::ImportKw) {
+                    break;
+                }
+
+                self.bump();
+            }
+        }
+
+        self.builder.finish_node();
+    }
+
+    fn parse_import(&mut self) -> ParseResult {
+        self.bump();
+
+        match self.current() {
+            Some(SyntaxKind::String) | Some(SyntaxKind::Ident) => {
+                self.bump();
+                Ok(())
+            },
+            _ => Err(self.error("Expected module name or path after 'import'."))
+        }
+    }
+
+    fn parse_definition(&mut self) -> ParseResult {
+        self.parse_export();
+        self.bump();
+        self.parse_prototype()?;
+        self.parse_expr()
+    }
+
+    fn parse_extern(&mut self) -> ParseResult {
+        self.parse_export();
+        self.bump();
+        self.parse_prototype()
+    }
+
+    /// Bumps the 'export' keyword preceding a definition or an extern declaration, if any.
+    fn parse_export(&mut self) {
+        if self.current() == Some(SyntaxKind::ExportKw) {
+            self.bump();
+        }
+    }
+
+    fn parse_prototype(&mut self) -> ParseResult {
+        self.start_node(SyntaxKind::Prototype);
+
+        match self.current() {
+            Some(SyntaxKind::Ident) => self.bump(),
+
+            Some(SyntaxKind::BinaryKw) => {
+                self.bump();
+
+                let op = match self.current_op() {
+                    Some(op) => op,
+                    None => return Err(self.error("Expected operator in custom operator declaration."))
+                };
+
+                if self.operators.is_builtin(op) {
+                    return Err(self.error("Builtin operators cannot be redefined."));
+                }
+
+                self.bump();
+
+                let prec = if self.current() == Some(SyntaxKind::Number) {
+                    let prec = self.current_text().parse::<f64>().unwrap_or(0.) as usize;
+
+                    self.bump();
+                    prec
+                } else {
+                    0
+                };
+
+                self.operators.insert(op, prec as i32, Associativity::Left);
+            },
+
+            Some(SyntaxKind::UnaryKw) => {
+                self.bump();
+
+                if self.current_op().is_none() {
+                    return Err(self.error("Expected operator in custom operator declaration."));
+                }
+
+                self.bump();
+            },
+
+            _ => return Err(self.error("Expected identifier in prototype declaration."))
+        }
+
+        self.start_node(SyntaxKind::ParamList);
+        self.expect(SyntaxKind::LParen, "Expected '(' character in prototype declaration.")?;
+
+        if self.current() == Some(SyntaxKind::RParen) {
+            self.bump();
+        } else {
+            loop {
+                self.expect(SyntaxKind::Ident, "Expected identifier in parameter declaration.")?;
+
+                match self.current() {
+                    Some(SyntaxKind::RParen) => {
+                        self.bump();
+                        break;
+                    },
+                    Some(SyntaxKind::Comma) => self.bump(),
+                    _ => return Err(self.error("Expected ',' or ')' character in prototype declaration."))
+                }
+            }
+        }
+
+        // finish parameter list and prototype
+        self.builder.finish_node();
+        self.builder.finish_node();
+
+        Ok(())
+    }
+
+    fn parse_expr(&mut self) -> ParseResult {
+        let checkpoint = self.checkpoint();
+
+        self.parse_unary_expr()?;
+        self.parse_binary_expr(0, checkpoint)
+    }
+
+    fn parse_unary_expr(&mut self) -> ParseResult {
+        if self.current() != Some(SyntaxKind::Op) {
+            return self.parse_primary();
+        }
+
+        self.start_node(SyntaxKind::UnaryExpr);
+        self.bump();
+        self.parse_unary_expr()?;
+        self.builder.finish_node();
+
+        Ok(())
+    }
+
+    /// Parses the binary operations following the expression added after the given checkpoint.
+    fn parse_binary_expr(&mut self, prec: i32, checkpoint: usize) -> ParseResult {
+        loop {
+            let curr_prec = self.token_precedence();
+
+            if curr_prec < prec {
+                return Ok(());
+            }
+
+            let op = self.current_op().unwrap_or_default();
+
+            self.builder.start_node_at(checkpoint, SyntaxKind::BinaryExpr);
+            self.bump();
+
+            let right_checkpoint = self.checkpoint();
+
+            self.parse_unary_expr()?;
+
+            let next_prec = self.token_precedence();
+
+            // the right operand of a right-associative operator also takes the following
+            // operators of the same precedence, even after an operator of higher precedence
+            if self.operators.associativity(op) == Associativity::Right && curr_prec <= next_prec {
+                self.parse_binary_expr(curr_prec, right_checkpoint)?;
+            } else if curr_prec < next_prec {
+                self.parse_binary_expr(curr_prec + 1, right_checkpoint)?;
+            }
+
+            self.builder.finish_node();
+        }
+    }
+
+    fn parse_primary(&mut self) -> ParseResult {
+        match self.current() {
+            Some(SyntaxKind::Ident) if self.peek() == Some(SyntaxKind::LParen) => self.parse_call_expr(),
+            Some(SyntaxKind::Ident) => self.parse_single_token_expr(SyntaxKind::VariableExpr),
+            Some(SyntaxKind::Number) => self.parse_single_token_expr(SyntaxKind::NumberExpr),
+            Some(SyntaxKind::LParen) => self.parse_paren_expr(),
+            Some(SyntaxKind::IfKw) => self.parse_conditional_expr(),
+            Some(SyntaxKind::ForKw) => self.parse_for_expr(),
+            Some(SyntaxKind::VarKw) => self.parse_var_expr(),
+            _ => Err(self.error("Unknown expression."))
+        }
+    }
+
+    fn parse_single_token_expr(&mut self, kind: SyntaxKind) -> ParseResult {
+        self.start_node(kind);
+        self.bump();
+        self.builder.finish_node();
+
+        Ok(())
+    }
+
+    fn parse_call_expr(&mut self) -> ParseResult {
+        self.start_node(SyntaxKind::CallExpr);
+        self.bump();
+        self.start_node(SyntaxKind::ArgList);
+        self.bump();
+
+        if self.current() == Some(SyntaxKind::RParen) {
+            self.bump();
+        } else {
+            loop {
+                self.parse_expr()?;
+
+                match self.current() {
+                    Some(SyntaxKind::Comma) => self.bump(),
+                    Some(SyntaxKind::RParen) => {
+                        self.bump();
+                        break;
+                    },
+                    _ => return Err(self.error("Expected ',' character in function call."))
+                }
+            }
+        }
+
+        // finish argument list and call
+        self.builder.finish_node();
+        self.builder.finish_node();
+
+        Ok(())
+    }
+
+    fn parse_paren_expr(&mut self) -> ParseResult {
+        self.start_node(SyntaxKind::ParenExpr);
+        self.bump();
+        self.parse_expr()?;
+        self.expect(SyntaxKind::RParen, "Expected ')' character at end of parenthesized expression.")?;
+        self.builder.finish_node();
+
+        Ok(())
+    }
+
+    fn parse_conditional_expr(&mut self) -> ParseResult {
+        self.start_node(SyntaxKind::IfExpr);
+        self.bump();
+        self.parse_expr()?;
+        self.expect(SyntaxKind::ThenKw, "Expected 'then' keyword.")?;
+        self.parse_expr()?;
+        self.expect(SyntaxKind::ElseKw, "Expected 'else' keyword.")?;
+        self.parse_expr()?;
+        self.builder.finish_node();
+
+        Ok(())
+    }
+
+    fn parse_for_expr(&mut self) -> ParseResult {
+        self.start_node(SyntaxKind::ForExpr);
+        self.bump();
+        self.expect(SyntaxKind::Ident, "Expected identifier in for loop.")?;
+
+        if self.current_op() != Some('=') {
+            return Err(self.error("Expected '=' character in for loop."));
+        }
+
+        self.bump();
+        self.parse_expr()?;
+        self.expect(SyntaxKind::Comma, "Expected ',' character in for loop.")?;
+        self.parse_expr()?;
+
+        if self.current() == Some(SyntaxKind::Comma) {
+            self.bump();
+            self.parse_expr()?;
+        }
+
+        self.expect(SyntaxKind::InKw, "Expected 'in' keyword in for loop.")?;
+        self.parse_expr()?;
+        self.builder.finish_node();
+
+        Ok(())
+    }
+
+    fn parse_var_expr(&mut self) -> ParseResult {
+        self.start_node(SyntaxKind::VarExpr);
+        self.bump();
+
+        loop {
+            self.start_node(SyntaxKind::VarBinding);
+            self.expect(SyntaxKind::Ident, "Expected identifier in 'var..in' declaration.")?;
+
+            if self.current_op() == Some('=') {
+                self.bump();
+                self.parse_expr()?;
+            }
+
+            self.builder.finish_node();
+
+            match self.current() {
+                Some(SyntaxKind::Comma) => self.bump(),
+                Some(SyntaxKind::InKw) => {
+                    self.bump();
+                    break;
+                },
+                _ => return Err(self.error("Expected comma or 'in' keyword in variable declaration."))
+            }
+        }
+
+        self.parse_expr()?;
+        self.builder.finish_node();
+
+        Ok(())
+    }
+}
+
+// ======================================================================================
+// LOWERING =============================================================================
+// ======================================================================================
+
+/// Derives an `Item` from an `ImportDecl`, `Definition`, `ExternDecl` or `TopLevelExpr` node.
+pub(crate) fn lower_item(node: SyntaxNode) -> Result<Item, &'static str> {
+    if node.kind() != SyntaxKind::ImportDecl {
+        return Ok(Item::Function {
+            function: lower_function(node)?,
+            exported: node.child_token(SyntaxKind::ExportKw).is_some()
+        });
+    }
+
+    let module = node.child_tokens()
+        .find(|token| token.kind() == SyntaxKind::String || token.kind() == SyntaxKind::Ident)
+        .ok_or("Expected module name or path after 'import'.")?;
+
+    match module.kind() {
+        SyntaxKind::String => Ok(Item::Import(Import::Path(module.text().trim_matches('"').to_string()))),
+        _ => Ok(Item::Import(Import::Module(module.text().to_string())))
+    }
+}
+
+/// Derives a `Function` from a `Definition`, `ExternDecl` or `TopLevelExpr` node.
+fn lower_function(node: SyntaxNode) -> Result<Function, &'static str> {
+    match node.kind() {
+        SyntaxKind::Definition | SyntaxKind::ExternDecl => {
+            let prototype = node.child_node(SyntaxKind::Prototype).ok_or("Expected prototype.")?;
+            let body = match node.child_exprs().next() {
+                Some(body) => Some(lower_expr(body)?),
+                None => None
+            };
+
+            // only the doc comments preceding the definition document it
+            let doc = node.child_tokens()
+                .take_while(|token| token.kind().is_trivia())
+                .filter(|token| token.kind() == SyntaxKind::DocComment)
+                .map(|token| {
+                    let doc = token.text().trim_start_matches('#');
+
+                    doc.strip_prefix(' ').unwrap_or(doc).trim_end().to_string()
+                })
+                .collect::<Vec<String>>();
+
+            Ok(Function {
+                prototype: lower_prototype(prototype)?,
+                body,
+                is_anon: false,
+                doc: if doc.is_empty() { None } else { Some(doc.join("\n")) },
+                locations: None
+            })
+        },
+
+        SyntaxKind::TopLevelExpr => {
+            let body = node.child_exprs().next().ok_or("Expected expression.")?;
+
+            Ok(Function {
+                prototype: Prototype {
+                    name: ANONYMOUS_FUNCTION_NAME.to_string(),
+                    args: vec![],
+                    is_op: false,
+                    prec: 0
+                },
+                body: Some(lower_expr(body)?),
+                is_anon: true,
+                doc: None,
+                locations: None
+            })
+        },
+
+        _ => Err("Invalid syntax.")
+    }
+}
+
+fn lower_prototype(node: SyntaxNode) -> Result<Prototype, &'static str> {
+    let mut tokens = node.child_tokens().filter(|token| !token.kind().is_trivia());
+
+    let (name, is_op, prec) = match tokens.next().map(|token| (token.kind(), token.text())) {
+        Some((SyntaxKind::Ident, name)) => (name.to_string(), false, 0),
+        Some((SyntaxKind::BinaryKw, _)) => {
+            let op = tokens.next().ok_or("Expected operator in custom operator declaration.")?;
+            let prec = match tokens.next() {
+                Some(prec) => prec.text().parse::<f64>().map_err(|_| "Invalid number literal.")? as usize,
+                None => 0
+            };
+
+            (format!("binary{}", op.text()), true, prec)
+        },
+        Some((SyntaxKind::UnaryKw, _)) => {
+            let op = tokens.next().ok_or("Expected operator in custom operator declaration.")?;
+
+            (format!("unary{}", op.text()), true, 0)
+        },
+        _ => return Err("Expected identifier in prototype declaration.")
+    };
+
+    let args = node.child_node(SyntaxKind::ParamList)
+        .ok_or("Expected '(' character in prototype declaration.")?
+        .child_tokens()
+        .filter(|token| token.kind() == SyntaxKind::Ident)
+        .map(|token| token.text().to_string())
+        .collect();
+
+    Ok(Prototype { name, args, is_op, prec })
+}
+
+fn lower_expr(node: SyntaxNode) -> Result<Expr, &'static str> {
+    let op_text = || node.child_token(SyntaxKind::Op).map(|token| token.text()).ok_or("Invalid operator.");
+    let ident_text = || node.child_token(SyntaxKind::Ident).map(|token| token.text().to_string()).ok_or("Expected identifier.");
+    let mut exprs = node.child_exprs();
+    let mut next_expr = || exprs.next().ok_or("Expected expression.").and_then(lower_expr);
+
+    match node.kind() {
+        SyntaxKind::NumberExpr => {
+            let number = node.child_token(SyntaxKind::Number).ok_or("Expected number literal.")?;
+
+            number.text().parse().map(Expr::Number).map_err(|_| "Invalid number literal.")
+        },
+
+        SyntaxKind::VariableExpr => Ok(Expr::Variable(ident_text()?)),
+
+        SyntaxKind::CallExpr => {
+            let args = node.child_node(SyntaxKind::ArgList)
+                .ok_or("Expected '(' character in function call.")?
+                .child_exprs()
+                .map(lower_expr)
+                .collect::<Result<Vec<Expr>, &'static str>>()?;
+
+            Ok(Expr::Call { func_name: ident_text()?, args })
+        },
+
+        SyntaxKind::ParenExpr => next_expr(),
+
+        SyntaxKind::UnaryExpr => Ok(Expr::Call {
+            func_name: format!("unary{}", op_text()?),
+            args: vec![ next_expr()? ]
+        }),
+
+        SyntaxKind::BinaryExpr => Ok(Expr::Binary {
+            op: op_text()?.chars().next().ok_or("Invalid operator.")?,
+            left: Box::new(next_expr()?),
+            right: Box::new(next_expr()?)
+        }),
+
+        SyntaxKind::IfExpr => Ok(Expr::Conditional {
+            cond: Box::new(next_expr()?),
+            consequence: Box::new(next_expr()?),
+            alternative: Box::new(next_expr()?)
+        }),
+
+        SyntaxKind::ForExpr => {
+            let mut exprs = node.child_exprs().map(lower_expr).collect::<Result<Vec<Expr>, &'static str>>()?;
+
+            let body = exprs.pop().ok_or("Expected expression.")?;
+            let step = if exprs.len() == 3 { exprs.pop() } else { None };
+            let end = exprs.pop().ok_or("Expected expression.")?;
+            let start = exprs.pop().ok_or("Expected expression.")?;
+
+            Ok(Expr::For {
+                var_name: ident_text()?,
+                start: Box::new(start),
+                end: Box::new(end),
+                step: step.map(Box::new),
+                body: Box::new(body)
+            })
+        },
+
+        SyntaxKind::VarExpr => {
+            let mut variables = Vec::new();
+
+            for binding in node.children().filter(|child| child.kind() == SyntaxKind::VarBinding) {
+                let name = binding.child_token(SyntaxKind::Ident).ok_or("Expected identifier.")?;
+                let initializer = match binding.child_exprs().next() {
+                    Some(init) => Some(lower_expr(init)?),
+                    None => None
+                };
+
+                variables.push((name.text().to_string(), initializer));
+            }
+
+            Ok(Expr::VarIn {
+                variables,
+                body: Box::new(next_expr()?)
+            })
+        },
+
+        _ => Err("Expected expression.")
+    }
+}
@@ -0,0 +1,225 @@
+//! Traversal of the AST.
+//!
+//! `Visitor` and `VisitorMut` walk a tree by reference, while `Fold` consumes a tree and
+//! rebuilds it. Each method of these traits defaults to the corresponding `walk_*` (or `fold_*`)
+//! function, which recurses into the children of the node; implementations override the methods
+//! they are interested in, and call the walk function themselves to keep recursing.
+//!
+//! Children are always walked in the order in which they appear in the source code, which is not
+//! the order in which they are evaluated: the body of a `for` loop is visited after its end
+//! condition and step, but evaluated before them.
+
+use crate::parser::{Expr, Function, Prototype};
+
+// ======================================================================================
+// VISITOR ==============================================================================
+// ======================================================================================
+
+/// Defines a visitor of an immutable AST.
+pub trait Visitor {
+    fn visit_function(&mut self, fun: &Function) {
+        walk_function(self, fun)
+    }
+
+    fn visit_prototype(&mut self, _proto: &Prototype) {}
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr)
+    }
+}
+
+/// Visits the prototype and the body of the given function.
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, fun: &Function) {
+    visitor.visit_prototype(&fun.prototype);
+
+    if let Some(body) = &fun.body {
+        visitor.visit_expr(body);
+    }
+}
+
+/// Visits the children of the given expression, in source order.
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Binary { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        },
+
+        Expr::Call { args, .. } => {
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        },
+
+        Expr::Conditional { cond, consequence, alternative } => {
+            visitor.visit_expr(cond);
+            visitor.visit_expr(consequence);
+            visitor.visit_expr(alternative);
+        },
+
+        Expr::For { start, end, step, body, .. } => {
+            visitor.visit_expr(start);
+            visitor.visit_expr(end);
+
+            if let Some(step) = step {
+                visitor.visit_expr(step);
+            }
+
+            visitor.visit_expr(body);
+        },
+
+        Expr::Number(_) | Expr::Variable(_) => (),
+
+        Expr::VarIn { variables, body } => {
+            for (_, initializer) in variables {
+                if let Some(init) = initializer {
+                    visitor.visit_expr(init);
+                }
+            }
+
+            visitor.visit_expr(body);
+        }
+    }
+}
+
+// ======================================================================================
+// MUTABLE VISITOR ======================================================================
+// ======================================================================================
+
+/// Defines a visitor which may modify the AST in place.
+pub trait VisitorMut {
+    fn visit_function_mut(&mut self, fun: &mut Function) {
+        walk_function_mut(self, fun)
+    }
+
+    fn visit_prototype_mut(&mut self, _proto: &mut Prototype) {}
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr)
+    }
+}
+
+/// Visits the prototype and the body of the given function.
+pub fn walk_function_mut<V: VisitorMut + ?Sized>(visitor: &mut V, fun: &mut Function) {
+    visitor.visit_prototype_mut(&mut fun.prototype);
+
+    if let Some(body) = &mut fun.body {
+        visitor.visit_expr_mut(body);
+    }
+}
+
+/// Visits the children of the given expression, in source order.
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Binary { left, right, .. } => {
+            visitor.visit_expr_mut(left);
+            visitor.visit_expr_mut(right);
+        },
+
+        Expr::Call { args, .. } => {
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        },
+
+        Expr::Conditional { cond, consequence, alternative } => {
+            visitor.visit_expr_mut(cond);
+            visitor.visit_expr_mut(consequence);
+            visitor.visit_expr_mut(alternative);
+        },
+
+        Expr::For { start, end, step, body, .. } => {
+            visitor.visit_expr_mut(start);
+            visitor.visit_expr_mut(end);
+
+            if let Some(step) = step {
+                visitor.visit_expr_mut(step);
+            }
+
+            visitor.visit_expr_mut(body);
+        },
+
+        Expr::Number(_) | Expr::Variable(_) => (),
+
+        Expr::VarIn { variables, body } => {
+            for (_, initializer) in variables {
+                if let Some(init) = initializer {
+                    visitor.visit_expr_mut(init);
+                }
+            }
+
+            visitor.visit_expr_mut(body);
+        }
+    }
+}
+
+// ======================================================================================
+// FOLD =================================================================================
+// ======================================================================================
+
+/// Defines a transformation which consumes an AST and returns a new one.
+pub trait Fold {
+    fn fold_function(&mut self, fun: Function) -> Function {
+        fold_function(self, fun)
+    }
+
+    fn fold_prototype(&mut self, proto: Prototype) -> Prototype {
+        proto
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        fold_expr(self, expr)
+    }
+}
+
+/// Folds the prototype and the body of the given function.
+pub fn fold_function<F: Fold + ?Sized>(folder: &mut F, fun: Function) -> Function {
+    Function {
+        prototype: folder.fold_prototype(fun.prototype),
+        body: fun.body.map(|body| folder.fold_expr(body)),
+        ..fun
+    }
+}
+
+/// Folds the children of the given expression, in source order, and rebuilds it.
+pub fn fold_expr<F: Fold + ?Sized>(folder: &mut F, expr: Expr) -> Expr {
+    let mut fold_box = |expr: Box<Expr>| Box::new(folder.fold_expr(*expr));
+
+    match expr {
+        Expr::Binary { op, left, right } => {
+            let left = fold_box(left);
+
+            Expr::Binary { op, left, right: fold_box(right) }
+        },
+
+        Expr::Call { func_name, args } => Expr::Call {
+            func_name,
+            args: args.into_iter().map(|arg| folder.fold_expr(arg)).collect()
+        },
+
+        Expr::Conditional { cond, consequence, alternative } => {
+            let cond = fold_box(cond);
+            let consequence = fold_box(consequence);
+
+            Expr::Conditional { cond, consequence, alternative: fold_box(alternative) }
+        },
+
+        Expr::For { var_name, start, end, step, body } => {
+            let start = fold_box(start);
+            let end = fold_box(end);
+            let step = step.map(&mut fold_box);
+
+            Expr::For { var_name, start, end, step, body: fold_box(body) }
+        },
+
+        Expr::Number(_) | Expr::Variable(_) => expr,
+
+        Expr::VarIn { variables, body } => {
+            let variables = variables.into_iter()
+                .map(|(name, initializer)| (name, initializer.map(|init| folder.fold_expr(init))))
+                .collect();
+
+            Expr::VarIn { variables, body: Box::new(folder.fold_expr(*body)) }
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use kaleidoscope::completion::complete;
+
+fn functions() -> Vec<String> {
+    [ "fib", "fibonacci", "binary|", "unary!", "math.helper" ].iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn keywords_and_functions_are_completed() {
+    assert_eq!(complete("fi", &functions()), (0, vec![ "fib".to_string(), "fibonacci".to_string() ]));
+    assert_eq!(complete("1 + ex", &functions()), (4, vec![ "export".to_string(), "extern".to_string() ]));
+    assert_eq!(complete("bin", &functions()), (0, vec![ "binary".to_string(), "binary|".to_string() ]));
+    assert_eq!(complete("u", &functions()), (0, vec![ "unary".to_string(), "unary!".to_string() ]));
+    assert_eq!(complete("(fib(1) + fibo", &functions()), (10, vec![ "fibonacci".to_string() ]));
+    assert_eq!(complete("ma", &functions()), (0, Vec::<String>::new()));
+}
+
+#[test]
+fn parameters_are_completed_in_definitions() {
+    assert_eq!(complete("def f(first, second) fi", &functions()), (21, vec![ "fib".to_string(), "fibonacci".to_string(), "first".to_string() ]));
+    assert_eq!(complete("def binary% 5 (lhs, rhs)\n  l", &functions()), (27, vec![ "lhs".to_string() ]));
+    assert_eq!(complete("def f(first, fi", &functions()).1, vec![ "fib".to_string(), "fibonacci".to_string() ]);
+    assert_eq!(complete("f(first) fi", &functions()).1, vec![ "fib".to_string(), "fibonacci".to_string() ]);
+}
+
+#[test]
+fn local_variables_are_completed_in_definitions() {
+    let candidates = |input: &str| complete(input, &[]).1;
+
+    assert_eq!(candidates("def f(x) var acc = 0, add = g(1, 2), a in for at = 0, at < x in a"), [ "a", "acc", "add", "at" ]);
+    assert_eq!(candidates("def f(x)\n  for i = 0, i < x, var inc = 1 in inc in\n    i"), [ "i", "if", "import", "in", "inc" ]);
+
+    // variables of previous definitions are not completed
+    assert_eq!(candidates("def f(x) var total = x in total\ndef g(y) to"), Vec::<String>::new());
+}
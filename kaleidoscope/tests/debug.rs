@@ -0,0 +1,127 @@
+//! Emission of debug information and inspection of the compiled code by the LLVM backend.
+
+#![cfg(feature = "llvm")]
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use inkwell::OptimizationLevel;
+use inkwell::context::Context;
+use kaleidoscope::backend::Backend;
+use kaleidoscope::gdb;
+use kaleidoscope::jit::Jit;
+use kaleidoscope::optimizer::{self, OptimizeOptions};
+use kaleidoscope::session::Session;
+
+// objects must not be (un)registered while they are listed
+static JIT: Mutex<()> = Mutex::new(());
+
+/// Returns the number of registered object files which define or call a function named `traced`.
+extern "C" fn registered(_: f64) -> f64 {
+    gdb::registered_objects().iter().filter(|object| object.windows(6).any(|name| name == b"traced")).count() as f64
+}
+
+#[test]
+fn functions_are_compiled_with_debug_info() {
+    let _lock = JIT.lock().unwrap();
+    let context = Context::create();
+    let mut jit = Jit::new(&context, OptimizationLevel::None);
+    let mut session = Session::new();
+
+    jit.enable_debug_info("test.ks");
+
+    let def = session.parse("def f(x)\n  var a = x in\n    for i = 0, i < 3 in\n      a = a * 2").unwrap();
+
+    assert_eq!(jit.eval(&def), Ok(None));
+    assert!(jit.ir().unwrap().contains("!dbg"));
+    session.define(def).unwrap();
+
+    // functions without locations are compiled without debug information
+    let expr = session.parse("f(1) + 1").unwrap();
+    let optimized = optimizer::optimize(expr.clone(), session.functions(), &OptimizeOptions::default());
+
+    assert_eq!(optimized.locations, None);
+    assert_eq!(jit.eval(&optimized), Ok(Some(1.)));
+    assert_eq!(jit.eval(&expr), Ok(Some(1.)));
+}
+
+#[test]
+fn optimized_functions_without_debug_info_are_reported() {
+    let run = |args: &[&str], input: &str| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_kaleido"))
+            .args(args)
+            .arg("--no-prelude")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Cannot run kaleido.");
+
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+
+        String::from_utf8(child.wait_with_output().unwrap().stderr).unwrap()
+    };
+    let warning = "Debug information is not emitted for functions optimized by the AST optimizer.";
+
+    assert!(run(&[ "-g", "--opt" ], "").contains(warning));
+    assert!(run(&[ "-g" ], ":opt\n").contains(warning));
+    assert!(!run(&[ "-g" ], "").contains(warning));
+    assert!(!run(&[ "--opt" ], "").contains(warning));
+}
+
+#[test]
+fn compiled_code_is_registered_with_debuggers() {
+    let _lock = JIT.lock().unwrap();
+    let context = Context::create();
+    let mut jit = Jit::new(&context, OptimizationLevel::None);
+    let mut session = Session::new();
+
+    jit.enable_debug_info("test.ks");
+    jit.add_global_mapping("registered", registered as extern "C" fn(f64) -> f64 as usize);
+
+    for input in &[ "extern registered(x)", "def traced(x) registered(x)" ] {
+        let fun = session.parse(input).unwrap();
+
+        assert_eq!(jit.eval(&fun), Ok(None));
+        session.define(fun).unwrap();
+    }
+
+    // the objects of the stub and the body of the function stay registered, while the one of
+    // the expression calling it is only registered while it is executed
+    assert_eq!(jit.eval(&session.parse("traced(0)").unwrap()), Ok(Some(3.)));
+    assert_eq!(registered(0.), 2.);
+
+    // redefining the function unregisters the object of its previous body
+    let redefinition = session.parse("def traced(x) registered(x) + 1").unwrap();
+
+    assert_eq!(jit.eval(&redefinition), Ok(None));
+    session.define(redefinition).unwrap();
+
+    assert_eq!(registered(0.), 1.);
+    assert_eq!(jit.eval(&session.parse("traced(0)").unwrap()), Ok(Some(4.)));
+    assert_eq!(registered(0.), 2.);
+}
+
+#[test]
+fn module_ir_and_assembly() {
+    let _lock = JIT.lock().unwrap();
+    let context = Context::create();
+    let mut jit = Jit::new(&context, OptimizationLevel::Default);
+    let mut session = Session::new();
+
+    for input in [ "extern sin(x)", "def twice(x) sin(x) * 2" ].iter() {
+        let function = session.parse(input).unwrap();
+
+        assert_eq!(jit.eval(&function), Ok(None));
+        session.define(function).unwrap();
+    }
+
+    let ir = jit.module_ir().unwrap();
+
+    assert!(ir.contains("declare double @sin(double)"));
+    assert!(ir.contains("define double @twice(double %x)"));
+
+    assert!(jit.asm("twice").unwrap().contains("twice:"));
+    assert_eq!(jit.asm("sin"), Err("External functions have no assembly."));
+    assert_eq!(jit.asm("cos"), Err("Unknown function."));
+}
@@ -0,0 +1,144 @@
+//! Runs every program of `tests/programs` with each backend and optimization level, and
+//! checks that they all produce the output stored in the corresponding `.out` file.
+//!
+//! Run with `BLESS=1` to (re)generate the `.out` files from the interpreter.
+
+use std::cell::RefCell;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use kaleidoscope::backend::{Backend, HostFunction};
+use kaleidoscope::interp::Interpreter;
+use kaleidoscope::operator::OperatorTable;
+use kaleidoscope::optimizer::{self, OptimizeOptions};
+use kaleidoscope::parser::Function;
+use kaleidoscope::syntax;
+
+thread_local! {
+    static OUTPUT: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+extern "C" fn putchard(x: f64) -> f64 {
+    OUTPUT.with(|output| output.borrow_mut().push(x as u8 as char));
+    x
+}
+
+extern "C" fn printd(x: f64) -> f64 {
+    OUTPUT.with(|output| output.borrow_mut().push_str(format!("{}\n", x).as_str()));
+    x
+}
+
+/// Returns the optimization levels of the AST optimizer the programs are run with.
+fn optimization_levels() -> Vec<(&'static str, Option<OptimizeOptions>)> {
+    vec![
+        ("none", None),
+        ("ast", Some(OptimizeOptions::default())),
+        ("ast+unroll", Some(OptimizeOptions { unroll_limit: 16, ..OptimizeOptions::default() }))
+    ]
+}
+
+/// Runs the given program, returning its output followed by the result of each top-level
+/// expression (or the error it produced), in the format of the REPL.
+fn run(source: &str, backend: &mut dyn Backend, options: Option<&OptimizeOptions>) -> String {
+    let functions = syntax::parse(source, &OperatorTable::new()).functions().expect("Cannot parse program.");
+    let mut defined: Vec<Function> = Vec::new();
+    let mut transcript = String::new();
+
+    OUTPUT.with(|output| output.borrow_mut().clear());
+
+    for function in functions {
+        let compiled = match options {
+            Some(options) => optimizer::optimize(function.clone(), defined.as_slice(), options),
+            None => function.clone()
+        };
+
+        let result = backend.eval(&compiled);
+
+        OUTPUT.with(|output| transcript.push_str(output.borrow_mut().drain(..).as_str()));
+
+        match result {
+            Ok(Some(result)) => transcript.push_str(format!("=> {}\n", result).as_str()),
+            Ok(None) => defined.push(function),
+            Err(err) => transcript.push_str(format!("!> {}\n", err).as_str())
+        }
+    }
+
+    transcript
+}
+
+fn register_builtins(backend: &mut dyn Backend) {
+    backend.register_extern("putchard", HostFunction::Args1(putchard)).unwrap();
+    backend.register_extern("printd", HostFunction::Args1(printd)).unwrap();
+}
+
+fn interpreter() -> Interpreter {
+    let mut interpreter = Interpreter::new();
+
+    register_builtins(&mut interpreter);
+    interpreter
+}
+
+/// Runs the given program with every backend and optimization level, returning the name of
+/// each configuration along with the transcript it produced.
+fn run_all(source: &str) -> Vec<(String, String)> {
+    let mut transcripts = Vec::new();
+
+    for (level, options) in optimization_levels() {
+        transcripts.push((format!("interp/{}", level), run(source, &mut interpreter(), options.as_ref())));
+
+        #[cfg(feature = "llvm")]
+        {
+            use inkwell::OptimizationLevel;
+            use inkwell::context::Context;
+            use kaleidoscope::jit::Jit;
+
+            for (llvm_level, optimization) in [("O0", OptimizationLevel::None), ("O2", OptimizationLevel::Default)].iter() {
+                let context = Context::create();
+                let mut jit = Jit::new(&context, *optimization);
+
+                register_builtins(&mut jit);
+
+                transcripts.push((format!("llvm-{}/{}", llvm_level, level), run(source, &mut jit, options.as_ref())));
+            }
+        }
+    }
+
+    transcripts
+}
+
+fn programs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("programs");
+    let mut programs = fs::read_dir(dir).expect("Cannot read the programs directory.")
+        .map(|entry| entry.expect("Cannot read the programs directory.").path())
+        .filter(|path| path.extension() == Some(OsStr::new("ks")))
+        .collect::<Vec<PathBuf>>();
+
+    programs.sort();
+    programs
+}
+
+#[test]
+fn backends_agree_on_all_programs() {
+    let bless = std::env::var_os("BLESS").is_some();
+    let mut failures = Vec::new();
+
+    for program in programs() {
+        let source = fs::read_to_string(&program).expect("Cannot read program.");
+        let expected_path = program.with_extension("out");
+        let transcripts = run_all(source.as_str());
+
+        if bless {
+            fs::write(&expected_path, transcripts[0].1.as_str()).expect("Cannot write expected output.");
+        }
+
+        let expected = fs::read_to_string(&expected_path).unwrap_or_else(|_| panic!("Missing expected output {}.", expected_path.display()));
+
+        for (config, transcript) in transcripts {
+            if transcript != expected {
+                failures.push(format!("{} ({}):\n--- expected\n{}--- actual\n{}", program.display(), config, expected, transcript));
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "Backends disagree with the expected output:\n\n{}", failures.join("\n"));
+}
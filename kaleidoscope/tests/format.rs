@@ -0,0 +1,116 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use kaleidoscope::format::{self, FormatOptions};
+use kaleidoscope::lexer::Span;
+use kaleidoscope::operator::OperatorTable;
+use kaleidoscope::syntax::SyntaxError;
+
+fn format_with_width(input: &str, width: usize) -> String {
+    format::format(input, &OperatorTable::new(), &FormatOptions { width, ..FormatOptions::default() }).expect("Cannot format test input.")
+}
+
+fn format(input: &str) -> String {
+    format_with_width(input, FormatOptions::default().width)
+}
+
+#[test]
+fn spacing_is_normalized() {
+    assert_eq!(
+        format("def   f(x,y)   if x<y then x+1 else   y*2\nextern   sin(  a )\n\n\n\nf(1,2)"),
+        "def f(x, y) if x < y then x + 1 else y * 2\nextern sin(a)\n\nf(1, 2)\n"
+    );
+}
+
+#[test]
+fn comments_are_preserved() {
+    let input = "# leading\ndef g(x) # after proto\n  x + 1 #[ block ]# * 2\n## doc\ndef h() a = a + 1 # trailing\n";
+
+    assert_eq!(
+        format(input),
+        "# leading\ndef g(x)\n    # after proto\n    x + 1 #[ block ]# * 2\n## doc\ndef h() a = a + 1 # trailing\n"
+    );
+}
+
+#[test]
+fn long_lines_are_wrapped() {
+    assert_eq!(
+        format_with_width("def long(alpha, beta, gamma) compute(alpha + beta, beta * gamma, gamma - alpha, 1234)", 30),
+        "def long(alpha, beta, gamma)\n    compute(\n        alpha + beta,\n        beta * gamma,\n        gamma - alpha,\n        1234\n    )\n"
+    );
+    assert_eq!(
+        format_with_width("def f(x) if x < 10 then something(x) else otherwise(x, x)", 30),
+        "def f(x)\n    if x < 10 then\n        something(x)\n    else\n        otherwise(x, x)\n"
+    );
+    assert_eq!(
+        format_with_width("def h() var a = 1, b in for i = 0, i < 10 in a = a + i", 30),
+        "def h()\n    var a = 1, b in\n        for i = 0, i < 10 in\n            a = a + i\n"
+    );
+
+    // the same code fits on a single line with the default width
+    assert_eq!(format("def h() var a = 1, b in for i = 0, i < 10 in a = a + i"), "def h() var a = 1, b in for i = 0, i < 10 in a = a + i\n");
+}
+
+#[test]
+fn formatting_is_idempotent() {
+    let programs = fs::read_dir("tests/programs").expect("Cannot read programs.")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension() == Some(OsStr::new("ks")))
+        .map(|path| fs::read_to_string(path).unwrap());
+
+    let inputs = [
+        "def   f(x,y)   if x<y then x+1 else   y*2\nextern   sin(  a )\n\n\n\nf(1,2)",
+        "# leading\ndef g(x) # after proto\n  x + 1 #[ block ]# * 2\n## doc\ndef h() var a = 1, b in for i = 0, i < 10 in a = a + i # trailing\n",
+        "def sum(n) first_value + second_value * third_value - n"
+    ];
+
+    for input in programs.chain(inputs.iter().map(|input| input.to_string())) {
+        for width in [ 20, 40, 80 ].iter() {
+            let formatted = format_with_width(input.as_str(), *width);
+
+            assert_eq!(format_with_width(formatted.as_str(), *width), formatted, "{}", input);
+        }
+    }
+}
+
+#[test]
+fn syntax_errors_are_reported() {
+    assert_eq!(
+        format::format("def f(x x", &OperatorTable::new(), &FormatOptions::default()),
+        Err(SyntaxError { error: "Expected ',' or ')' character in prototype declaration.", span: Span::new(8, 9) })
+    );
+}
+
+#[test]
+fn check_mode_exit_status() {
+    let dir = std::env::temp_dir().join(format!("kaleidoscope-fmt-{}", std::process::id()));
+    let file = |name: &str, source: &str| -> PathBuf {
+        let path = dir.join(name);
+
+        fs::write(&path, source).expect("Cannot write test file.");
+        path
+    };
+
+    fs::create_dir_all(&dir).expect("Cannot create test directory.");
+
+    let formatted = file("formatted.ks", "def f(x) x + 1\n");
+    let unformatted = file("unformatted.ks", "def f(x)   x+1");
+    let invalid = file("invalid.ks", "def f(x x");
+
+    let fmt = |args: &[&OsStr]| Command::new(env!("CARGO_BIN_EXE_kaleido")).arg("fmt").args(args).output().expect("Cannot run kaleido.");
+
+    let output = fmt(&[ OsStr::new("--check"), formatted.as_os_str(), unformatted.as_os_str() ]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), format!("{} is not formatted.\n", unformatted.display()));
+
+    assert_eq!(fmt(&[ OsStr::new("--check"), formatted.as_os_str() ]).status.code(), Some(0));
+    assert_eq!(fmt(&[ OsStr::new("--check"), invalid.as_os_str() ]).status.code(), Some(1));
+
+    // checked files are left untouched, while other files are formatted in place
+    assert_eq!(fs::read_to_string(&unformatted).unwrap(), "def f(x)   x+1");
+    assert_eq!(fmt(&[ unformatted.as_os_str() ]).status.code(), Some(0));
+    assert_eq!(fs::read_to_string(&unformatted).unwrap(), "def f(x) x + 1\n");
+    assert_eq!(fmt(&[ OsStr::new("--check"), unformatted.as_os_str() ]).status.code(), Some(0));
+}
@@ -0,0 +1,138 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use kaleidoscope::backend::{Backend, BackendError, HostFunction};
+use kaleidoscope::interp::Interpreter;
+use kaleidoscope::session::Session;
+
+/// Evaluates the given inputs in order, returning the result of the last one.
+fn eval(interpreter: &mut Interpreter, inputs: &[&str]) -> Result<Option<f64>, BackendError> {
+    let mut session = Session::new();
+    let mut result = Ok(None);
+
+    for input in inputs {
+        let function = session.parse(input).expect("Cannot parse test input.");
+
+        result = interpreter.eval(&function);
+
+        if result.is_ok() {
+            session.define(function).unwrap();
+        }
+    }
+
+    result
+}
+
+#[test]
+fn functions_and_operators() {
+    let mut interpreter = Interpreter::new();
+    let inputs = [
+        "def fib(n) if n < 2 then n else fib(n - 1) + fib(n - 2)",
+        "def binary| 5 (a, b) if a then 1 else if b then 1 else 0",
+        "def unary!(v) if v then 0 else 1",
+        "fib(10) + (!0 | 0) * 1000"
+    ];
+
+    assert_eq!(eval(&mut interpreter, &inputs), Ok(Some(1055.)));
+}
+
+#[test]
+fn variables_are_scoped() {
+    let mut interpreter = Interpreter::new();
+
+    assert_eq!(eval(&mut interpreter, &["def f(x) (var x = x + 1, x = x * 2 in x) + x", "f(3)"]), Ok(Some(11.)));
+    assert_eq!(eval(&mut interpreter, &["def f(x) var y = 0 in (for x = 0, x < 3 in y = y + x) + x + y", "f(10)"]), Ok(Some(16.)));
+}
+
+#[test]
+fn comparisons_with_nan() {
+    let mut interpreter = Interpreter::new();
+
+    assert_eq!(eval(&mut interpreter, &["def nan() 0 / 0", "(nan() < 1) + (1 > nan()) * 2"]), Ok(Some(3.)));
+    assert_eq!(eval(&mut interpreter, &["def nan() 0 / 0", "if nan() then 1 else 2"]), Ok(Some(2.)));
+}
+
+#[test]
+fn externs_call_host_functions() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::new();
+    let captured = Rc::clone(&output);
+
+    interpreter.register_closure("printd", move |args| {
+        captured.borrow_mut().push(args[0]);
+        args[0]
+    });
+
+    let inputs = ["extern printd(x)", "for i = 1, i < 3 in printd(i)"];
+
+    assert_eq!(eval(&mut interpreter, &inputs), Ok(Some(0.)));
+    assert_eq!(*output.borrow(), [1., 2., 3.]);
+    assert_eq!(eval(&mut interpreter, &["extern sin(x)", "sin(1)"]), Err(BackendError::Execution("Unknown external function.")));
+}
+
+extern "C" fn hypot(x: f64, y: f64) -> f64 {
+    x.hypot(y)
+}
+
+extern "C" fn abs(x: f64) -> f64 {
+    x.abs()
+}
+
+#[test]
+fn registered_host_functions_are_checked() {
+    let mut interpreter = Interpreter::new();
+
+    assert_eq!(interpreter.register_extern("hypot", HostFunction::Args2(hypot)), Ok(()));
+
+    assert_eq!(eval(&mut interpreter, &["extern hypot(x)"]), Err(BackendError::Compilation("Incorrect number of parameters for host function.")));
+    assert_eq!(eval(&mut interpreter, &["extern hypot(x, y)", "hypot(3, 4)"]), Ok(Some(5.)));
+
+    // host functions are also checked against the declarations evaluated before them
+    assert_eq!(eval(&mut interpreter, &["extern norm(x)"]), Ok(None));
+    assert_eq!(interpreter.register_extern("norm", HostFunction::Args2(hypot)), Err("Incorrect number of parameters for host function."));
+    assert_eq!(eval(&mut interpreter, &["norm(3)"]), Err(BackendError::Execution("Unknown external function.")));
+}
+
+#[test]
+fn host_functions_are_registered_with_the_session() {
+    let mut interpreter = Interpreter::new();
+    let mut session = Session::new();
+
+    assert_eq!(session.register_extern(&mut interpreter, "hypot", HostFunction::Args2(hypot)), Ok(()));
+    assert_eq!(session.get_function("hypot").map(|fun| fun.prototype.args.clone()), Some(vec![ "x".to_string(), "y".to_string() ]));
+    assert_eq!(interpreter.eval(&session.parse("hypot(3, 4)").unwrap()), Ok(Some(5.)));
+
+    // the parameters of a previous declaration are kept
+    let declaration = session.parse("extern length(dx, dy)").unwrap();
+
+    session.define(declaration).unwrap();
+
+    assert_eq!(session.register_extern(&mut interpreter, "length", HostFunction::Args2(hypot)), Ok(()));
+    assert_eq!(session.get_function("length").map(|fun| fun.prototype.args.clone()), Some(vec![ "dx".to_string(), "dy".to_string() ]));
+
+    // a declaration with a different number of parameters cannot be replaced
+    assert_eq!(
+        session.register_extern(&mut interpreter, "length", HostFunction::Args1(abs)),
+        Err(BackendError::Compilation("Incorrect number of parameters for host function."))
+    );
+    assert_eq!(interpreter.eval(&session.parse("length(6, 8)").unwrap()), Ok(Some(10.)));
+}
+
+#[test]
+fn no_code_is_produced() {
+    let mut interpreter = Interpreter::new();
+
+    assert_eq!(eval(&mut interpreter, &["def f(x) x + 1"]), Ok(None));
+    assert_eq!(interpreter.module_ir(), None);
+    assert_eq!(interpreter.asm("f"), Err("Native code is not produced by this backend."));
+}
+
+#[test]
+fn errors_are_reported_at_definition() {
+    let mut interpreter = Interpreter::new();
+
+    assert_eq!(eval(&mut interpreter, &["def f(x) if x then x else y"]), Err(BackendError::Compilation("Could not find a matching variable.")));
+    assert_eq!(eval(&mut interpreter, &["def f(x) if x then x else g(x)"]), Err(BackendError::Compilation("Unknown function.")));
+    assert_eq!(eval(&mut interpreter, &["def f(x) x % 2"]), Err(BackendError::Compilation("Undefined binary operator.")));
+    assert_eq!(eval(&mut interpreter, &["def f(x) 1 = x"]), Err(BackendError::Compilation("Expected variable as left-hand operator of assignement.")));
+    assert_eq!(eval(&mut interpreter, &["def f(x) f(x, x)"]), Err(BackendError::Compilation("Incorrect number of arguments passed.")));
+}
@@ -0,0 +1,140 @@
+use kaleidoscope::lexer::{Lexer, Span, Token};
+use kaleidoscope::operator::OperatorTable;
+use kaleidoscope::parser::{Item, Parser};
+
+fn ident(name: &str) -> Token {
+    Token::Ident(name.to_string())
+}
+
+#[test]
+fn keywords_identifiers_and_numbers() {
+    let tokens = Lexer::new("def f(x, y2) if x < 1.5 then y2 else var _a in for").collect::<Vec<Token>>();
+
+    assert_eq!(tokens, [
+        Token::Def, ident("f"), Token::LParen, ident("x"), Token::Comma, ident("y2"), Token::RParen,
+        Token::If, ident("x"), Token::Op('<'), Token::Number(1.5), Token::Then, ident("y2"),
+        Token::Else, Token::Var, ident("_a"), Token::In, Token::For
+    ]);
+}
+
+#[test]
+fn tokens_at_end_of_input() {
+    assert_eq!(Lexer::new("x").collect::<Vec<Token>>(), [ ident("x") ]);
+    assert_eq!(Lexer::new("42").collect::<Vec<Token>>(), [ Token::Number(42.) ]);
+    assert_eq!(Lexer::new("extern").collect::<Vec<Token>>(), [ Token::Extern ]);
+    assert_eq!(Lexer::new("").collect::<Vec<Token>>(), []);
+}
+
+#[test]
+fn comments_are_skipped_unless_preserved() {
+    let input = "# line\nx #[ block #[ nested ]# ]# + 1 # end";
+
+    assert_eq!(Lexer::new(input).collect::<Vec<Token>>(), [ ident("x"), Token::Op('+'), Token::Number(1.) ]);
+    assert_eq!(Lexer::with_comments(input).collect::<Vec<Token>>(), [
+        Token::Comment("# line".to_string()),
+        ident("x"),
+        Token::Comment("#[ block #[ nested ]# ]#".to_string()),
+        Token::Op('+'),
+        Token::Number(1.),
+        Token::Comment("# end".to_string())
+    ]);
+}
+
+#[test]
+fn nested_block_comments() {
+    let input = "#[ a #[ b #[ c ]# ]# # not a line comment\n ]# x #[]# y";
+
+    assert_eq!(Lexer::new(input).collect::<Vec<Token>>(), [ ident("x"), ident("y") ]);
+    assert_eq!(Lexer::with_comments(input).collect::<Vec<Token>>(), [
+        Token::Comment("#[ a #[ b #[ c ]# ]# # not a line comment\n ]#".to_string()),
+        ident("x"),
+        Token::Comment("#[]#".to_string()),
+        ident("y")
+    ]);
+
+    // a block comment can be commented out by a line comment
+    assert_eq!(Lexer::new("# #[\nx").collect::<Vec<Token>>(), [ ident("x") ]);
+}
+
+#[test]
+fn doc_comments() {
+    let tokens = Lexer::new("## Adds two numbers.  \n##\ndef add(a, b) a + b").collect::<Vec<Token>>();
+
+    assert_eq!(tokens[0], Token::DocComment("Adds two numbers.".to_string()));
+    assert_eq!(tokens[1], Token::DocComment(String::new()));
+    assert_eq!(tokens[2], Token::Def);
+}
+
+#[test]
+fn doc_comments_are_attached_to_definitions() {
+    let parse = |input: &str| match Parser::new(input.to_string(), &mut OperatorTable::new()).parse_item() {
+        Ok(Item::Function { function, .. }) => function.doc,
+        result => panic!("Unexpected result: {:?}", result)
+    };
+
+    assert_eq!(parse("## Sine,\n## in radians.\nextern sin(x)").as_deref(), Some("Sine,\nin radians."));
+    assert_eq!(parse("# Not documentation.\n## Doubles.\nexport def double(x) x * 2").as_deref(), Some("Doubles."));
+
+    // doc comments which do not precede a definition are dropped
+    assert_eq!(parse("## Not attached.\n1 + 2"), None);
+    assert_eq!(parse("def f(x)\n  ## Not attached.\n  x"), None);
+}
+
+#[test]
+fn unterminated_block_comment() {
+    let mut lexer = Lexer::new("x #[ #[ ]#");
+
+    assert_eq!(lexer.lexer().ok(), Some(ident("x")));
+
+    let err = lexer.lexer().expect_err("Expected an error.");
+
+    assert_eq!(err.error, "Unterminated block comment.");
+    assert_eq!(err.index, 2);
+
+    // the error is reported by the parser, with the location of the comment
+    let input = "def f(x)\n  #[ x ]";
+    let mut lexer = Lexer::new(input);
+    let err = loop {
+        if let Err(err) = lexer.lexer() {
+            break err;
+        }
+    };
+
+    assert_eq!(Span::new(err.index, err.index).location(input), (2, 3));
+    assert_eq!(Parser::new("def f(x) #[ x ]".to_string(), &mut OperatorTable::new()).parse(), Err("Unterminated block comment."));
+}
+
+#[test]
+fn imports_and_string_literals() {
+    assert_eq!(Lexer::new("import \"lib/a b.ks\" export def").collect::<Vec<Token>>(), [
+        Token::Import, Token::Str("lib/a b.ks".to_string()), Token::Export, Token::Def
+    ]);
+
+    let mut lexer = Lexer::new("import \"lib");
+
+    assert_eq!(lexer.lexer().ok(), Some(Token::Import));
+
+    let err = lexer.lexer().expect_err("Expected an error.");
+
+    assert_eq!(err.error, "Unterminated string literal.");
+    assert_eq!(err.index, 7);
+}
+
+#[test]
+fn spans_and_locations() {
+    let input = "def f()\n  é + x";
+    let mut lexer = Lexer::new(input);
+    let mut spans = Vec::new();
+
+    while let Ok((token, span)) = lexer.lex_spanned() {
+        if token == Token::EOF {
+            break;
+        }
+
+        spans.push(span);
+    }
+
+    assert_eq!(spans.last(), Some(&Span::new(15, 16)));
+    assert_eq!(&input[spans[5].start..spans[5].end], "+");
+    assert_eq!(spans[5].location(input), (2, 5));
+}
@@ -0,0 +1,152 @@
+use std::cell::Cell;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use kaleidoscope::backend::BackendError;
+use kaleidoscope::interp::Interpreter;
+use kaleidoscope::module::{Loader, ModuleError};
+use kaleidoscope::parser::Import;
+use kaleidoscope::session::Session;
+
+/// Creates an empty directory for the modules of a test.
+fn directory(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("kaleidoscope-modules-{}-{}", name, std::process::id()));
+
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("Cannot create test directory.");
+    dir
+}
+
+/// Writes the given modules in the given directory.
+fn write(dir: &Path, modules: &[(&str, &str)]) {
+    for (path, source) in modules {
+        let path = dir.join(path);
+
+        fs::create_dir_all(path.parent().unwrap()).expect("Cannot create test directory.");
+        fs::write(path, source).expect("Cannot write test module.");
+    }
+}
+
+fn load(loader: &mut Loader, path: &Path) -> Result<Vec<f64>, ModuleError> {
+    loader.load(path, &mut Session::new(), &mut Interpreter::new())
+}
+
+#[test]
+fn exported_functions_and_operators_are_imported() {
+    let dir = directory("exports");
+
+    write(&dir, &[
+        ("lib/ops.ks", "def binary : 1 (x, y) y\ndef unary ! (x) if x then 0 else 1"),
+        ("lib/math.ks", "import \"ops.ks\"\ndef helper(x) x * 2\nexport def double(x) helper(x)"),
+        ("main.ks", "import math\n!0\ndef helper(x) x + 100\ndouble(3) : helper(1)\ndouble(1)")
+    ]);
+
+    let mut loader = Loader::new(vec![ dir.join("lib") ]);
+
+    assert_eq!(load(&mut loader, &dir.join("main.ks")), Ok(vec![ 1., 101., 2. ]));
+}
+
+#[test]
+fn private_functions_are_not_visible() {
+    let dir = directory("private");
+
+    write(&dir, &[
+        ("math.ks", "def helper(x) x * 2\nexport def double(x) helper(x)"),
+        ("main.ks", "import math\nhelper(1)")
+    ]);
+
+    let mut loader = Loader::new(Vec::new());
+    let mut session = Session::new();
+    let result = loader.load(&dir.join("main.ks"), &mut session, &mut Interpreter::new());
+
+    assert_eq!(result, Err(ModuleError::Backend(dir.join("main.ks").canonicalize().unwrap(), BackendError::Compilation("Unknown function."))));
+    assert!(session.get_function("math.helper").is_some());
+    assert!(session.get_function("helper").is_none());
+}
+
+#[test]
+fn modules_are_loaded_once() {
+    let dir = directory("once");
+
+    write(&dir, &[
+        ("counter.ks", "extern tick()\ntick()"),
+        ("a.ks", "import counter"),
+        ("main.ks", "import a\nimport counter\nimport \"./counter.ks\"")
+    ]);
+
+    let ticks = Rc::new(Cell::new(0));
+    let mut interpreter = Interpreter::new();
+    let mut loader = Loader::new(Vec::new());
+    let mut session = Session::new();
+
+    let counter = ticks.clone();
+    interpreter.register_closure("tick", move |_| {
+        counter.set(counter.get() + 1);
+        0.
+    });
+
+    assert_eq!(loader.load(&dir.join("main.ks"), &mut session, &mut interpreter), Ok(Vec::new()));
+    assert_eq!(loader.import(&Import::Module("counter".to_string()), Some(&dir), &mut session, &mut interpreter), Ok(()));
+    assert_eq!(ticks.get(), 1);
+}
+
+#[test]
+fn import_errors() {
+    let dir = directory("errors");
+
+    write(&dir, &[
+        ("a.ks", "import b"),
+        ("b.ks", "import \"a.ks\""),
+        ("missing.ks", "import nowhere"),
+        ("invalid.ks", "import b\n\ndef f(x"),
+    ]);
+
+    let mut loader = Loader::new(Vec::new());
+    let a = dir.join("a.ks").canonicalize().unwrap();
+    let b = dir.join("b.ks").canonicalize().unwrap();
+
+    assert_eq!(load(&mut loader, &dir.join("a.ks")), Err(ModuleError::Cycle(vec![ a.clone(), b, a ])));
+    assert_eq!(load(&mut loader, &dir.join("missing.ks")), Err(ModuleError::NotFound("nowhere.ks".to_string())));
+    assert_eq!(load(&mut loader, &dir.join("invalid.ks")), Err(ModuleError::Syntax {
+        path: dir.join("invalid.ks").canonicalize().unwrap(),
+        line: 3,
+        column: 8,
+        error: "Expected ',' or ')' character in prototype declaration."
+    }));
+}
+
+#[test]
+fn saved_sessions_are_loaded_again() {
+    let dir = directory("save");
+    let repl = |input: &str| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_kaleido"))
+            .current_dir(&dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Cannot run kaleido.");
+
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+
+        let output = String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap();
+
+        output.lines().filter(|line| line.starts_with("=>") || line.starts_with("!>")).map(str::to_string).collect::<Vec<String>>()
+    };
+
+    write(&dir, &[ ("lib/math.ks", "def helper(x) x * 2\nexport def double(x) helper(x)") ]);
+
+    // the redefinition of 'g' uses 'h', which was defined after it
+    assert_eq!(repl("import \"lib/math.ks\"\ndef g(x) x + 1\ndef h(x) x * 3\ndef binary% 7 (a, b) g(a) * b\ndef f(x) x % 3\ndef g(x) h(x) + double(x)\n:save saved.ks\n"), Vec::<String>::new());
+
+    let saved = fs::read_to_string(dir.join("saved.ks")).unwrap();
+
+    // private functions of imported modules are not saved, since the module is imported again
+    assert!(saved.starts_with("import \""), "{}", saved);
+    assert!(!saved.contains("helper"), "{}", saved);
+    assert_eq!(saved.matches("def ").count(), 4, "{}", saved);
+
+    assert_eq!(repl(":load saved.ks\ng(2)\nf(1)\ndouble(4)\n"), [ "=> 10", "=> 15", "=> 8" ]);
+}
+
@@ -0,0 +1,117 @@
+use kaleidoscope::operator::{Associativity, Operator, OperatorTable};
+use kaleidoscope::parser::{Expr, Parser};
+use kaleidoscope::session::Session;
+use kaleidoscope::syntax;
+
+fn parse(input: &str, operators: &mut OperatorTable) -> Expr {
+    Parser::new(input.to_string(), operators).parse().unwrap().body.unwrap()
+}
+
+fn binary(op: char, left: Expr, right: Expr) -> Expr {
+    Expr::Binary { op, left: Box::new(left), right: Box::new(right) }
+}
+
+fn var(name: &str) -> Expr {
+    Expr::Variable(name.to_string())
+}
+
+fn num(nb: f64) -> Expr {
+    Expr::Number(nb)
+}
+
+#[test]
+fn builtin_operators() {
+    let mut operators = OperatorTable::new();
+
+    assert_eq!(operators.iter().map(|op| (op.op, op.precedence)).collect::<Vec<(char, i32)>>(), [
+        ('*', 40), ('+', 20), ('-', 20), ('/', 40), ('<', 10), ('=', 2), ('>', 10)
+    ]);
+    assert_eq!(operators.user_defined().count(), 0);
+    assert_eq!(operators.associativity('='), Associativity::Right);
+    assert_eq!(operators.associativity('-'), Associativity::Left);
+
+    // builtin operators are never removed
+    assert_eq!(operators.remove('+'), None);
+    assert_eq!(operators.precedence('+'), Some(20));
+
+    assert_eq!(OperatorTable::empty().iter().count(), 0);
+}
+
+#[test]
+fn user_defined_operators() {
+    let mut operators = OperatorTable::new();
+
+    operators.insert('|', 5, Associativity::Left);
+    operators.insert('^', 50, Associativity::Right);
+
+    assert_eq!(operators.get('^'), Some(&Operator { op: '^', precedence: 50, associativity: Associativity::Right, builtin: false }));
+    assert_eq!(operators.user_defined().map(|op| op.op).collect::<String>(), "^|");
+
+    // redefining an operator replaces it
+    operators.insert('|', 6, Associativity::Left);
+
+    assert_eq!(operators.precedence('|'), Some(6));
+    assert_eq!(operators.remove('|').map(|op| op.precedence), Some(6));
+    assert_eq!(operators.get('|'), None);
+
+    // unknown operators are left-associative
+    assert_eq!(operators.precedence('%'), None);
+    assert_eq!(operators.associativity('%'), Associativity::Left);
+}
+
+#[test]
+fn associativity() {
+    let mut operators = OperatorTable::new();
+
+    operators.insert('^', 50, Associativity::Right);
+
+    assert_eq!(parse("a - b - c", &mut operators), binary('-', binary('-', var("a"), var("b")), var("c")));
+    assert_eq!(parse("a ^ b ^ c", &mut operators), binary('^', var("a"), binary('^', var("b"), var("c"))));
+    assert_eq!(parse("a = b = c", &mut operators), binary('=', var("a"), binary('=', var("b"), var("c"))));
+
+    // the right operand of '=' still extends over the following assignment after '+'
+    assert_eq!(
+        parse("a = 1 + b = 2", &mut operators),
+        binary('=', var("a"), binary('=', binary('+', num(1.), var("b")), num(2.)))
+    );
+    assert_eq!(
+        parse("a ^ b * c ^ d", &mut operators),
+        binary('*', binary('^', var("a"), var("b")), binary('^', var("c"), var("d")))
+    );
+}
+
+#[test]
+fn builtin_operators_cannot_be_redefined() {
+    let mut session = Session::new();
+
+    for input in &[ "def binary= 9 (a, b) a", "def binary+ 20 (a, b) a" ] {
+        assert_eq!(session.parse(input), Err("Builtin operators cannot be redefined."));
+        assert_eq!(syntax::parse(input, session.operators()).errors()[0].error, "Builtin operators cannot be redefined.");
+    }
+
+    // functions which were not parsed by the session are checked as well
+    let mut function = session.parse("def binary| 9 (a, b) a").unwrap();
+
+    function.prototype.name = "binary=".to_string();
+
+    assert_eq!(session.define(function), Err("Builtin operators cannot be redefined."));
+    assert_eq!(session.operators().get('='), Some(&Operator { op: '=', precedence: 2, associativity: Associativity::Right, builtin: true }));
+    assert_eq!(session.parse("x = y = 1").unwrap().body, Some(binary('=', var("x"), binary('=', var("y"), num(1.)))));
+}
+
+#[test]
+fn tables_are_serializable() {
+    let mut session = Session::new();
+
+    session.define(session.parse("def binary| 5 (a, b) a").unwrap()).unwrap();
+
+    let json = serde_json::to_string(session.operators()).unwrap();
+    let restored: OperatorTable = serde_json::from_str(json.as_str()).unwrap();
+
+    assert_eq!(&restored, session.operators());
+
+    // a session restored from the table parses the operators of the previous one
+    let restored = Session::with_operators(restored);
+
+    assert_eq!(restored.parse("a < b | c").unwrap().body, Some(binary('|', binary('<', var("a"), var("b")), var("c"))));
+}
@@ -0,0 +1,101 @@
+use kaleidoscope::operator::OperatorTable;
+use kaleidoscope::optimizer::{optimize, OptimizeOptions};
+use kaleidoscope::parser::{Expr, Function, Parser};
+
+/// Parses the given inputs in order, sharing their operators.
+fn parse_all(inputs: &[&str]) -> Vec<Function> {
+    let mut operators = OperatorTable::new();
+
+    inputs.iter()
+        .map(|input| Parser::new(input.to_string(), &mut operators).parse().expect("Cannot parse test input."))
+        .collect()
+}
+
+/// Optimizes the last input, given the functions defined by the previous ones, and
+/// returns its printed body.
+fn optimized(inputs: &[&str], options: &OptimizeOptions) -> String {
+    let mut functions = parse_all(inputs);
+    let function = functions.pop().unwrap();
+
+    optimize(function, &functions, options).body.unwrap().to_string()
+}
+
+#[test]
+fn constants_are_folded() {
+    let options = OptimizeOptions::default();
+
+    assert_eq!(optimized(&["1 + 2 * 3 - 4 / 8"], &options), "6.5");
+    assert_eq!(optimized(&["def f(x) x * (2 - 1) + (3 < 4) * x"], &options), "x + x");
+    assert_eq!(optimized(&["def f(x) x - (1 - 1) / 1"], &options), "x");
+    assert_eq!(optimized(&["def f(x) x + 0"], &options), "x + 0");
+}
+
+#[test]
+fn comparisons_follow_compiled_semantics() {
+    let options = OptimizeOptions::default();
+
+    // comparisons are unordered, and thus true when an operand is NaN
+    assert_eq!(optimized(&["(0 / 0) < 1"], &options), "1");
+    assert_eq!(optimized(&["1 > (0 / 0)"], &options), "1");
+    assert_eq!(optimized(&["2 > 1"], &options), "1");
+    assert_eq!(optimized(&["1 < 1"], &options), "0");
+
+    // while conditions are ordered, and thus false for NaN
+    assert_eq!(optimized(&["if 0 / 0 then 1 else 2"], &options), "2");
+}
+
+#[test]
+fn constant_conditions_are_eliminated() {
+    let options = OptimizeOptions::default();
+
+    assert_eq!(optimized(&["def f(x) if 1 < 2 then x else f(x)"], &options), "x");
+    assert_eq!(optimized(&["def f(x) if 0 then x else if x then 1 else 2"], &options), "if x then 1 else 2");
+}
+
+#[test]
+fn trivial_operators_are_inlined() {
+    let options = OptimizeOptions::default();
+    let defs = ["def unary-(v) 0 - v", "def binary| 5 (a, b) if a then 1 else if b then 1 else 0"];
+
+    assert_eq!(optimized(&[defs[0], defs[1], "def f(x) x | 0"], &options), "if x then 1 else 0");
+    assert_eq!(optimized(&[defs[0], defs[1], "-2 | 0"], &options), "1");
+
+    // only numbers and variables are substituted, so that arguments are never duplicated
+    assert_eq!(optimized(&[defs[0], defs[1], "def f(x) -x | 0"], &options), "(0 - x) | 0");
+    assert_eq!(optimized(&[defs[0], defs[1], "def f(x) f(x) | x"], &options), "f(x) | x");
+
+    // operators are not inlined in their own redefinition
+    assert_eq!(optimized(&[defs[0], "def unary-(v) -v"], &options), "-v");
+
+    let options = OptimizeOptions { inline_operators: false, ..OptimizeOptions::default() };
+
+    assert_eq!(optimized(&[defs[0], "-2"], &options), "-2");
+}
+
+#[test]
+fn small_loops_are_unrolled() {
+    let input = "def f(x) for i = 0, i < 2 in x = x + i";
+
+    assert_eq!(optimized(&[input], &OptimizeOptions::default()), "for i = 0, i < 2 in x = x + i");
+
+    let options = OptimizeOptions { unroll_limit: 3, ..OptimizeOptions::default() };
+
+    // the body is evaluated before the end condition, hence three iterations
+    assert_eq!(optimized(&[input], &options), "var i = 0, i = x = x + i, i = 1, i = x = x + i, i = 2, i = x = x + i in 0");
+    assert_eq!(optimized(&["def f(x) for i = 0, i < 1, 0.5 in f(i)"], &options), "var i = 0, i = f(i), i = 0.5, i = f(i), i = 1, i = f(i) in 0");
+
+    // too many iterations, non-constant bounds or assignments to the loop variable
+    assert_eq!(optimized(&["def f(x) for i = 0, i < 3 in f(i)"], &options), "for i = 0, i < 3 in f(i)");
+    assert_eq!(optimized(&["def f(x) for i = 0, i < x in f(i)"], &options), "for i = 0, i < x in f(i)");
+    assert_eq!(optimized(&["def f(x) for i = 0, i < 2 in i = 3"], &options), "for i = 0, i < 2 in i = 3");
+}
+
+#[test]
+fn definitions_are_preserved() {
+    let mut functions = parse_all(&["def f(x) 1 + 1"]);
+    let function = functions.pop().unwrap();
+    let optimized = optimize(function.clone(), &functions, &OptimizeOptions::default());
+
+    assert_eq!(optimized.prototype, function.prototype);
+    assert_eq!(optimized.body, Some(Expr::Number(2.)));
+}
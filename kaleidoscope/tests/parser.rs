@@ -0,0 +1,188 @@
+use kaleidoscope::operator::OperatorTable;
+use kaleidoscope::parser::{Expr, Function, Import, Item, Location, Parser, Prototype};
+use kaleidoscope::session::Session;
+use kaleidoscope::syntax;
+
+fn parse(input: &str) -> Result<Function, &'static str> {
+    Parser::new(input.to_string(), &mut OperatorTable::new()).parse()
+}
+
+fn var(name: &str) -> Box<Expr> {
+    Box::new(Expr::Variable(name.to_string()))
+}
+
+fn num(nb: f64) -> Box<Expr> {
+    Box::new(Expr::Number(nb))
+}
+
+#[test]
+fn definitions_and_externs() {
+    let def = parse("## Doubles a number.\ndef double(x) x * 2").unwrap();
+
+    assert_eq!(def.prototype, Prototype { name: "double".to_string(), args: vec![ "x".to_string() ], is_op: false, prec: 0 });
+    assert_eq!(def.body, Some(Expr::Binary { op: '*', left: var("x"), right: num(2.) }));
+    assert_eq!(def.doc.as_deref(), Some("Doubles a number."));
+    assert!(!def.is_anon);
+
+    let ext = parse("extern sin(x)").unwrap();
+
+    assert_eq!(ext.prototype.name, "sin");
+    assert_eq!(ext.body, None);
+
+    let anon = parse("f()").unwrap();
+
+    assert!(anon.is_anon);
+    assert_eq!(anon.body, Some(Expr::Call { func_name: "f".to_string(), args: vec![] }));
+}
+
+#[test]
+fn precedence_and_associativity() {
+    assert_eq!(parse("a - b * c - d").unwrap().body, Some(Expr::Binary {
+        op: '-',
+        left: Box::new(Expr::Binary { op: '-', left: var("a"), right: Box::new(Expr::Binary { op: '*', left: var("b"), right: var("c") }) }),
+        right: var("d")
+    }));
+
+    assert_eq!(parse("a = b = 1").unwrap().body, Some(Expr::Binary {
+        op: '=',
+        left: var("a"),
+        right: Box::new(Expr::Binary { op: '=', left: var("b"), right: num(1.) })
+    }));
+}
+
+#[test]
+fn user_defined_operators() {
+    let mut operators = OperatorTable::new();
+    let def = Parser::new("def binary| 5 (a, b) a".to_string(), &mut operators).parse().unwrap();
+
+    assert_eq!(def.prototype.binary_operator(), Some('|'));
+    assert_eq!(def.prototype.prec, 5);
+    assert_eq!(operators.precedence('|'), Some(5));
+
+    // '|' binds less tightly than '<'
+    let expr = Parser::new("a < b | !c".to_string(), &mut operators).parse().unwrap();
+
+    assert_eq!(expr.body, Some(Expr::Binary {
+        op: '|',
+        left: Box::new(Expr::Binary { op: '<', left: var("a"), right: var("b") }),
+        right: Box::new(Expr::Call { func_name: "unary!".to_string(), args: vec![ Expr::Variable("c".to_string()) ] })
+    }));
+}
+
+#[test]
+fn control_flow() {
+    assert_eq!(parse("for i = 0, i < n, 2 in f(i)").unwrap().body, Some(Expr::For {
+        var_name: "i".to_string(),
+        start: num(0.),
+        end: Box::new(Expr::Binary { op: '<', left: var("i"), right: var("n") }),
+        step: Some(num(2.)),
+        body: Box::new(Expr::Call { func_name: "f".to_string(), args: vec![ Expr::Variable("i".to_string()) ] })
+    }));
+
+    assert_eq!(parse("var a = 1, b in if a then b else 0").unwrap().body, Some(Expr::VarIn {
+        variables: vec![ ("a".to_string(), Some(Expr::Number(1.))), ("b".to_string(), None) ],
+        body: Box::new(Expr::Conditional { cond: var("a"), consequence: var("b"), alternative: num(0.) })
+    }));
+}
+
+/// Returns the line and column of the definition of the given function, followed by those
+/// of its expressions.
+fn locations_of(fun: &Function) -> (Location, Vec<(usize, usize)>) {
+    let locations = fun.locations.as_ref().expect("Expected locations.");
+
+    (locations.definition, locations.expressions.iter().map(|location| (location.line, location.column)).collect())
+}
+
+#[test]
+fn locations() {
+    let def = parse("# Doc.\ndef f(x)\n  var a = x in\n    a * -f(a + 1)").unwrap();
+
+    assert_eq!(locations_of(&def), (
+        Location { line: 2, column: 1 },
+        vec![ (3, 3), (3, 11), (4, 7), (4, 5), (4, 9), (4, 10), (4, 14), (4, 12), (4, 16) ]
+    ));
+
+    // binary expressions precede their operands, whatever their precedence
+    let anon = parse("a - b * c - d").unwrap();
+
+    assert_eq!(locations_of(&anon), (
+        Location { line: 1, column: 1 },
+        vec![ (1, 11), (1, 3), (1, 1), (1, 7), (1, 5), (1, 9), (1, 13) ]
+    ));
+
+    // functions are equal regardless of their locations
+    assert_eq!(parse("\n\n  a - b * c - d").unwrap(), anon);
+}
+
+#[test]
+fn imports_and_exports() {
+    let parse_item = |input: &str| Parser::new(input.to_string(), &mut OperatorTable::new()).parse_item();
+
+    assert_eq!(parse_item("import \"lib/math.ks\""), Ok(Item::Import(Import::Path("lib/math.ks".to_string()))));
+    assert_eq!(parse_item("import math"), Ok(Item::Import(Import::Module("math".to_string()))));
+    assert_eq!(parse_item("import 1"), Err("Expected module name or path after 'import'."));
+    assert_eq!(parse_item("import math x"), Err("Unexpected token after import declaration."));
+    assert_eq!(parse_item("export 1"), Err("Expected 'def' or 'extern' after 'export'."));
+
+    assert_eq!(parse_item("## Doc.\nexport def f(x) x"), Ok(Item::Function { function: parse("## Doc.\ndef f(x) x").unwrap(), exported: true }));
+    assert_eq!(parse_item("extern f(x)"), Ok(Item::Function { function: parse("extern f(x)").unwrap(), exported: false }));
+}
+
+#[test]
+fn syntax_errors() {
+    assert!(parse("def (x) x").is_err());
+    assert!(parse("def f(x y) x").is_err());
+    assert!(parse("if x then y").is_err());
+    assert!(parse("(1 + 2").is_err());
+
+    // the input must contain a single item, even if the following ones are invalid
+    assert_eq!(parse("1 + 2)"), Err("Unexpected token after parsed expression."));
+    assert_eq!(parse("def f(x) x\ndef g(y) y"), Err("Unexpected token after parsed expression."));
+    assert_eq!(parse("def f(x x\ndef g(y) y"), Err("Expected ',' or ')' character in prototype declaration."));
+}
+
+#[test]
+fn parser_derives_the_ast_from_the_syntax_tree() {
+    let operators = OperatorTable::new();
+
+    for input in &[ "## Doc.\ndef f(x) x + 1 # c", "#[ block ]#\n## Sine.\nextern sin(x)", "var a = 1, b in for i = 0, i < a in (b)" ] {
+        let functions = syntax::parse(input, &operators).functions().unwrap();
+
+        assert_eq!(functions, [ parse(input).unwrap() ], "{}", input);
+    }
+}
+
+#[test]
+fn incomplete_inputs() {
+    let session = Session::new();
+
+    assert!(session.is_incomplete("def f(x)"));
+    assert!(session.is_incomplete("def f(x) if x then"));
+    assert!(session.is_incomplete("(1 + 2) * (3 +\n"));
+    assert!(session.is_incomplete("foo(1,\n  (2"));
+    assert!(!session.is_incomplete("def f(x) x"));
+    assert!(!session.is_incomplete("1 + 2)"));
+    assert!(!session.is_incomplete("   "));
+    assert!(!session.is_incomplete("# comment"));
+
+    // dangling prototypes, which are reported as invalid by the parser
+    assert!(session.is_incomplete("extern"));
+    assert!(session.is_incomplete("def"));
+    assert!(session.is_incomplete("export def\n"));
+    assert!(session.is_incomplete("def f"));
+    assert!(session.is_incomplete("extern sin(x,"));
+    assert!(session.is_incomplete("def binary| 5"));
+    assert!(!session.is_incomplete("def (x) x"));
+    assert!(!session.is_incomplete("extern 1"));
+
+    // unterminated block comments, which are lexer errors
+    assert!(session.is_incomplete("#["));
+    assert!(session.is_incomplete("def f(x) x #[ unterminated"));
+    assert!(session.is_incomplete("#[ a #[ b ]#\n"));
+    assert!(!session.is_incomplete("#[ a ]# 1"));
+    assert!(!session.is_incomplete("import \"unterminated"));
+
+    // doc comments document the definition on the next line
+    assert!(session.is_incomplete("## Doubles."));
+    assert!(!session.is_incomplete("## Doubles.\ndef double(x) x * 2"));
+}
@@ -0,0 +1,142 @@
+use kaleidoscope::operator::{Associativity, OperatorTable};
+use kaleidoscope::parser::{Expr, Function, Parser, Prototype};
+use proptest::prelude::*;
+
+const KEYWORDS: &[&str] = &["binary", "def", "else", "extern", "for", "if", "in", "then", "unary", "var"];
+
+/// Returns the operators used by the tests: the builtin ones, a user-defined `|` with a lower
+/// precedence than `<`, and a right-associative `^`.
+fn operators() -> OperatorTable {
+    let mut operators = OperatorTable::new();
+
+    operators.insert('|', 5, Associativity::Left);
+    operators.insert('^', 50, Associativity::Right);
+    operators
+}
+
+fn parse(input: &str) -> Result<Function, &'static str> {
+    Parser::new(input.to_string(), &mut operators()).parse()
+}
+
+fn ident() -> impl Strategy<Value = String> {
+    "[a-z][a-z0-9]{0,4}".prop_filter("Identifier must not be a keyword.", |id| !KEYWORDS.contains(&id.as_str()))
+}
+
+fn number() -> impl Strategy<Value = f64> {
+    prop_oneof![ (0..1000u32).prop_map(f64::from), (0..1000u32).prop_map(|nb| f64::from(nb) / 8.) ]
+}
+
+fn expr() -> impl Strategy<Value = Expr> {
+    let leaf = prop_oneof![ number().prop_map(Expr::Number), ident().prop_map(Expr::Variable) ];
+
+    leaf.prop_recursive(5, 48, 3, |inner| {
+        prop_oneof![
+            (prop::sample::select(vec!['=', '<', '>', '+', '-', '*', '/', '|', '^', '&']), inner.clone(), inner.clone())
+                .prop_map(|(op, left, right)| Expr::Binary { op, left: Box::new(left), right: Box::new(right) }),
+
+            (prop::sample::select(vec!['!', '-']), inner.clone())
+                .prop_map(|(op, arg)| Expr::Call { func_name: format!("unary{}", op), args: vec![ arg ] }),
+
+            (ident(), prop::collection::vec(inner.clone(), 0..3))
+                .prop_map(|(func_name, args)| Expr::Call { func_name, args }),
+
+            (inner.clone(), inner.clone(), inner.clone())
+                .prop_map(|(cond, consequence, alternative)| Expr::Conditional {
+                    cond: Box::new(cond),
+                    consequence: Box::new(consequence),
+                    alternative: Box::new(alternative)
+                }),
+
+            (ident(), inner.clone(), inner.clone(), prop::option::of(inner.clone()), inner.clone())
+                .prop_map(|(var_name, start, end, step, body)| Expr::For {
+                    var_name,
+                    start: Box::new(start),
+                    end: Box::new(end),
+                    step: step.map(Box::new),
+                    body: Box::new(body)
+                }),
+
+            (prop::collection::vec((ident(), prop::option::of(inner.clone())), 1..3), inner)
+                .prop_map(|(variables, body)| Expr::VarIn { variables, body: Box::new(body) })
+        ]
+    })
+}
+
+fn prototype() -> impl Strategy<Value = Prototype> {
+    prop_oneof![
+        (ident(), prop::collection::vec(ident(), 0..3))
+            .prop_map(|(name, args)| Prototype { name, args, is_op: false, prec: 0 }),
+
+        (ident(), ident(), 0..100usize)
+            .prop_map(|(left, right, prec)| Prototype { name: "binary|".to_string(), args: vec![ left, right ], is_op: true, prec }),
+
+        ident().prop_map(|arg| Prototype { name: "unary!".to_string(), args: vec![ arg ], is_op: true, prec: 0 })
+    ]
+}
+
+proptest! {
+    #[test]
+    fn expressions_round_trip(expr in expr()) {
+        let printed = expr.pretty(&operators()).to_string();
+        let parsed = parse(&printed).unwrap_or_else(|err| panic!("Cannot parse '{}': {}", printed, err));
+
+        prop_assert_eq!(parsed.body, Some(expr), "{}", printed);
+    }
+
+    #[test]
+    fn definitions_round_trip(prototype in prototype(), body in expr(), doc in prop::option::of("[a-z ]{0,10}(\n[a-z ]{0,10})?")) {
+        let doc = doc.map(|doc| doc.split('\n').map(str::trim_end).collect::<Vec<_>>().join("\n"));
+        let function = Function { prototype, body: Some(body), is_anon: false, doc, locations: None };
+        let printed = function.pretty(&operators()).to_string();
+        let parsed = parse(&printed).unwrap_or_else(|err| panic!("Cannot parse '{}': {}", printed, err));
+
+        prop_assert_eq!(parsed, function, "{}", printed);
+    }
+
+    #[test]
+    fn externs_round_trip(prototype in prototype()) {
+        let function = Function { prototype, body: None, is_anon: false, doc: None, locations: None };
+        let printed = function.pretty(&operators()).to_string();
+
+        prop_assert_eq!(parse(&printed), Ok(function), "{}", printed);
+    }
+}
+
+#[test]
+fn parentheses_are_minimal() {
+    let cases = [
+        "a + b * c",
+        "(a + b) * c",
+        "a - (b - c)",
+        "a - b - c",
+        "a = b = c",
+        "(a = b) = c",
+        "a ^ b ^ c",
+        "(a ^ b) ^ c",
+        "a < b | c < d",
+        "(a | b) < c",
+        "!(a + b) * -c",
+        "(if a then b else c) + f(a + b, g())",
+        "a + if a then b else c",
+        "var x = 1, y in for i = 0, i < x in y = y + i"
+    ];
+
+    for case in cases.iter() {
+        let function = parse(case).expect("Cannot parse test case.");
+
+        assert_eq!(function.body.unwrap().pretty(&operators()).to_string(), *case);
+    }
+}
+
+#[test]
+fn display_uses_builtin_operators() {
+    let proto = Prototype { name: "binary|".to_string(), args: vec![ "a".to_string(), "b".to_string() ], is_op: true, prec: 5 };
+    let expr = Expr::Binary {
+        op: '*',
+        left: Box::new(Expr::Number(-1.)),
+        right: Box::new(Expr::Binary { op: '+', left: Box::new(Expr::Number(2.5)), right: Box::new(Expr::Variable("x".to_string())) })
+    };
+
+    assert_eq!(proto.to_string(), "binary| 5 (a, b)");
+    assert_eq!(expr.to_string(), "(0 - 1) * (2.5 + x)");
+}
@@ -0,0 +1,79 @@
+//! Inputs which crashed the lexer, parser, printer or compiler, as minimized by the fuzz
+//! targets of the `fuzz` directory.
+
+use kaleidoscope::operator::{Associativity, OperatorTable};
+use kaleidoscope::parser::{Expr, Function, Parser};
+use kaleidoscope::syntax;
+
+fn parse(input: &str) -> Result<Function, &'static str> {
+    Parser::new(input.to_string(), &mut OperatorTable::new()).parse()
+}
+
+#[test]
+fn truncated_inputs_are_errors() {
+    let cases = [
+        ("def", "Unexpected end of file."),
+        ("extern", "Unexpected end of file."),
+        ("def f", "Unexpected end of file."),
+        ("def binary", "Unexpected end of file."),
+        ("var", "Unexpected end of file."),
+        ("var a = 1", "Unexpected end of file."),
+        ("(1", "Unexpected end of file."),
+        ("f(1,", "Unexpected end of file."),
+        ("for i = 0, i < 1 in", "Unexpected end of file."),
+        ("1 +", "Unexpected end of file."),
+        ("!", "Unexpected end of file.")
+    ];
+
+    for (input, error) in cases.iter() {
+        assert_eq!(parse(input), Err(*error), "{}", input);
+    }
+}
+
+#[test]
+fn invalid_numbers_are_errors() {
+    for input in [ "1.2.3", "1..", ".", "def f(x) x + 2.." ].iter() {
+        assert_eq!(parse(input), Err("Invalid number literal."), "{}", input);
+        assert!(syntax::parse(input, &OperatorTable::new()).functions().is_err(), "{}", input);
+    }
+
+    // hexadecimal digits are not part of numbers
+    assert_eq!(parse("1a"), Err("Unexpected token after parsed expression."));
+}
+
+#[test]
+fn assignments_mixed_with_operators_of_the_same_precedence() {
+    let mut operators = OperatorTable::new();
+
+    operators.insert('|', 2, Associativity::Left);
+
+    for input in [ "(l = 0) | 0", "l = 0 | 0", "l = 0 + 0 | 0", "l = l = 0 + 0 | 0" ].iter() {
+        let function = Parser::new(input.to_string(), &mut operators.clone()).parse().expect("Cannot parse test case.");
+        let lowered = syntax::parse(input, &operators).functions().expect("Cannot parse test case.");
+
+        assert_eq!(lowered.as_slice(), std::slice::from_ref(&function), "{}", input);
+        assert_eq!(function.body.unwrap().pretty(&operators).to_string(), *input);
+    }
+
+    // the assignment takes the whole expression, as it does without the addition
+    match Parser::new("l = 0 + 0 | 0".to_string(), &mut operators).parse().unwrap().body {
+        Some(Expr::Binary { op: '=', right, .. }) => assert!(matches!(*right, Expr::Binary { op: '|', .. })),
+        body => panic!("Unexpected body {:?}.", body)
+    }
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn calls_with_the_wrong_number_of_arguments_are_errors() {
+    use inkwell::OptimizationLevel;
+    use inkwell::context::Context;
+    use kaleidoscope::backend::{Backend, BackendError};
+    use kaleidoscope::jit::Jit;
+
+    let context = Context::create();
+    let mut jit = Jit::new(&context, OptimizationLevel::None);
+
+    assert_eq!(jit.eval(&parse("def f(a) a").unwrap()), Ok(None));
+    assert_eq!(jit.eval(&parse("f(1, 2)").unwrap()), Err(BackendError::Compilation("Incorrect number of arguments passed.")));
+    assert_eq!(jit.eval(&parse("f()").unwrap()), Err(BackendError::Compilation("Incorrect number of arguments passed.")));
+}
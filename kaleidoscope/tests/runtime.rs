@@ -0,0 +1,114 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use kaleidoscope::backend::{Backend, BackendError};
+use kaleidoscope::format::{self, FormatOptions};
+use kaleidoscope::interp::Interpreter;
+use kaleidoscope::operator::OperatorTable;
+use kaleidoscope::runtime;
+use kaleidoscope::session::Session;
+
+/// Evaluates the given input in a session where the builtins are declared.
+fn eval(backend: &mut dyn Backend, input: &str) -> Result<Option<f64>, BackendError> {
+    let mut session = Session::new();
+
+    runtime::declare(&mut session, backend)?;
+
+    let function = session.parse(input).expect("Cannot parse test input.");
+
+    backend.eval(&function)
+}
+
+#[test]
+fn builtins_are_declared() {
+    let mut session = Session::new();
+
+    runtime::declare(&mut session, &mut Interpreter::new()).unwrap();
+
+    for builtin in runtime::BUILTINS {
+        let function = session.get_function(builtin.name).expect("Builtin not declared.");
+
+        assert!(function.body.is_none());
+        assert_eq!(function.prototype.args.len(), builtin.function.arity());
+    }
+}
+
+#[test]
+fn math_builtins() {
+    let mut interpreter = Interpreter::new();
+
+    assert_eq!(eval(&mut interpreter, "sqrt(16) + pow(2, 10)"), Ok(Some(1028.)));
+    assert_eq!(eval(&mut interpreter, "floor(0 - 1.5) + abs(0 - 3)"), Ok(Some(1.)));
+    assert_eq!(eval(&mut interpreter, "sin(0) + cos(0)"), Ok(Some(1.)));
+    assert_eq!(eval(&mut interpreter, "sin(1, 2)"), Err(BackendError::Compilation("Incorrect number of arguments passed.")));
+}
+
+#[test]
+fn random_numbers_are_in_the_unit_interval() {
+    let mut interpreter = Interpreter::new();
+
+    for _ in 0..100 {
+        let result = eval(&mut interpreter, "random()").unwrap().unwrap();
+
+        assert!((0. ..1.).contains(&result));
+    }
+
+    assert!(eval(&mut interpreter, "clock()").unwrap().unwrap() > 0.);
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn builtins_are_called_by_compiled_code() {
+    use inkwell::OptimizationLevel;
+    use inkwell::context::Context;
+    use kaleidoscope::jit::Jit;
+
+    let context = Context::create();
+    let mut jit = Jit::new(&context, OptimizationLevel::Default);
+
+    assert_eq!(eval(&mut jit, "sqrt(16) + pow(2, 10)"), Ok(Some(1028.)));
+    assert_eq!(eval(&mut jit, "floor(0 - 1.5) + abs(0 - 3)"), Ok(Some(1.)));
+}
+
+#[test]
+fn prelude_defines_operators() {
+    let mut interpreter = Interpreter::new();
+    let mut session = Session::new();
+
+    runtime::load_prelude(&mut session, &mut interpreter).unwrap();
+
+    let precedences = session.operators().user_defined().map(|op| (op.op, op.precedence)).collect::<Vec<(char, i32)>>();
+
+    assert_eq!(precedences, [ ('&', 6), (':', 1), ('|', 5), ('~', 9) ]);
+
+    for (input, expected) in [ ("(-(1 + 2)) * -3", 9.), ("(!0) + !1 + !!5", 2.), ("1 | 0 & 0", 1.), ("1 < 2 ~ 2 > 1", 1.), ("var a = 5 in a = 3 ~ 3", 1.), ("1 : 2 : 3", 3.) ].iter() {
+        let function = session.parse(input).expect("Cannot parse test input.");
+
+        assert_eq!(interpreter.eval(&function), Ok(Some(*expected)), "{}", input);
+    }
+}
+
+#[test]
+fn prelude_is_formatted() {
+    let formatted = format::format(runtime::PRELUDE, &OperatorTable::new(), &FormatOptions::default()).ok();
+
+    assert_eq!(formatted.as_deref(), Some(runtime::PRELUDE));
+}
+
+#[test]
+fn documentation_is_shown_by_repl_help() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_kaleido"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Cannot run kaleido.");
+
+    child.stdin.take().unwrap().write_all(b":help binary|\n## Doubles,\n##\n## twice.\ndef double(x) x * 2\n:help double\n:help sin\n:help nope\n").unwrap();
+
+    let output = String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap();
+
+    assert!(output.contains("## Logical or.\ndef binary| 5 (a, b)\n"), "{}", output);
+    assert!(output.contains("## Doubles,\n##\n## twice.\ndef double(x)\n"), "{}", output);
+    assert!(output.contains("extern sin(x)\n"), "{}", output);
+    assert!(output.contains("!> Unknown function 'nope'."), "{}", output);
+}
+
@@ -0,0 +1,152 @@
+use std::ffi::OsStr;
+use std::fs;
+use kaleidoscope::lexer::Span;
+use kaleidoscope::operator::OperatorTable;
+use kaleidoscope::runtime;
+use kaleidoscope::syntax::{self, SyntaxElement, SyntaxError, SyntaxKind, SyntaxNode, SyntaxTree};
+
+/// Inputs with comments and syntax errors, which must be kept as they are by the tree.
+const INPUTS: &[&str] = &[
+    "",
+    "  \n\t",
+    "## Doc\ndef f(x) x + 1 # c\nf(2)",
+    "#[ nested #[ block ]# ]#\n## Sine.\nextern sin(x)\n",
+    "def binary| 5 (a, b) a\n1 | 2 )",
+    "def f(x x + 1\ndef g(y) y * 2\n1 +\ng(3)",
+    "def f(x) x #[ unterminated",
+    "import \"unterminated",
+    "if then else )) ( ,, def",
+    "var a = 1, in for i = 0, i < 10 in\n",
+    "export 1 + é"
+];
+
+fn parse(input: &str) -> SyntaxTree {
+    syntax::parse(input, &OperatorTable::new())
+}
+
+/// Checks that the children of the given node cover its span without any gap, recursively,
+/// and that the text of each token is the text of the source at its span.
+fn check_spans(node: SyntaxNode<'_>, source: &str) {
+    let mut offset = node.span().start;
+
+    for child in node.children_with_tokens() {
+        let span = child.span();
+
+        assert_eq!(span.start, offset, "{:?} in {:?}", child, source);
+        offset = span.end;
+
+        match child {
+            SyntaxElement::Node(child) => check_spans(child, source),
+            SyntaxElement::Token(token) => assert_eq!(token.text(), &source[span.start..span.end])
+        }
+    }
+
+    assert_eq!(offset, node.span().end);
+}
+
+/// Returns the kind and text of the children of the root of the given tree.
+fn children(tree: &SyntaxTree) -> Vec<(SyntaxKind, String)> {
+    tree.root().children_with_tokens()
+        .map(|child| match child {
+            SyntaxElement::Node(node) => (node.kind(), node.text()),
+            SyntaxElement::Token(token) => (token.kind(), token.text().to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn trees_are_lossless() {
+    let programs = fs::read_dir("tests/programs").expect("Cannot read programs.")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension() == Some(OsStr::new("ks")))
+        .map(|path| fs::read_to_string(path).unwrap())
+        .collect::<Vec<String>>();
+
+    assert!(!programs.is_empty());
+
+    for program in programs.iter().map(String::as_str).chain(std::iter::once(runtime::PRELUDE)) {
+        let tree = parse(program);
+
+        assert_eq!(tree.errors(), [], "{}", program);
+        assert_eq!(tree.text(), program);
+        check_spans(tree.root(), program);
+    }
+
+    for input in INPUTS {
+        let tree = parse(input);
+
+        assert_eq!(tree.text(), *input);
+        assert_eq!(tree.root().span(), Span::new(0, input.len()));
+        check_spans(tree.root(), input);
+    }
+}
+
+#[test]
+fn nodes_and_spans() {
+    let tree = parse("## Doc\ndef f(x) x + 1 # c\nf(2)");
+
+    // trivia preceding a definition belongs to it, while trailing trivia belongs to the source
+    assert_eq!(children(&tree), [
+        (SyntaxKind::Definition, "## Doc\ndef f(x) x + 1".to_string()),
+        (SyntaxKind::Whitespace, " ".to_string()),
+        (SyntaxKind::Comment, "# c".to_string()),
+        (SyntaxKind::Whitespace, "\n".to_string()),
+        (SyntaxKind::TopLevelExpr, "f(2)".to_string())
+    ]);
+
+    let definition = tree.root().child_node(SyntaxKind::Definition).unwrap();
+    let prototype = definition.child_node(SyntaxKind::Prototype).unwrap();
+    let body = definition.child_exprs().next().unwrap();
+
+    assert_eq!(prototype.span(), Span::new(11, 15));
+    assert_eq!(prototype.child_token(SyntaxKind::Ident).map(|name| name.text()), Some("f"));
+    assert_eq!(body.kind(), SyntaxKind::BinaryExpr);
+    assert_eq!(body.span(), Span::new(16, 21));
+    assert_eq!(body.child_exprs().map(|operand| operand.kind()).collect::<Vec<SyntaxKind>>(), [
+        SyntaxKind::VariableExpr, SyntaxKind::NumberExpr
+    ]);
+    assert_eq!(body.child_token(SyntaxKind::Op).map(|op| op.span()), Some(Span::new(18, 19)));
+}
+
+#[test]
+fn errors_are_recovered() {
+    let tree = parse("def f(x x + 1\ndef g(y) y * 2\n1 +\ng(3)");
+
+    assert_eq!(tree.errors(), [
+        SyntaxError { error: "Expected ',' or ')' character in prototype declaration.", span: Span::new(8, 9) }
+    ]);
+    assert_eq!(tree.items().err(), Some("Expected ',' or ')' character in prototype declaration."));
+
+    // parsing resumes at the next definition, and the skipped tokens are kept in an error node
+    let nodes = tree.root().children().map(|node| (node.kind(), node.text())).collect::<Vec<(SyntaxKind, String)>>();
+
+    assert_eq!(nodes, [
+        (SyntaxKind::Definition, "def f(x".to_string()),
+        (SyntaxKind::Error, "x + 1".to_string()),
+        (SyntaxKind::Definition, "def g(y) y * 2".to_string()),
+        (SyntaxKind::TopLevelExpr, "1 +\ng(3)".to_string())
+    ]);
+
+    // lexer errors are reported, and the rest of the input is kept as an error token
+    let tree = parse("def f(x) x #[ unterminated");
+
+    assert_eq!(tree.errors().iter().map(|err| err.error).collect::<Vec<&str>>(), [ "Unterminated block comment." ]);
+    assert_eq!(tree.root().descendant_tokens().last().map(|token| (token.kind(), token.text())), Some((SyntaxKind::Error, "#[ unterminated")));
+}
+
+#[test]
+fn trees_are_lowered_to_the_ast() {
+    let tree = parse("def binary| 5 (a, b) a\n## Sine.\nextern sin(x)\n1 | sin(2)");
+    let functions = tree.functions().unwrap();
+
+    assert_eq!(functions.iter().map(|fun| fun.prototype.name.as_str()).collect::<Vec<&str>>(), [ "binary|", "sin", "anonymous" ]);
+    assert_eq!(functions[1].doc.as_deref(), Some("Sine."));
+    assert!(functions[2].is_anon);
+
+    // the operators defined by the source are not added to the given table
+    let operators = OperatorTable::new();
+
+    syntax::parse("def binary| 5 (a, b) a", &operators);
+
+    assert_eq!(operators.precedence('|'), None);
+}
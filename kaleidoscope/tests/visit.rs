@@ -0,0 +1,81 @@
+use kaleidoscope::operator::OperatorTable;
+use kaleidoscope::parser::{Expr, Function, Parser};
+use kaleidoscope::visit::{self, Fold, Visitor, VisitorMut};
+
+fn parse(input: &str) -> Function {
+    Parser::new(input.to_string(), &mut OperatorTable::new()).parse().expect("Cannot parse test input.")
+}
+
+/// Collects the names of the called functions, in source order (callees after their arguments).
+struct Calls(Vec<String>);
+
+impl Visitor for Calls {
+    fn visit_expr(&mut self, expr: &Expr) {
+        visit::walk_expr(self, expr);
+
+        if let Expr::Call { func_name, .. } = expr {
+            self.0.push(func_name.clone());
+        }
+    }
+}
+
+/// Renames a variable everywhere it appears.
+struct Rename<'a>(&'a str, &'a str);
+
+impl<'a> VisitorMut for Rename<'a> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Variable(name) if name == self.0 => *name = self.1.to_string(),
+            Expr::For { var_name, .. } if var_name == self.0 => *var_name = self.1.to_string(),
+            Expr::VarIn { variables, .. } => {
+                for (name, _) in variables.iter_mut().filter(|(name, _)| name == self.0) {
+                    *name = self.1.to_string();
+                }
+            },
+            _ => ()
+        }
+
+        visit::walk_expr_mut(self, expr);
+    }
+}
+
+/// Replaces additions of two numbers by their result.
+struct FoldAdditions;
+
+impl Fold for FoldAdditions {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        match visit::fold_expr(self, expr) {
+            Expr::Binary { op: '+', left, right } => match (*left, *right) {
+                (Expr::Number(left), Expr::Number(right)) => Expr::Number(left + right),
+                (left, right) => Expr::Binary { op: '+', left: Box::new(left), right: Box::new(right) }
+            },
+            expr => expr
+        }
+    }
+}
+
+#[test]
+fn visitor_walks_in_source_order() {
+    let mut calls = Calls(Vec::new());
+
+    // the body of the loop is evaluated before its end condition and step, but follows them
+    calls.visit_function(&parse("def f(x) var a = g(x) in if h() then i(a, j()) else for n = 0, k(), m() in l()"));
+
+    assert_eq!(calls.0, ["g", "h", "j", "i", "k", "m", "l"]);
+}
+
+#[test]
+fn mutable_visitor_modifies_in_place() {
+    let mut fun = parse("def f(x) var a = x in for i = a, x in a + f(i)");
+
+    Rename("a", "b").visit_function_mut(&mut fun);
+
+    assert_eq!(fun, parse("def f(x) var b = x in for i = b, x in b + f(i)"));
+}
+
+#[test]
+fn fold_rebuilds_the_tree() {
+    let fun = FoldAdditions.fold_function(parse("def f(x) if 1 + 2 then x + (3 + 4) * 5 else f(6 + 7 + 8)"));
+
+    assert_eq!(fun, parse("def f(x) if 3 then x + 7 * 5 else f(21)"));
+}